@@ -0,0 +1,411 @@
+use std::fmt;
+
+use crate::chunk_type::ChunkType;
+use crate::error::Location;
+use crate::ihdr::ColorType;
+use crate::lenient::Recovery;
+use crate::png::Png;
+use crate::reader::ParseOptions;
+use crate::statistics::{chi_square, rs_analysis};
+use crate::validate::KNOWN_CHUNKS;
+use crate::zlib::stream_len;
+use crate::Result;
+
+/// Data length above which ancillary chunk without fixed size is suspicious,
+/// if it is also larger than all image data
+const LARGE_CHUNK: usize = 4096;
+
+/// Largest data length of ancillary chunks with fixed layout
+const FIXED_SIZE_CHUNKS: [(&[u8; 4], usize); 10] = [
+    (b"cHRM", 32),
+    (b"cICP", 4),
+    (b"gAMA", 4),
+    (b"mDCV", 24),
+    (b"cLLI", 8),
+    (b"sBIT", 4),
+    (b"sRGB", 1),
+    (b"bKGD", 6),
+    (b"pHYs", 9),
+    (b"tIME", 7),
+];
+
+/// Property of png which suggests it carries hidden data
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub code: &'static str,
+    /// Suspicion from 0 (innocent) to 1 (certainly hidden data)
+    pub score: f64,
+    pub location: Option<Location>,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:3.0}% [{}]", self.score * 100.0, self.code)?;
+        if let Some(location) = self.location {
+            write!(f, " chunk {} at offset {}", location.index, location.offset)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Report of [`Png::analyze`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub findings: Vec<Finding>,
+}
+
+impl Analysis {
+    /// Overall suspicion from 0 to 1, probability that at least one finding is right
+    /// if they were independent
+    pub fn score(&self) -> f64 {
+        1.0 - self
+            .findings
+            .iter()
+            .map(|finding| 1.0 - finding.score)
+            .product::<f64>()
+    }
+}
+
+struct Analyzer<'a> {
+    bytes: &'a [u8],
+    options: ParseOptions,
+    png: Png,
+    offsets: Vec<usize>,
+    findings: Vec<Finding>,
+}
+
+impl Analyzer<'_> {
+    fn report(&mut self, code: &'static str, score: f64, index: Option<usize>, message: String) {
+        let location = index.map(|index| Location {
+            index,
+            offset: self.offsets[index],
+        });
+        self.findings.push(Finding {
+            code,
+            score,
+            location,
+            message,
+        });
+    }
+
+    fn check_chunk_types(&mut self) {
+        for index in 0..self.png.chunks().len() {
+            let chunk_type = *self.png.chunks()[index].chunk_type();
+            if KNOWN_CHUNKS.contains(&&chunk_type.bytes()) {
+                continue;
+            }
+
+            if chunk_type.is_public() {
+                self.report(
+                    "unknown-chunk",
+                    0.4,
+                    Some(index),
+                    format!("public chunk type {} is not registered", chunk_type),
+                );
+            } else {
+                self.report(
+                    "unknown-private-chunk",
+                    0.7,
+                    Some(index),
+                    format!(
+                        "private {} chunk {} with {} bytes of data",
+                        if chunk_type.is_critical() {
+                            "critical"
+                        } else {
+                            "ancillary"
+                        },
+                        chunk_type,
+                        self.png.chunks()[index].length()
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Chunks or other bytes after the first IEND
+    fn check_after_iend(&mut self) {
+        let chunks = self.png.chunks();
+        let Some(iend) = chunks
+            .iter()
+            .position(|chunk| *chunk.chunk_type() == ChunkType::IEND)
+        else {
+            return;
+        };
+        let end = self.offsets[iend] + chunks[iend].bytes_len();
+        if end < self.bytes.len() {
+            let message = format!(
+                "{} bytes ({} chunks) follow IEND",
+                self.bytes.len() - end,
+                chunks.len() - iend - 1
+            );
+            self.report("after-iend", 0.9, Some(iend), message);
+        }
+    }
+
+    fn check_chunk_sizes(&mut self) {
+        let image_data = self.png.image_data().len();
+        for index in 0..self.png.chunks().len() {
+            let chunk = &self.png.chunks()[index];
+            let chunk_type = chunk.chunk_type();
+            let len = chunk.length() as usize;
+            if chunk_type.is_critical() {
+                continue;
+            }
+
+            let fixed = FIXED_SIZE_CHUNKS
+                .iter()
+                .find(|(bytes, _)| **bytes == chunk_type.bytes());
+            let message = match fixed {
+                Some((_, max)) if len > *max => format!(
+                    "{} has {} bytes of data, but at most {} are expected",
+                    chunk_type, len, max
+                ),
+                None if len > LARGE_CHUNK && len > image_data => format!(
+                    "{} has {} bytes of data, more than {} bytes of image data",
+                    chunk_type, len, image_data
+                ),
+                _ => continue,
+            };
+            self.report("oversized-chunk", 0.6, Some(index), message);
+        }
+    }
+
+    /// Bytes following the end of zlib stream in IDAT chunks
+    fn check_idat_trailer(&mut self) {
+        let Some(first_idat) = self
+            .png
+            .chunks()
+            .iter()
+            .position(|chunk| *chunk.chunk_type() == ChunkType::IDAT)
+        else {
+            return;
+        };
+        let data = self.png.image_data();
+        match stream_len(&data, self.options.max_decompressed_size) {
+            Some(len) if len < data.len() => self.report(
+                "idat-trailer",
+                0.8,
+                Some(first_idat),
+                format!(
+                    "{} bytes follow the end of zlib stream in IDAT",
+                    data.len() - len
+                ),
+            ),
+            Some(_) => {}
+            None => self.report(
+                "invalid-idat",
+                0.2,
+                Some(first_idat),
+                "IDAT does not contain complete zlib stream".to_string(),
+            ),
+        }
+    }
+
+    /// Statistical attacks on least significant bits of color samples
+    fn check_samples(&mut self) {
+        let Ok(image) = self.png.decode_image_with(&self.options) else {
+            return;
+        };
+        let color_type = image.header.color_type;
+        if color_type == ColorType::Indexed {
+            return;
+        }
+        let colors = color_type.channels() - color_type.has_alpha() as usize;
+        let channels: Vec<Vec<u16>> = (0..colors)
+            .map(|channel| {
+                (channel..image.samples.len())
+                    .step_by(image.channels())
+                    .map(|index| image.samples.get(index))
+                    .collect()
+            })
+            .collect();
+
+        if let Some(result) = chi_square(&channels.concat(), image.max_sample()) {
+            // smooth histograms of clean images look like embedding too, so alone it is
+            // weak evidence
+            self.report(
+                "chi-square",
+                0.6 * result.probability,
+                None,
+                format!(
+                    "statistic {:.2} with {} degrees of freedom, embedding probability {:.1}%",
+                    result.statistic,
+                    result.degrees_of_freedom,
+                    result.probability * 100.0
+                ),
+            );
+        }
+
+        let estimates: Vec<f64> = channels
+            .iter()
+            .filter_map(|values| rs_analysis(values))
+            .collect();
+        if !estimates.is_empty() {
+            let estimate = estimates.iter().sum::<f64>() / estimates.len() as f64;
+            // clean images give estimates of a few percent
+            let score = ((estimate - 0.05) / 0.25).clamp(0.0, 1.0);
+            self.report(
+                "rs-analysis",
+                score,
+                None,
+                format!(
+                    "estimated {:.1}% of color samples carry hidden bits",
+                    estimate * 100.0
+                ),
+            );
+        }
+    }
+}
+
+impl Png {
+    /// Look for signs of hidden data in png file: unknown private chunks, data after IEND,
+    /// oversized ancillary chunks, bytes after zlib stream in IDAT, and chi-square and RS
+    /// analysis of least significant bits of pixels. Corrupted chunks are recovered as in
+    /// [`Png::parse_lenient`] and reported too
+    pub fn analyze(bytes: &[u8], options: ParseOptions) -> Result<Analysis> {
+        let recovery = Recovery::run(bytes, options)?;
+        let mut analyzer = Analyzer {
+            bytes,
+            options,
+            png: Png::from_chunks(recovery.chunks),
+            offsets: recovery.offsets,
+            findings: Vec::new(),
+        };
+        for issue in recovery.issues {
+            analyzer.findings.push(Finding {
+                code: issue.code,
+                score: 0.3,
+                location: issue.location,
+                message: issue.message,
+            });
+        }
+
+        analyzer.check_chunk_types();
+        analyzer.check_after_iend();
+        analyzer.check_chunk_sizes();
+        analyzer.check_idat_trailer();
+        analyzer.check_samples();
+
+        Ok(Analysis {
+            findings: analyzer.findings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::ihdr::Ihdr;
+    use crate::image::{IdatOptions, Image};
+    use crate::lsb::LsbOptions;
+    use crate::png::ChunkPosition;
+
+    /// Noisy gradient image with stretched contrast, so clean one passes statistical tests
+    fn testing_png() -> Png {
+        let header = Ihdr::new(100, 100, 8, ColorType::Rgb).unwrap();
+        let mut image = Image::new(header).unwrap();
+        let mut seed: u32 = 7;
+        for y in 0..100 {
+            for x in 0..100 {
+                for channel in 0..3 {
+                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                    let base = 70.0
+                        + 40.0 * (x as f64 / 15.0 + channel as f64).sin()
+                        + 30.0 * (y as f64 / 10.0).cos();
+                    let value = (base as u16 + ((seed >> 16) % 5) as u16) * 3 / 2;
+                    image.set_sample(x, y, channel, value);
+                }
+            }
+        }
+
+        let mut chunks = vec![header.to_chunk()];
+        chunks.extend(image.to_idat_chunks(&IdatOptions::default()).unwrap());
+        chunks.push(Chunk::new(ChunkType::IEND, vec![]));
+        Png::from_chunks(chunks)
+    }
+
+    fn codes(bytes: &[u8]) -> Vec<&'static str> {
+        Png::analyze(bytes, ParseOptions::default())
+            .unwrap()
+            .findings
+            .iter()
+            .filter(|finding| finding.score > 0.1)
+            .map(|finding| finding.code)
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_png() {
+        let analysis = Png::analyze(&testing_png().as_bytes(), ParseOptions::default()).unwrap();
+        assert!(analysis.score() < 0.3, "{:?}", analysis);
+    }
+
+    #[test]
+    fn test_chunk_findings() {
+        let mut png = testing_png();
+        png.insert_chunk(
+            Chunk::from_strings("ruSt", "message").unwrap(),
+            ChunkPosition::BeforeIend,
+        )
+        .unwrap();
+        png.insert_chunk(
+            Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0; 40]),
+            ChunkPosition::BeforeIdat,
+        )
+        .unwrap();
+        let mut bytes = png.as_bytes();
+        bytes.extend_from_slice(b"appended");
+
+        let codes = codes(&bytes);
+        for code in ["unknown-private-chunk", "oversized-chunk", "after-iend"] {
+            assert!(codes.contains(&code), "{:?}", codes);
+        }
+    }
+
+    #[test]
+    fn test_idat_trailer() {
+        let mut png = testing_png();
+        let idat = png
+            .chunks()
+            .iter()
+            .rposition(|chunk| *chunk.chunk_type() == ChunkType::IDAT)
+            .unwrap();
+        let mut data = png.chunks()[idat].data().to_vec();
+        data.extend_from_slice(b"hidden");
+        png.replace_chunk(idat, Chunk::new(ChunkType::IDAT, data))
+            .unwrap();
+
+        assert!(codes(&png.as_bytes()).contains(&"idat-trailer"));
+    }
+
+    #[test]
+    fn test_lsb_embedding_detected() {
+        let mut png = testing_png();
+        let mut image = png.decode_image().unwrap();
+        let options = LsbOptions::default();
+        // pseudo-random message filling whole capacity
+        let mut seed: u32 = 1;
+        let message: Vec<u8> = (0..image.lsb_capacity(&options).unwrap() - 12)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), message);
+        image.embed_lsb(&chunk, &options).unwrap();
+        png.set_image(&image, &IdatOptions::default()).unwrap();
+
+        let analysis = Png::analyze(&png.as_bytes(), ParseOptions::default()).unwrap();
+        let rs = analysis
+            .findings
+            .iter()
+            .find(|finding| finding.code == "rs-analysis")
+            .unwrap();
+        assert!(rs.score > 0.9, "{:?}", analysis);
+        assert!(analysis.score() > 0.9);
+    }
+}
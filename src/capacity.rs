@@ -0,0 +1,293 @@
+use std::fmt;
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::envelope::ENVELOPE_OVERHEAD;
+use crate::image::{IdatOptions, Image};
+use crate::lsb::{Channels, LsbOptions};
+use crate::png::Png;
+use crate::reader::ParseOptions;
+use crate::Result;
+
+/// Length, type and crc stored around every hidden message
+pub const FRAMING_OVERHEAD: usize = 12;
+/// Chunk framing and envelope (without filename) written by encode around every message
+const OVERHEAD: usize = FRAMING_OVERHEAD + ENVELOPE_OVERHEAD;
+
+/// Way of hiding data in png
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Embedding {
+    /// Own ancillary chunk
+    Chunk,
+    /// Bytes appended after zlib stream in the last IDAT chunk
+    IdatTail,
+    /// Given number of least significant bits of every selected sample
+    Lsb { bits_per_sample: u8 },
+    /// Least significant bits of selected samples with Hamming code of given size
+    Matrix { code_bits: u8 },
+    /// Order of palette entries of indexed image
+    Palette,
+}
+
+impl fmt::Display for Embedding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Embedding::Chunk => write!(f, "chunk"),
+            Embedding::IdatTail => write!(f, "idat tail"),
+            Embedding::Lsb { bits_per_sample } => write!(
+                f,
+                "lsb ({} bit{} per sample)",
+                bits_per_sample,
+                if *bits_per_sample == 1 { "" } else { "s" }
+            ),
+            Embedding::Matrix { code_bits } => write!(f, "matrix (code {})", code_bits),
+            Embedding::Palette => write!(f, "palette"),
+        }
+    }
+}
+
+/// How much can be hidden with one embedding, see [`Png::capacities`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capacity {
+    pub embedding: Embedding,
+    /// Largest message in bytes
+    pub payload: usize,
+    /// Bytes of framing stored with message
+    pub overhead: usize,
+    /// Change of file size after hiding the largest message (pixel methods re-encode IDAT,
+    /// so it may even shrink)
+    pub growth: i64,
+    /// Whether message can be hidden this way, idat tail is only measured as there is no
+    /// encoder for it yet
+    pub supported: bool,
+}
+
+impl Png {
+    /// Capacity of every embedding usable with this png. Pixel methods use given channels
+    /// (every bit plane for lsb, one for matrix code), their growth is measured by actually
+    /// hiding pseudo-random message of the largest size. Methods which cannot be used with
+    /// the image are left out, pixel methods also when its pixels cannot be decoded
+    pub fn capacities(
+        &self,
+        channels: Channels,
+        code_bits: u8,
+        options: &ParseOptions,
+    ) -> Result<Vec<Capacity>> {
+        let max_length = options.max_chunk_length.min(Chunk::MAX_LENGTH) as usize;
+        let mut capacities = vec![Capacity {
+            embedding: Embedding::Chunk,
+            payload: max_length.saturating_sub(ENVELOPE_OVERHEAD),
+            overhead: OVERHEAD,
+            growth: (max_length + FRAMING_OVERHEAD) as i64,
+            supported: true,
+        }];
+
+        if let Some(last_idat) = self
+            .chunks()
+            .iter()
+            .rfind(|chunk| *chunk.chunk_type() == ChunkType::IDAT)
+        {
+            let available = max_length.saturating_sub(last_idat.data().len() + FRAMING_OVERHEAD);
+            capacities.push(Capacity {
+                embedding: Embedding::IdatTail,
+                payload: available.saturating_sub(ENVELOPE_OVERHEAD),
+                overhead: OVERHEAD,
+                growth: (available + FRAMING_OVERHEAD) as i64,
+                supported: false,
+            });
+        }
+
+        if let Ok(image) = self.decode_image_with(options) {
+            capacities.extend(self.pixel_capacities(&image, channels, code_bits, options)?);
+        }
+        Ok(capacities)
+    }
+
+    /// Capacities of methods which change pixels or palette of decoded image
+    fn pixel_capacities(
+        &self,
+        image: &Image,
+        channels: Channels,
+        code_bits: u8,
+        options: &ParseOptions,
+    ) -> Result<Vec<Capacity>> {
+        let mut capacities = Vec::new();
+        let size = self.bytes_len() as i64;
+        for bits_per_sample in 1..=image.header.bit_depth {
+            let lsb = LsbOptions {
+                channels,
+                bits_per_sample,
+                key: None,
+            };
+            let Ok(available) = image.lsb_capacity(&lsb) else {
+                break;
+            };
+            let mut png = self.clone();
+            let mut embedded = image.clone();
+            if let Some(chunk) = filler(available) {
+                embedded.embed_lsb(&chunk, &lsb)?;
+            }
+            capacities.push(Capacity {
+                embedding: Embedding::Lsb { bits_per_sample },
+                payload: available.saturating_sub(OVERHEAD),
+                overhead: OVERHEAD,
+                growth: set_image_growth(&mut png, &embedded, size)?,
+                supported: true,
+            });
+        }
+
+        let lsb = LsbOptions {
+            channels,
+            ..LsbOptions::default()
+        };
+        if let Ok(available) = image.matrix_capacity(&lsb, code_bits) {
+            let mut png = self.clone();
+            let mut embedded = image.clone();
+            if let Some(chunk) = filler(available) {
+                embedded.embed_matrix(&chunk, &lsb, code_bits)?;
+            }
+            capacities.push(Capacity {
+                embedding: Embedding::Matrix { code_bits },
+                payload: available.saturating_sub(OVERHEAD),
+                overhead: OVERHEAD,
+                growth: set_image_growth(&mut png, &embedded, size)?,
+                supported: true,
+            });
+        }
+
+        if let Ok(available) = self.palette_capacity() {
+            let mut png = self.clone();
+            if let Some(chunk) = filler(available) {
+                png.embed_palette(&chunk, options)?;
+            }
+            capacities.push(Capacity {
+                embedding: Embedding::Palette,
+                payload: available.saturating_sub(OVERHEAD),
+                overhead: OVERHEAD,
+                growth: png.bytes_len() as i64 - size,
+                supported: true,
+            });
+        }
+
+        Ok(capacities)
+    }
+}
+
+/// Chunk of pseudo-random data taking exactly `available` bytes with framing,
+/// `None` if framing alone does not fit
+fn filler(available: usize) -> Option<Chunk> {
+    let mut data = vec![0; available.checked_sub(FRAMING_OVERHEAD)?];
+    ChaCha20Rng::seed_from_u64(0).fill_bytes(&mut data);
+    Some(Chunk::new(
+        ChunkType::try_from(*b"ruSt").expect("valid chunk type"),
+        data,
+    ))
+}
+
+fn set_image_growth(png: &mut Png, image: &Image, size: i64) -> Result<i64> {
+    png.set_image(image, &IdatOptions::default())?;
+    Ok(png.bytes_len() as i64 - size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::{ColorType, Ihdr};
+
+    fn testing_png(color_type: ColorType) -> Png {
+        let header = Ihdr::new(32, 32, 8, color_type).unwrap();
+        let image = Image::new(header).unwrap();
+        let mut chunks = vec![header.to_chunk()];
+        if color_type == ColorType::Indexed {
+            chunks.push(Chunk::new(
+                ChunkType::PLTE,
+                (0..=255).flat_map(|i| [i, i, i]).collect(),
+            ));
+        }
+        chunks.extend(image.to_idat_chunks(&IdatOptions::default()).unwrap());
+        chunks.push(Chunk::new(ChunkType::IEND, vec![]));
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_rgb_capacities() {
+        let png = testing_png(ColorType::Rgb);
+        let options = ParseOptions {
+            max_chunk_length: 1000,
+            ..ParseOptions::default()
+        };
+        let capacities = png.capacities(Channels::RGB, 3, &options).unwrap();
+        let embeddings: Vec<Embedding> = capacities.iter().map(|c| c.embedding).collect();
+
+        assert_eq!(embeddings.len(), 2 + 8 + 1);
+        assert!(!embeddings.contains(&Embedding::Palette));
+        assert_eq!(capacities[0].payload, 1000 - ENVELOPE_OVERHEAD);
+        assert_eq!(capacities[0].overhead, 12 + ENVELOPE_OVERHEAD);
+        assert_eq!(capacities[0].growth, 1012);
+
+        let samples = 32 * 32 * 3;
+        let lsb = capacities
+            .iter()
+            .find(|c| c.embedding == Embedding::Lsb { bits_per_sample: 2 })
+            .unwrap();
+        assert_eq!(lsb.payload, samples * 2 / 8 - OVERHEAD);
+        // black image compresses well, random bits do not
+        assert!(lsb.growth > 0);
+        let matrix = capacities.last().unwrap();
+        assert_eq!(matrix.embedding, Embedding::Matrix { code_bits: 3 });
+        assert_eq!(matrix.payload, samples / 7 * 3 / 8 - OVERHEAD);
+    }
+
+    #[test]
+    fn test_indexed_capacities() {
+        let png = testing_png(ColorType::Indexed);
+        let capacities = png
+            .capacities(Channels::RGB, 3, &ParseOptions::default())
+            .unwrap();
+        let embeddings: Vec<Embedding> = capacities.iter().map(|c| c.embedding).collect();
+
+        assert_eq!(
+            embeddings,
+            [Embedding::Chunk, Embedding::IdatTail, Embedding::Palette]
+        );
+        assert!(!capacities[1].supported);
+        assert_eq!(capacities[2].payload, 210 - OVERHEAD);
+    }
+
+    #[test]
+    fn test_undecodable_pixels() {
+        let mut png = testing_png(ColorType::Rgb);
+        let index = png
+            .chunks()
+            .iter()
+            .position(|chunk| *chunk.chunk_type() == ChunkType::IDAT)
+            .unwrap();
+        png.replace_chunk(index, Chunk::new(ChunkType::IDAT, b"not zlib".to_vec()))
+            .unwrap();
+
+        let capacities = png
+            .capacities(Channels::RGB, 3, &ParseOptions::default())
+            .unwrap();
+        let embeddings: Vec<Embedding> = capacities.iter().map(|c| c.embedding).collect();
+        assert_eq!(embeddings, [Embedding::Chunk, Embedding::IdatTail]);
+    }
+
+    #[test]
+    fn test_embedding_display() {
+        assert_eq!(
+            Embedding::Lsb { bits_per_sample: 1 }.to_string(),
+            "lsb (1 bit per sample)"
+        );
+        assert_eq!(
+            Embedding::Lsb { bits_per_sample: 4 }.to_string(),
+            "lsb (4 bits per sample)"
+        );
+        assert_eq!(
+            Embedding::Matrix { code_bits: 3 }.to_string(),
+            "matrix (code 3)"
+        );
+    }
+}
@@ -1,15 +1,18 @@
 use std::fmt;
-use std::io::{BufReader, Read};
+use std::io;
+use std::io::{ErrorKind, Read, Write};
 use std::str::FromStr;
 
 use crc::{Crc, CRC_32_ISO_HDLC};
 
 use crate::chunk_type::ChunkType;
+use crate::error::Limit;
+use crate::reader::ParseOptions;
 use crate::{Error, Result};
 
-const CRC_CALCULATOR: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+pub(crate) static CRC_CALCULATOR: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Chunk {
     chunk_type: ChunkType,
     len: u32,
@@ -39,13 +42,10 @@ impl Chunk {
     }
 
     pub fn calculate_crc(chunk_type: &ChunkType, data: &[u8]) -> u32 {
-        let crc_bytes: Vec<u8> = chunk_type
-            .bytes()
-            .iter()
-            .chain(data.iter())
-            .copied()
-            .collect();
-        CRC_CALCULATOR.checksum(&crc_bytes)
+        let mut digest = CRC_CALCULATOR.digest();
+        digest.update(&chunk_type.bytes());
+        digest.update(data);
+        digest.finalize()
     }
 
     pub fn length(&self) -> u32 {
@@ -58,74 +58,188 @@ impl Chunk {
     pub fn crc(&self) -> u32 {
         self.crc
     }
+
+    /// Whether stored crc matches chunk type and data (it may not for chunks parsed leniently)
+    pub fn has_valid_crc(&self) -> bool {
+        self.crc == Self::calculate_crc(&self.chunk_type, &self.data)
+    }
     pub fn data(&self) -> &[u8] {
         &self.data
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
-        self.len
-            .to_be_bytes()
-            .iter()
-            .chain(self.chunk_type.bytes().iter())
-            .chain(self.data.iter())
-            .chain(self.crc.to_be_bytes().iter())
-            .copied()
-            .collect()
+        let mut bytes = Vec::with_capacity(self.bytes_len());
+        self.write_to(&mut bytes)
+            .expect("writing to vector cannot fail");
+        bytes
     }
 
+    /// Serialize chunk directly into writer
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.len.to_be_bytes())?;
+        writer.write_all(&self.chunk_type.bytes())?;
+        writer.write_all(&self.data)?;
+        writer.write_all(&self.crc.to_be_bytes())
+    }
+
+    /// Message as text, without envelope and signature and decompressed (with default limit)
+    /// when needed, see [`Chunk::message`]
     pub fn data_as_string(&self) -> Result<String> {
-        let string = String::from_utf8(self.data.clone())?;
-        Ok(string)
+        self.message(&ParseOptions::default())
     }
 
     pub fn bytes_len(&self) -> usize {
         12 + self.len as usize
     }
-}
 
-impl TryFrom<&[u8]> for Chunk {
-    type Error = Error;
+    /// Maximum chunk data length allowed by png specification
+    pub const MAX_LENGTH: u32 = (1 << 31) - 1;
 
-    fn try_from(value: &[u8]) -> Result<Self> {
-        if value.len() < 12 {
-            return Err(Error::from(format!(
-                "Minimum length of chunk bytes is 12, but {} was specified",
-                value.len()
-            )));
-        }
-        let mut reader = BufReader::new(value);
+    /// Read single chunk from reader, verifying its crc while data is read.
+    /// Returns `None` if reader is already at its end
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Option<Chunk>> {
+        let (len, chunk_type) = match Self::read_header(reader)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        Self::check_length(len, Self::MAX_LENGTH)?;
 
-        let mut u32_buff: [u8; 4] = [0, 0, 0, 0];
+        let (chunk, expected_crc) = Self::read_body(reader, len, chunk_type)?;
+        chunk.verify_crc(expected_crc)?;
 
-        reader.read_exact(&mut u32_buff)?;
+        Ok(Some(chunk))
+    }
+
+    /// Read length and type of chunk. Returns `None` if reader is already at its end
+    pub(crate) fn read_header<R: Read>(reader: &mut R) -> Result<Option<(u32, ChunkType)>> {
+        let mut u32_buff = [0; 4];
+
+        let read = read_full(reader, &mut u32_buff)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if read < 4 {
+            return Err(Self::truncated(12, read));
+        }
         let len = u32::from_be_bytes(u32_buff);
 
-        reader.read_exact(&mut u32_buff)?;
+        let read = read_full(reader, &mut u32_buff)?;
+        if read < 4 {
+            return Err(Self::truncated(12, 4 + read));
+        }
         let chunk_type = ChunkType::try_from(u32_buff)?;
 
-        let mut data_buff: Vec<u8> = vec![0; len as usize];
-        reader.read_exact(&mut data_buff)?;
-
-        let data = data_buff.to_vec();
+        Ok(Some((len, chunk_type)))
+    }
 
-        reader.read_exact(&mut u32_buff)?;
-        let crc = u32::from_be_bytes(u32_buff);
+    pub(crate) fn check_length(len: u32, max: u32) -> Result<()> {
+        if len > max {
+            return Err(Error::LimitExceeded {
+                limit: Limit::ChunkLength,
+                value: len as u64,
+                max: max as u64,
+                location: None,
+            });
+        }
+        Ok(())
+    }
 
-        let expected_crc = Chunk::calculate_crc(&chunk_type, &data);
+    /// Read data and crc of chunk with already read header.
+    /// Returns chunk with crc stored in input together with crc calculated from its data
+    pub(crate) fn read_body<R: Read>(
+        reader: &mut R,
+        len: u32,
+        chunk_type: ChunkType,
+    ) -> Result<(Chunk, u32)> {
+        let needed = 12 + len as usize;
+        let mut digest = CRC_CALCULATOR.digest();
+        digest.update(&chunk_type.bytes());
+
+        // data is read in blocks, so allocation grows only with bytes actually present in input
+        let mut data = Vec::new();
+        let mut block = [0; 8192];
+        let mut remaining = len as usize;
+        while remaining > 0 {
+            let wanted = remaining.min(block.len());
+            let read = read_full(reader, &mut block[..wanted])?;
+            digest.update(&block[..read]);
+            data.extend_from_slice(&block[..read]);
+            if read < wanted {
+                return Err(Self::truncated(needed, 8 + data.len()));
+            }
+            remaining -= read;
+        }
 
-        if crc != expected_crc {
-            return Err(Error::from(format!(
-                "Input crc is incorrect, got {}, but {} is correct crc",
-                crc, expected_crc
-            )));
+        let mut u32_buff = [0; 4];
+        let read = read_full(reader, &mut u32_buff)?;
+        if read < 4 {
+            return Err(Self::truncated(needed, needed - 4 + read));
         }
+        let crc = u32::from_be_bytes(u32_buff);
 
-        Ok(Chunk {
+        let chunk = Chunk {
             len,
             chunk_type,
             data,
             crc,
-        })
+        };
+        Ok((chunk, digest.finalize()))
+    }
+
+    pub(crate) fn verify_crc(&self, expected_crc: u32) -> Result<()> {
+        if self.crc != expected_crc {
+            return Err(Error::CrcMismatch {
+                chunk_type: self.chunk_type.to_string(),
+                expected: expected_crc,
+                actual: self.crc,
+                location: None,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn truncated(needed: usize, available: usize) -> Error {
+        Error::TruncatedChunk {
+            needed,
+            available,
+            location: None,
+        }
+    }
+}
+
+/// Fill buffer from reader, stopping early only at the end of input. Returns number of bytes read
+pub(crate) fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(read)
+}
+
+impl TryFrom<&[u8]> for Chunk {
+    type Error = Error;
+
+    fn try_from(mut value: &[u8]) -> Result<Self> {
+        let available = value.len();
+        let (len, chunk_type) =
+            Chunk::read_header(&mut value)?.ok_or_else(|| Chunk::truncated(12, 0))?;
+        Chunk::check_length(len, Chunk::MAX_LENGTH)?;
+
+        // reject lengths which cannot fit into input before reading anything
+        let needed = 12 + len as usize;
+        if needed > available {
+            return Err(Chunk::truncated(needed, available));
+        }
+
+        let (chunk, expected_crc) = Chunk::read_body(&mut value, len, chunk_type)?;
+        chunk.verify_crc(expected_crc)?;
+
+        Ok(chunk)
     }
 }
 
@@ -249,7 +363,69 @@ mod tests {
 
         let chunk = Chunk::try_from(chunk_data.as_ref());
 
-        assert!(chunk.is_err());
+        assert!(matches!(
+            chunk,
+            Err(Error::CrcMismatch {
+                expected: 2882656334,
+                actual: 2882656333,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_write_to() {
+        let chunk = testing_chunk();
+        let mut bytes = Vec::new();
+        chunk.write_to(&mut bytes).unwrap();
+
+        assert_eq!(bytes.len(), chunk.bytes_len());
+        assert_eq!(bytes, chunk.as_bytes());
+        assert_eq!(&bytes[..4], &42_u32.to_be_bytes());
+        assert_eq!(&bytes[bytes.len() - 4..], &2882656334_u32.to_be_bytes());
+    }
+
+    #[test]
+    fn test_truncated_chunk_from_bytes() {
+        let chunk_data: Vec<u8> = 42_u32
+            .to_be_bytes()
+            .iter()
+            .chain("RuSt".as_bytes().iter())
+            .chain("too short".as_bytes().iter())
+            .copied()
+            .collect();
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+
+        assert!(matches!(
+            chunk,
+            Err(Error::TruncatedChunk {
+                needed: 54,
+                available: 17,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_chunk_length_above_spec_limit() {
+        let chunk_data: Vec<u8> = u32::MAX
+            .to_be_bytes()
+            .iter()
+            .chain("RuSt".as_bytes().iter())
+            .chain([0; 22].iter())
+            .copied()
+            .collect();
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+
+        assert!(matches!(
+            chunk,
+            Err(Error::LimitExceeded {
+                limit: Limit::ChunkLength,
+                ..
+            })
+        ));
     }
 
     #[test]
@@ -4,12 +4,25 @@ use std::str::FromStr;
 
 use crate::Error;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub struct ChunkType {
     values: [u8; 4],
 }
 
 impl ChunkType {
+    pub const IHDR: ChunkType = ChunkType { values: *b"IHDR" };
+    pub const PLTE: ChunkType = ChunkType { values: *b"PLTE" };
+    pub const IDAT: ChunkType = ChunkType { values: *b"IDAT" };
+    pub const IEND: ChunkType = ChunkType { values: *b"IEND" };
+    pub const TRNS: ChunkType = ChunkType { values: *b"tRNS" };
+    pub const HIST: ChunkType = ChunkType { values: *b"hIST" };
+    pub const BKGD: ChunkType = ChunkType { values: *b"bKGD" };
+    pub const ICCP: ChunkType = ChunkType { values: *b"iCCP" };
+    pub const SRGB: ChunkType = ChunkType { values: *b"sRGB" };
+    pub const TEXT: ChunkType = ChunkType { values: *b"tEXt" };
+    pub const ZTXT: ChunkType = ChunkType { values: *b"zTXt" };
+    pub const ITXT: ChunkType = ChunkType { values: *b"iTXt" };
+
     pub fn bytes(&self) -> [u8; 4] {
         self.values
     }
@@ -34,29 +47,26 @@ impl ChunkType {
         byte.is_ascii_uppercase()
     }
 
-    fn is_byte_valid(byte: u8) -> crate::Result<()> {
+    fn is_byte_valid(byte: u8, position: usize) -> crate::Result<()> {
         if !byte.is_ascii_alphabetic() {
-            return Err(ChunkType::error_byte(byte));
+            return Err(Error::InvalidChunkTypeByte {
+                byte,
+                position,
+                location: None,
+            });
         }
         Ok(())
     }
     pub fn is_safe_to_copy(&self) -> bool {
         self.values[3].is_ascii_lowercase()
     }
-
-    fn error_byte(byte: u8) -> Error {
-        return Error::from(format!(
-            "{} is not a valid png byte (it must be a letter)",
-            byte
-        ));
-    }
 }
 impl TryFrom<[u8; 4]> for ChunkType {
     type Error = Error;
 
     fn try_from(array: [u8; 4]) -> Result<Self, Self::Error> {
-        for byte in array {
-            Self::is_byte_valid(byte)?
+        for (position, byte) in array.into_iter().enumerate() {
+            Self::is_byte_valid(byte, position)?
         }
         Ok(ChunkType { values: array })
     }
@@ -69,10 +79,7 @@ impl FromStr for ChunkType {
         let s = s.as_bytes();
         let len = s.len();
         if len < 4 {
-            return Err(Error::from(format!(
-                "Required 4 byte string got {} bytes",
-                len
-            )));
+            return Err(Error::InvalidChunkTypeLength { len });
         }
         let bytes = [s[0], s[1], s[2], s[3]];
         ChunkType::try_from(bytes)
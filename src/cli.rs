@@ -0,0 +1,1173 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use clap::{ArgEnum, Args, Parser, Subcommand};
+
+use crate::lenient::read_file;
+use crate::text::keyword_of;
+use crate::{crypto, recipient, signature};
+use crate::{
+    Capacity, Channels, Chunk, ChunkPosition, ChunkType, CompressedTextChunk, Compression,
+    ContentType, CrcRepair, Embedding, Envelope, Error, Finding, IdatOptions, Identity, Ihdr,
+    InternationalTextChunk, Issue, KdfParams, LsbOptions, ParseOptions, Png, RecipientKey,
+    RepairOptions, Result, Secret, Severity, SigningKey, TextChunk, TextMetadata, Verification,
+    VerifyingKey,
+};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+#[clap(propagate_version = true)]
+pub struct CliArgs {
+    #[clap(subcommand)]
+    command: CommandType,
+    #[clap(flatten)]
+    limits: LimitArgs,
+}
+
+impl CliArgs {
+    pub fn command(self) -> CommandType {
+        self.command
+    }
+
+    pub fn parse_options(&self) -> ParseOptions {
+        self.limits.parse_options()
+    }
+}
+
+/// Resource limits used when reading png files
+#[derive(Args, Debug)]
+pub struct LimitArgs {
+    /// Maximum data length of single chunk
+    #[clap(long, global = true)]
+    max_chunk_length: Option<u32>,
+    /// Maximum number of chunks in png file
+    #[clap(long, global = true)]
+    max_chunks: Option<usize>,
+    /// Maximum size of png file in bytes
+    #[clap(long, global = true)]
+    max_file_size: Option<u64>,
+    /// Maximum size of decompressed data in bytes
+    #[clap(long, global = true)]
+    max_decompressed_size: Option<u64>,
+}
+
+impl LimitArgs {
+    fn parse_options(&self) -> ParseOptions {
+        let default = ParseOptions::default();
+        ParseOptions {
+            max_chunk_length: self.max_chunk_length.unwrap_or(default.max_chunk_length),
+            max_chunks: self.max_chunks.unwrap_or(default.max_chunks),
+            max_file_size: self.max_file_size.unwrap_or(default.max_file_size),
+            max_decompressed_size: self
+                .max_decompressed_size
+                .unwrap_or(default.max_decompressed_size),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CommandType {
+    /// Encode a chunk with given chunk type and message into file
+    /// (note: by default it creates given file if it doesnt exists,
+    /// but if it exists it checks whether file is a valid png)
+    Encode(EncodeArgs),
+
+    /// Decode a secret message encoded in png file
+    Decode(DecodeArgs),
+
+    /// Remove (and decode) first secret message found with given chunk type encoded in png file
+    /// (note: it deletes oldest message first, use -a flag to delete all matched messages
+    /// or -n flag to pick which one should be deleted)
+    Remove(RemoveArgs),
+
+    /// Print png file data as bytes from given path
+    Print(PrintArgs),
+
+    /// Check png file against png specification and report every violation
+    /// (exits with error when any error severity violation is found)
+    Validate(ValidateArgs),
+
+    /// Salvage corrupted png file: recompute wrong crcs, skip garbage between chunks,
+    /// append missing IEND and keep data after IEND, printing every applied fix
+    Repair(RepairArgs),
+
+    /// Add, list, edit or delete tEXt, zTXt and iTXt text metadata by keyword
+    Text(TextArgs),
+
+    /// Look for signs of hidden data (unknown private chunks, data after IEND, oversized
+    /// chunks, IDAT trailers and statistical tests of pixels) and print scored report
+    Analyze(AnalyzeArgs),
+
+    /// Print largest message which can be hidden with every method, its framing overhead
+    /// and how much the file grows
+    Capacity(CapacityArgs),
+
+    /// Generate key pair for encrypting messages to recipients, writing secret key to given
+    /// file and public key to the same file with .pub extension
+    Keygen(KeygenArgs),
+
+    /// Check signatures of every signed chunk and print fingerprints of their signers
+    /// (exits with error when any signature is invalid)
+    Verify(VerifyArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct EncodeArgs {
+    file_path: String,
+    chunk_type: String,
+    #[clap(required_unless_present = "file")]
+    message: Option<String>,
+    /// Hide contents of given file (with its name) instead of message
+    #[clap(long, value_name = "PATH", conflicts_with = "message")]
+    file: Option<String>,
+    /// Outputs png file with encoded message to another file instead
+    #[clap(short, long)]
+    output_file: Option<String>,
+    /// Where to place new chunk in png file
+    #[clap(short, long, arg_enum, default_value = "before-iend")]
+    position: Position,
+    #[clap(flatten)]
+    method: MethodArgs,
+    /// Encrypt message with key derived from passphrase
+    #[clap(long, requires = "passphrase")]
+    encrypt: bool,
+    /// Passphrase used to encrypt message
+    #[clap(long, env = "PNGME_PASSPHRASE", hide_env_values = true)]
+    passphrase: Option<String>,
+    /// Encrypt message for owner of public key in given file (created by keygen), can be
+    /// repeated to encrypt it for more recipients
+    #[clap(
+        long = "recipient",
+        value_name = "PUBKEY_FILE",
+        conflicts_with = "encrypt"
+    )]
+    recipients: Vec<String>,
+    /// Sign message and its chunk type with secret key in given file (created by keygen
+    /// --signing)
+    #[clap(long, value_name = "SECRET_KEY_FILE")]
+    sign: Option<String>,
+    /// Compress message before encrypting and hiding it, decode decompresses it automatically
+    #[clap(long, arg_enum)]
+    compress: Option<CompressionArg>,
+}
+
+/// How message is hidden in png file
+#[derive(Args, Debug)]
+pub struct MethodArgs {
+    /// Hide message in its own chunk, in least significant bits of image pixels, in the same
+    /// bits with matrix embedding, which changes fewer of them, or in order of palette entries
+    /// of indexed image
+    #[clap(short, long, arg_enum, default_value = "chunk")]
+    method: Method,
+    /// Channels of pixels carrying message with lsb and matrix methods (letters r, g, b and a)
+    #[clap(long, default_value = "rgb")]
+    channels: Channels,
+    /// Number of least significant bits of every sample carrying message with lsb and matrix
+    /// methods
+    #[clap(long, default_value = "1")]
+    bits: u8,
+    /// Passphrase scattering message over pseudo-random pixels with lsb and matrix methods,
+    /// the same one is needed to decode it
+    #[clap(long)]
+    key: Option<String>,
+    /// Message bits hidden in every 2^k - 1 carrier bits with matrix method, larger values
+    /// change fewer samples but lower capacity
+    #[clap(long, default_value = "3")]
+    code: u8,
+}
+
+impl MethodArgs {
+    fn lsb_options(&self) -> LsbOptions {
+        LsbOptions {
+            channels: self.channels,
+            bits_per_sample: self.bits,
+            key: self.key.clone(),
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    Chunk,
+    Lsb,
+    Matrix,
+    Palette,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub enum CompressionArg {
+    Deflate,
+    Zstd,
+}
+
+impl From<CompressionArg> for Compression {
+    fn from(compression: CompressionArg) -> Self {
+        match compression {
+            CompressionArg::Deflate => Compression::Deflate,
+            CompressionArg::Zstd => Compression::Zstd,
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub enum Position {
+    BeforeIend,
+    BeforeIdat,
+    AfterIdat,
+}
+
+impl From<Position> for ChunkPosition {
+    fn from(position: Position) -> Self {
+        match position {
+            Position::BeforeIend => ChunkPosition::BeforeIend,
+            Position::BeforeIdat => ChunkPosition::BeforeIdat,
+            Position::AfterIdat => ChunkPosition::AfterIdat,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct DecodeArgs {
+    file_path: String,
+    chunk_type: String,
+    /// Recover messages from corrupted file instead of failing, reporting found problems
+    #[clap(short, long)]
+    lenient: bool,
+    /// Write message into given file instead of printing it, needed for hidden files
+    #[clap(short, long)]
+    output_file: Option<String>,
+    #[clap(flatten)]
+    method: MethodArgs,
+    /// Decrypt message encrypted with encode --encrypt
+    #[clap(long, requires = "passphrase")]
+    decrypt: bool,
+    /// Passphrase used to encrypt message
+    #[clap(long, env = "PNGME_PASSPHRASE", hide_env_values = true)]
+    passphrase: Option<String>,
+    /// Decrypt message encrypted with encode --recipient using secret key in given file
+    #[clap(long, value_name = "SECRET_KEY_FILE", conflicts_with = "decrypt")]
+    identity: Option<String>,
+    /// Check that message is signed by owner of public key in given file, failing when its
+    /// signature is invalid or missing
+    #[clap(long, value_name = "PUBKEY_FILE")]
+    verify: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct RemoveArgs {
+    file_path: String,
+    chunk_type: String,
+    /// Dont decode and output removed messages
+    #[clap(short, long)]
+    ignore_messages: bool,
+    /// Remove all chunks with given type instead of only the first one
+    #[clap(short, long, conflicts_with = "index")]
+    all: bool,
+    /// Remove n-th chunk with given type (counting from 0) instead of the first one
+    #[clap(short = 'n', long)]
+    index: Option<usize>,
+    /// Remove only chunks whose message is exactly the same as given one
+    #[clap(short, long)]
+    message: Option<String>,
+    /// Outputs png file without removed chunks to another file instead
+    #[clap(short, long)]
+    output_file: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct PrintArgs {
+    file_path: String,
+    /// Print chunks of corrupted file instead of failing, reporting found problems
+    #[clap(short, long)]
+    lenient: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ValidateArgs {
+    file_path: String,
+    /// Format of printed report
+    #[clap(short, long, arg_enum, default_value = "text")]
+    format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct AnalyzeArgs {
+    file_path: String,
+    /// Format of printed report
+    #[clap(short, long, arg_enum, default_value = "text")]
+    format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct CapacityArgs {
+    file_path: String,
+    /// Channels of pixels carrying message with lsb and matrix methods (letters r, g, b and a)
+    #[clap(long, default_value = "rgb")]
+    channels: Channels,
+    /// Message bits hidden in every 2^k - 1 carrier bits with matrix method
+    #[clap(long, default_value = "3")]
+    code: u8,
+    /// Format of printed report
+    #[clap(short, long, arg_enum, default_value = "text")]
+    format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct KeygenArgs {
+    /// File for secret key, it must not exist yet
+    output_file: String,
+    /// Generate key pair for signing messages instead
+    #[clap(long)]
+    signing: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    file_path: String,
+}
+
+#[derive(Args, Debug)]
+pub struct RepairArgs {
+    file_path: String,
+    /// Outputs repaired png file to another file instead
+    #[clap(short, long)]
+    output_file: Option<String>,
+    /// Which chunks should have their wrong crc recomputed
+    #[clap(short, long, arg_enum, default_value = "all")]
+    crc: CrcArg,
+    /// Dont append IEND chunk when it is missing
+    #[clap(long)]
+    no_iend: bool,
+    /// Remove data after IEND, where hidden messages are usually stored, instead of
+    /// keeping (and repairing) it
+    #[clap(long)]
+    strip_after_iend: bool,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub enum CrcArg {
+    All,
+    Ancillary,
+    None,
+}
+
+impl From<CrcArg> for CrcRepair {
+    fn from(crc: CrcArg) -> Self {
+        match crc {
+            CrcArg::All => CrcRepair::All,
+            CrcArg::Ancillary => CrcRepair::Ancillary,
+            CrcArg::None => CrcRepair::None,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct TextArgs {
+    #[clap(subcommand)]
+    command: TextCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TextCommand {
+    /// Add text chunk with given keyword
+    Add(TextAddArgs),
+
+    /// List all text chunks
+    List(TextListArgs),
+
+    /// Replace text of every text chunk with given keyword, keeping its kind
+    Edit(TextEditArgs),
+
+    /// Delete every text chunk with given keyword
+    Delete(TextDeleteArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct TextAddArgs {
+    file_path: String,
+    keyword: String,
+    text: String,
+    /// Kind of text chunk (tEXt and zTXt store only Latin-1 text, iTXt stores any UTF-8 text)
+    #[clap(short, long, arg_enum, default_value = "text")]
+    kind: TextKind,
+    /// Compress text of iTXt chunk
+    #[clap(short, long)]
+    compress: bool,
+    /// Language tag of iTXt chunk (like 'en' or 'pl-PL')
+    #[clap(short, long)]
+    language: Option<String>,
+    /// Keyword translated to language of iTXt chunk
+    #[clap(short, long)]
+    translated_keyword: Option<String>,
+    /// Outputs png file with added text to another file instead
+    #[clap(short, long)]
+    output_file: Option<String>,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub enum TextKind {
+    Text,
+    Ztxt,
+    Itxt,
+}
+
+#[derive(Args, Debug)]
+pub struct TextListArgs {
+    file_path: String,
+}
+
+#[derive(Args, Debug)]
+pub struct TextEditArgs {
+    file_path: String,
+    keyword: String,
+    text: String,
+    /// Outputs png file with edited text to another file instead
+    #[clap(short, long)]
+    output_file: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct TextDeleteArgs {
+    file_path: String,
+    keyword: String,
+    /// Outputs png file without deleted text to another file instead
+    #[clap(short, long)]
+    output_file: Option<String>,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+pub fn execute_command(command: CommandType, options: ParseOptions) -> Result<()> {
+    match command {
+        CommandType::Encode(args) => encode(args, options),
+        CommandType::Decode(args) => decode(args, options),
+        CommandType::Print(args) => print_png(args, options),
+        CommandType::Remove(args) => remove_chunk(args, options),
+        CommandType::Validate(args) => validate(args, options),
+        CommandType::Repair(args) => repair(args, options),
+        CommandType::Text(args) => match args.command {
+            TextCommand::Add(args) => add_text(args, options),
+            TextCommand::List(args) => list_text(args, options),
+            TextCommand::Edit(args) => edit_text(args, options),
+            TextCommand::Delete(args) => delete_text(args, options),
+        },
+        CommandType::Analyze(args) => analyze(args, options),
+        CommandType::Capacity(args) => capacity(args, options),
+        CommandType::Keygen(args) => keygen(args),
+        CommandType::Verify(args) => verify(args, options),
+    }
+}
+
+pub fn run(args: CliArgs) -> Result<()> {
+    let options = args.parse_options();
+    let command = args.command;
+
+    execute_command(command, options)
+}
+
+pub fn get_args() -> CliArgs {
+    CliArgs::parse()
+}
+
+/// Exit code reported by cli when command fails with given error
+/// (clap uses 2 for invalid arguments)
+pub fn exit_code(err: &Error) -> u8 {
+    match err {
+        Error::Io { .. } => 3,
+        Error::MissingSignature { .. } => 4,
+        Error::InvalidSignature { .. } => 5,
+        Error::TruncatedChunk { .. } => 6,
+        Error::CrcMismatch { .. } => 7,
+        Error::InvalidChunkTypeByte { .. } => 8,
+        Error::InvalidChunkTypeLength { .. } => 9,
+        Error::ChunkNotFound { .. } => 10,
+        Error::Utf8(_) => 11,
+        Error::InvalidChunkPosition { .. } => 12,
+        Error::LimitExceeded { .. } => 13,
+        Error::InvalidHeader(_) => 14,
+        Error::ValidationFailed { .. } => 15,
+        Error::InvalidText(_) => 16,
+        Error::TextNotFound { .. } => 17,
+        Error::InvalidCompressedData(_) => 18,
+        Error::InvalidImageData(_) => 19,
+        Error::UnsupportedCarrier(_) => 20,
+        Error::CapacityExceeded { .. } => 21,
+        Error::InvalidPalette(_) => 22,
+        Error::WrongPassphrase => 23,
+        Error::CorruptedCiphertext(_) => 24,
+        Error::InvalidKey(_) => 25,
+        Error::NotRecipient => 26,
+        Error::UnverifiedMessage(_) => 27,
+        Error::InvalidEnvelope(_) => 28,
+    }
+}
+
+fn get_png(filename: &str, options: ParseOptions) -> Result<Png> {
+    Png::from_file_with(filename, options)
+}
+
+fn encode(args: EncodeArgs, options: ParseOptions) -> Result<()> {
+    let mut png = get_png(&args.file_path, options)?;
+    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+    let content = match &args.file {
+        Some(path) => fs::read(path).map_err(|err| Error::from(err).with_path(path))?,
+        None => args.message.unwrap_or_default().into_bytes(),
+    };
+    let message = match args.compress {
+        Some(compression) => Compression::from(compression).compress(&content),
+        None => content,
+    };
+    let envelope = Envelope {
+        filename: args
+            .file
+            .as_deref()
+            .and_then(|path| Path::new(path).file_name())
+            .map(|name| name.to_string_lossy().into_owned()),
+        compressed: args.compress.is_some(),
+        encrypted: args.encrypt || !args.recipients.is_empty(),
+        signed: args.sign.is_some(),
+        ..Envelope::new(match args.file {
+            Some(_) => ContentType::File,
+            None => ContentType::Text,
+        })
+    };
+    let authenticated = envelope.authenticated()?;
+    let message = match &args.passphrase {
+        Some(passphrase) if args.encrypt => crypto::encrypt(
+            &message,
+            passphrase,
+            &chunk_type,
+            &authenticated,
+            &KdfParams::default(),
+        )?,
+        _ if !args.recipients.is_empty() => {
+            let recipients = args
+                .recipients
+                .iter()
+                .map(RecipientKey::from_file)
+                .collect::<Result<Vec<_>>>()?;
+            recipient::encrypt(&message, &recipients, &chunk_type, &authenticated)?
+        }
+        _ => message,
+    };
+    let message = match &args.sign {
+        Some(key) => signature::sign(
+            &message,
+            &SigningKey::from_file(key)?,
+            &chunk_type,
+            &authenticated,
+        ),
+        None => message,
+    };
+    let chunk = Chunk::enveloped(chunk_type, &envelope, &message)?;
+
+    match args.method.method {
+        Method::Chunk => {
+            png.insert_chunk(chunk, args.position.into())?;
+        }
+        Method::Lsb | Method::Matrix => {
+            let mut image = png.decode_image_with(&options)?;
+            let lsb_options = args.method.lsb_options();
+            let changed = if args.method.method == Method::Lsb {
+                image.embed_lsb(&chunk, &lsb_options)?
+            } else {
+                image.embed_matrix(&chunk, &lsb_options, args.method.code)?
+            };
+            png.set_image(&image, &IdatOptions::default())?;
+            println!("changed {} samples", changed);
+        }
+        Method::Palette => png.embed_palette(&chunk, &options)?,
+    }
+
+    let output = &args.output_file.unwrap_or(args.file_path);
+    png.write_file(output)
+}
+
+fn decode(args: DecodeArgs, options: ParseOptions) -> Result<()> {
+    let chunk = match args.method.method {
+        Method::Chunk => {
+            let mut chunk = None;
+            // keep only the most recent matching chunk instead of whole png
+            for read_chunk in read_chunks(&args.file_path, options, args.lenient)? {
+                let read_chunk = read_chunk?;
+                if read_chunk.chunk_type().to_string() == args.chunk_type {
+                    chunk = Some(read_chunk);
+                }
+            }
+            chunk
+        }
+        Method::Lsb | Method::Matrix => {
+            let image =
+                load_png(&args.file_path, options, args.lenient)?.decode_image_with(&options)?;
+            let lsb_options = args.method.lsb_options();
+            let chunk = if args.method.method == Method::Lsb {
+                image.extract_lsb(&lsb_options, &options)?
+            } else {
+                image.extract_matrix(&lsb_options, args.method.code, &options)?
+            };
+            chunk.filter(|chunk| chunk.chunk_type().to_string() == args.chunk_type)
+        }
+        Method::Palette => load_png(&args.file_path, options, args.lenient)?
+            .extract_palette(&options)?
+            .filter(|chunk| chunk.chunk_type().to_string() == args.chunk_type),
+    };
+
+    if let Some(chunk) = chunk {
+        if let Some(key) = &args.verify {
+            let verification = chunk.verify(&VerifyingKey::from_file(key)?);
+            println!("signature: {}", verification);
+            match verification {
+                Verification::Valid(_) => {}
+                Verification::Invalid(reason) => return Err(Error::UnverifiedMessage(reason)),
+                Verification::Unsigned => {
+                    return Err(Error::UnverifiedMessage(
+                        "message is not signed".to_string(),
+                    ))
+                }
+            }
+        }
+
+        let envelope = chunk.envelope();
+        if let Some(envelope) = &envelope {
+            println!("envelope: {}", envelope);
+        }
+
+        let identity = args
+            .identity
+            .as_ref()
+            .map(Identity::from_file)
+            .transpose()?;
+        let secret = match (&identity, &args.passphrase) {
+            (Some(identity), _) => Some(Secret::Identity(identity)),
+            (None, Some(passphrase)) if args.decrypt => Some(Secret::Passphrase(passphrase)),
+            _ if chunk.is_encrypted() => {
+                println!("Secret message is encrypted, use --decrypt to read it");
+                return Ok(());
+            }
+            _ if chunk.is_encrypted_for_recipients() => {
+                println!("Secret message is encrypted for recipients, use --identity to read it");
+                return Ok(());
+            }
+            _ => None,
+        };
+        let content = chunk.message_bytes(secret, &options)?;
+
+        let content_type = envelope.map_or(ContentType::Text, |envelope| envelope.content_type);
+        match &args.output_file {
+            Some(output) => {
+                fs::write(output, &content).map_err(|err| Error::from(err).with_path(output))?;
+                println!("secret message written to {}", output);
+            }
+            None if content_type == ContentType::File => println!(
+                "Secret message is a file with {} bytes, use --output-file to save it",
+                content.len()
+            ),
+            None => println!("secret message: '{}'", String::from_utf8(content)?),
+        }
+    } else {
+        println!("No chunk with type '{}' was found", args.chunk_type);
+    }
+
+    Ok(())
+}
+
+fn keygen(args: KeygenArgs) -> Result<()> {
+    let (secret, public, fingerprint) = if args.signing {
+        let key = SigningKey::generate();
+        let verifying_key = key.verifying_key();
+        (
+            key.to_string(),
+            verifying_key.to_string(),
+            verifying_key.fingerprint(),
+        )
+    } else {
+        let identity = Identity::generate();
+        let recipient = identity.recipient();
+        (
+            identity.to_string(),
+            recipient.to_string(),
+            recipient.fingerprint(),
+        )
+    };
+    let public_file = format!("{}.pub", args.output_file);
+
+    write_key_file(&args.output_file, &secret, true)?;
+    write_key_file(&public_file, &public, false)?;
+    println!("secret key written to {}", args.output_file);
+    println!(
+        "public key written to {} (fingerprint {})",
+        public_file, fingerprint
+    );
+    Ok(())
+}
+
+fn verify(args: VerifyArgs, options: ParseOptions) -> Result<()> {
+    let png = get_png(&args.file_path, options)?;
+    let results = png.verify_signatures();
+
+    let mut invalid = 0;
+    for (index, verification) in &results {
+        if matches!(verification, Verification::Invalid(_)) {
+            invalid += 1;
+        }
+        println!(
+            "chunk {} {}: {}",
+            index,
+            png.chunks()[*index].chunk_type(),
+            verification
+        );
+    }
+    println!(
+        "{}: {} signed chunks, {} invalid",
+        args.file_path,
+        results.len(),
+        invalid
+    );
+
+    if invalid > 0 {
+        return Err(Error::UnverifiedMessage(format!(
+            "{} of {} signed chunks have invalid signature",
+            invalid,
+            results.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Write key into new file, which only its owner can read when key is secret
+fn write_key_file(path: &str, key: &str, secret: bool) -> Result<()> {
+    let mut open_options = OpenOptions::new();
+    open_options.write(true).create_new(true);
+    #[cfg(unix)]
+    if secret {
+        use std::os::unix::fs::OpenOptionsExt;
+        open_options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = secret;
+
+    let write = || -> std::io::Result<()> {
+        let mut file = open_options.open(path)?;
+        writeln!(file, "{}", key)
+    };
+    write().map_err(|err| Error::from(err).with_path(path))
+}
+
+fn print_png(args: PrintArgs, options: ParseOptions) -> Result<()> {
+    let mut chunks = read_chunks(&args.file_path, options, args.lenient)?;
+
+    println!("Png {{");
+    println!("   HEADER: {:?}", Png::STANDARD_HEADER);
+
+    let first = chunks.next().transpose()?;
+    if let Some(first) = &first {
+        match Ihdr::try_from(first) {
+            Ok(ihdr) => println!("   IHDR: {}", ihdr),
+            Err(err) => println!("   IHDR: {}", err),
+        }
+    }
+
+    println!("   Chunks: {{");
+    for chunk in first.into_iter().map(Ok).chain(chunks) {
+        println!("        {}", chunk?);
+    }
+    println!("}}");
+
+    Ok(())
+}
+
+/// Chunks of png file, streamed lazily or (in lenient mode) recovered from whole file
+/// with problems reported to stderr
+fn read_chunks(
+    filename: &str,
+    options: ParseOptions,
+    lenient: bool,
+) -> Result<Box<dyn Iterator<Item = Result<Chunk>>>> {
+    if !lenient {
+        return Ok(Box::new(Png::open_file_with(filename, options)?));
+    }
+
+    let png = load_png(filename, options, lenient)?;
+    Ok(Box::new(png.chunks().to_vec().into_iter().map(Ok)))
+}
+
+/// Whole png file, in lenient mode recovered with problems reported to stderr
+fn load_png(filename: &str, options: ParseOptions, lenient: bool) -> Result<Png> {
+    if !lenient {
+        return get_png(filename, options);
+    }
+
+    let (png, issues) = Png::from_file_lenient(filename, options)?;
+    for issue in issues {
+        eprintln!("{}", issue);
+    }
+    Ok(png)
+}
+
+fn remove_chunk(args: RemoveArgs, options: ParseOptions) -> Result<()> {
+    let mut png = get_png(&args.file_path, options)?;
+
+    let selected_index = args.index.unwrap_or(0);
+    let mut matched = 0;
+    let removed = png.remove_chunks_where(|_, chunk| {
+        let matches = chunk.chunk_type().to_string() == args.chunk_type
+            && args
+                .message
+                .as_ref()
+                .is_none_or(|message| chunk.message(&options).is_ok_and(|data| data == *message));
+        if !matches {
+            return false;
+        }
+        matched += 1;
+        args.all || matched - 1 == selected_index
+    });
+
+    if removed.is_empty() {
+        return Err(Error::ChunkNotFound {
+            chunk_type: args.chunk_type,
+        });
+    }
+
+    let output = args.output_file.as_ref().unwrap_or(&args.file_path);
+    png.write_file(output)?;
+
+    for (index, chunk) in removed {
+        if args.ignore_messages {
+            println!("deleted chunk {} at index {}", chunk.chunk_type(), index);
+        } else {
+            println!(
+                "deleted chunk with message '{}' at index {}",
+                chunk
+                    .message(&options)
+                    .unwrap_or_else(|_| String::from_utf8_lossy(chunk.payload()).into_owned()),
+                index
+            );
+        }
+    }
+    Ok(())
+}
+
+fn validate(args: ValidateArgs, options: ParseOptions) -> Result<()> {
+    let png = get_png(&args.file_path, options)?;
+    let issues = png.validate();
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+
+    match args.format {
+        OutputFormat::Text => {
+            for issue in &issues {
+                println!("{}", issue);
+            }
+            println!(
+                "{}: {} errors, {} warnings",
+                args.file_path,
+                errors,
+                issues.len() - errors
+            );
+        }
+        OutputFormat::Json => {
+            let issues: Vec<String> = issues.iter().map(issue_json).collect();
+            println!(
+                "{{\"file\":{},\"valid\":{},\"issues\":[{}]}}",
+                json_string(&args.file_path),
+                errors == 0,
+                issues.join(",")
+            );
+        }
+    }
+
+    if errors > 0 {
+        return Err(Error::ValidationFailed { errors });
+    }
+    Ok(())
+}
+
+fn repair(args: RepairArgs, options: ParseOptions) -> Result<()> {
+    let bytes = read_file(Path::new(&args.file_path), options)?;
+    let repaired = Png::repair(
+        &bytes,
+        options,
+        RepairOptions {
+            crc: args.crc.into(),
+            append_iend: !args.no_iend,
+            strip_after_iend: args.strip_after_iend,
+        },
+    )?;
+
+    for fix in &repaired.fixes {
+        println!("fixed {}", fix);
+    }
+    for issue in &repaired.unfixed {
+        println!("not fixed {}", issue);
+    }
+
+    if repaired.fixes.is_empty() && args.output_file.is_none() {
+        println!("Nothing to repair in {}", args.file_path);
+        return Ok(());
+    }
+
+    let output = args.output_file.as_ref().unwrap_or(&args.file_path);
+    repaired.write_file(output)?;
+    println!(
+        "{} fixes applied, written to {}",
+        repaired.fixes.len(),
+        output
+    );
+    Ok(())
+}
+
+fn add_text(args: TextAddArgs, options: ParseOptions) -> Result<()> {
+    let mut png = get_png(&args.file_path, options)?;
+
+    let itxt_only = args.compress || args.language.is_some() || args.translated_keyword.is_some();
+    let text = match args.kind {
+        TextKind::Text | TextKind::Ztxt if itxt_only => {
+            return Err(Error::InvalidText(
+                "compression flag, language and translated keyword need iTXt (use --kind itxt)"
+                    .to_string(),
+            ))
+        }
+        TextKind::Text => TextMetadata::Text(TextChunk::new(&args.keyword, &args.text)?),
+        TextKind::Ztxt => {
+            TextMetadata::Compressed(CompressedTextChunk::new(&args.keyword, &args.text)?)
+        }
+        TextKind::Itxt => TextMetadata::International(InternationalTextChunk {
+            compressed: args.compress,
+            language_tag: args.language.unwrap_or_default(),
+            translated_keyword: args.translated_keyword.unwrap_or_default(),
+            ..InternationalTextChunk::new(&args.keyword, &args.text)?
+        }),
+    };
+
+    let index = png.insert_chunk(text.to_chunk()?, ChunkPosition::BeforeIend)?;
+
+    let output = args.output_file.as_ref().unwrap_or(&args.file_path);
+    png.write_file(output)?;
+    println!("added {} at index {}", text, index);
+    Ok(())
+}
+
+fn list_text(args: TextListArgs, options: ParseOptions) -> Result<()> {
+    let mut found = false;
+    for (index, chunk) in Png::open_file_with(&args.file_path, options)?.enumerate() {
+        let chunk = chunk?;
+        match TextMetadata::from_chunk(&chunk, &options) {
+            Ok(Some(text)) => println!("{}: {}", index, text),
+            Ok(None) => continue,
+            Err(err) => println!("{}: {} {}", index, chunk.chunk_type(), err),
+        }
+        found = true;
+    }
+
+    if !found {
+        println!("No text chunks were found");
+    }
+    Ok(())
+}
+
+fn edit_text(args: TextEditArgs, options: ParseOptions) -> Result<()> {
+    let mut png = get_png(&args.file_path, options)?;
+
+    let matching: Vec<usize> = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| keyword_of(chunk).as_ref() == Some(&args.keyword))
+        .map(|(index, _)| index)
+        .collect();
+    if matching.is_empty() {
+        return Err(Error::TextNotFound {
+            keyword: args.keyword,
+        });
+    }
+
+    let mut edited = Vec::new();
+    for index in matching {
+        let mut text = TextMetadata::from_chunk(&png.chunks()[index], &options)?
+            .expect("chunk with keyword is a text chunk");
+        text.set_text(args.text.clone());
+        png.replace_chunk(index, text.to_chunk()?)?;
+        edited.push((index, text));
+    }
+
+    let output = args.output_file.as_ref().unwrap_or(&args.file_path);
+    png.write_file(output)?;
+    for (index, text) in edited {
+        println!("edited {} at index {}", text, index);
+    }
+    Ok(())
+}
+
+fn delete_text(args: TextDeleteArgs, options: ParseOptions) -> Result<()> {
+    let mut png = get_png(&args.file_path, options)?;
+
+    let removed =
+        png.remove_chunks_where(|_, chunk| keyword_of(chunk).as_ref() == Some(&args.keyword));
+    if removed.is_empty() {
+        return Err(Error::TextNotFound {
+            keyword: args.keyword,
+        });
+    }
+
+    let output = args.output_file.as_ref().unwrap_or(&args.file_path);
+    png.write_file(output)?;
+    for (index, chunk) in removed {
+        println!(
+            "deleted {} chunk with keyword '{}' at index {}",
+            chunk.chunk_type(),
+            args.keyword,
+            index
+        );
+    }
+    Ok(())
+}
+
+fn analyze(args: AnalyzeArgs, options: ParseOptions) -> Result<()> {
+    let bytes = read_file(Path::new(&args.file_path), options)?;
+    let analysis = Png::analyze(&bytes, options)?;
+
+    match args.format {
+        OutputFormat::Text => {
+            for finding in &analysis.findings {
+                println!("{}", finding);
+            }
+            println!(
+                "{}: hidden data score {:.0}%",
+                args.file_path,
+                analysis.score() * 100.0
+            );
+        }
+        OutputFormat::Json => {
+            let findings: Vec<String> = analysis.findings.iter().map(finding_json).collect();
+            println!(
+                "{{\"file\":{},\"score\":{:.4},\"findings\":[{}]}}",
+                json_string(&args.file_path),
+                analysis.score(),
+                findings.join(",")
+            );
+        }
+    }
+    Ok(())
+}
+
+fn capacity(args: CapacityArgs, options: ParseOptions) -> Result<()> {
+    let png = get_png(&args.file_path, options)?;
+    let capacities = png.capacities(args.channels, args.code, &options)?;
+
+    match args.format {
+        OutputFormat::Text => {
+            for capacity in &capacities {
+                println!(
+                    "{}: {} bytes (with {} bytes of framing), file grows by {} bytes{}",
+                    capacity.embedding,
+                    capacity.payload,
+                    capacity.overhead,
+                    capacity.growth,
+                    if capacity.supported {
+                        ""
+                    } else {
+                        " (not supported by encode)"
+                    }
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let capacities: Vec<String> = capacities.iter().map(capacity_json).collect();
+            println!(
+                "{{\"file\":{},\"methods\":[{}]}}",
+                json_string(&args.file_path),
+                capacities.join(",")
+            );
+        }
+    }
+    Ok(())
+}
+
+fn capacity_json(capacity: &Capacity) -> String {
+    let method = match capacity.embedding {
+        Embedding::Chunk => "\"method\":\"chunk\"".to_string(),
+        Embedding::IdatTail => "\"method\":\"idat-tail\"".to_string(),
+        Embedding::Lsb { bits_per_sample } => {
+            format!("\"method\":\"lsb\",\"bits\":{}", bits_per_sample)
+        }
+        Embedding::Matrix { code_bits } => {
+            format!("\"method\":\"matrix\",\"code\":{}", code_bits)
+        }
+        Embedding::Palette => "\"method\":\"palette\"".to_string(),
+    };
+    format!(
+        "{{{},\"payload\":{},\"overhead\":{},\"growth\":{},\"supported\":{}}}",
+        method, capacity.payload, capacity.overhead, capacity.growth, capacity.supported
+    )
+}
+
+fn finding_json(finding: &Finding) -> String {
+    let (index, offset) = match finding.location {
+        Some(location) => (location.index.to_string(), location.offset.to_string()),
+        None => ("null".to_string(), "null".to_string()),
+    };
+    format!(
+        "{{\"code\":\"{}\",\"score\":{:.4},\"index\":{},\"offset\":{},\"message\":{}}}",
+        finding.code,
+        finding.score,
+        index,
+        offset,
+        json_string(&finding.message)
+    )
+}
+
+fn issue_json(issue: &Issue) -> String {
+    let (index, offset) = match issue.location {
+        Some(location) => (location.index.to_string(), location.offset.to_string()),
+        None => ("null".to_string(), "null".to_string()),
+    };
+    format!(
+        "{{\"severity\":\"{}\",\"code\":\"{}\",\"index\":{},\"offset\":{},\"message\":{}}}",
+        issue.severity,
+        issue.code,
+        index,
+        offset,
+        json_string(&issue.message)
+    )
+}
+
+/// Quote and escape string as json string literal
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(
+            json_string("a \"b\"\\\n\u{1}"),
+            "\"a \\\"b\\\"\\\\\\n\\u0001\""
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use std::io::Read;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::envelope::{sealed_chunk, ContentType, Envelope};
+use crate::error::Limit;
+use crate::{zlib, Error, Result};
+
+const MAGIC: &[u8; 4] = b"PNGZ";
+/// Magic, algorithm and length of uncompressed data
+const HEADER_LEN: usize = 4 + 1 + 4;
+const ZSTD_LEVEL: i32 = 19;
+
+/// Algorithm compressing message before it is hidden
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Zlib stream, as in zTXt and IDAT chunks
+    Deflate,
+    Zstd,
+}
+
+impl Compression {
+    fn flag(self) -> u8 {
+        match self {
+            Compression::Deflate => 1,
+            Compression::Zstd => 2,
+        }
+    }
+
+    fn from_flag(flag: u8) -> Result<Compression> {
+        match flag {
+            1 => Ok(Compression::Deflate),
+            2 => Ok(Compression::Zstd),
+            _ => Err(Error::InvalidCompressedData(format!(
+                "unknown compression algorithm {}",
+                flag
+            ))),
+        }
+    }
+
+    /// Data with header naming the algorithm and length of uncompressed data in front
+    pub fn compress(self, data: &[u8]) -> Vec<u8> {
+        let len = u32::try_from(data.len()).expect("message fits in chunk");
+        let mut compressed = Vec::with_capacity(HEADER_LEN + data.len() / 2);
+        compressed.extend_from_slice(MAGIC);
+        compressed.push(self.flag());
+        compressed.extend_from_slice(&len.to_be_bytes());
+        match self {
+            Compression::Deflate => compressed.extend_from_slice(&zlib::deflate(data, 9)),
+            Compression::Zstd => compressed.extend_from_slice(
+                &zstd::bulk::compress(data, ZSTD_LEVEL).expect("compression into vec"),
+            ),
+        }
+        compressed
+    }
+
+    /// Decompress data made by [`Compression::compress`], failing once more than `max_size`
+    /// bytes would be produced
+    pub fn decompress(data: &[u8], max_size: u64) -> Result<Vec<u8>> {
+        if !data.starts_with(MAGIC) {
+            return Err(Error::InvalidCompressedData(
+                "compressed message header is missing".to_string(),
+            ));
+        }
+        if data.len() < HEADER_LEN {
+            return Err(Error::InvalidCompressedData(format!(
+                "compressed message needs at least {} bytes, but it has {}",
+                HEADER_LEN,
+                data.len()
+            )));
+        }
+        let compression = Compression::from_flag(data[4])?;
+        let len = u32::from_be_bytes([data[5], data[6], data[7], data[8]]) as u64;
+        // refuse bombs before doing any work, actual size is checked below anyway
+        if len > max_size {
+            return Err(Error::LimitExceeded {
+                limit: Limit::DecompressedSize,
+                value: len,
+                max: max_size,
+                location: None,
+            });
+        }
+
+        let stream = &data[HEADER_LEN..];
+        let decompressed = match compression {
+            Compression::Deflate => zlib::inflate(stream, len)?,
+            Compression::Zstd => {
+                let mut decompressed = Vec::new();
+                zstd::stream::read::Decoder::new(stream)
+                    .and_then(|decoder| {
+                        decoder
+                            .take(len.saturating_add(1))
+                            .read_to_end(&mut decompressed)
+                    })
+                    .map_err(|err| Error::InvalidCompressedData(err.to_string()))?;
+                decompressed
+            }
+        };
+        if decompressed.len() as u64 != len {
+            return Err(Error::InvalidCompressedData(format!(
+                "message should have {} bytes after decompression, but it has {}",
+                len,
+                decompressed.len()
+            )));
+        }
+        Ok(decompressed)
+    }
+}
+
+impl Chunk {
+    /// Create chunk with message compressed by given algorithm, in envelope telling so
+    pub fn compressed(chunk_type: ChunkType, data: &[u8], compression: Compression) -> Chunk {
+        let envelope = Envelope {
+            compressed: true,
+            ..Envelope::new(ContentType::Text)
+        };
+        sealed_chunk(chunk_type, envelope, &compression.compress(data))
+    }
+
+    /// Whether envelope of chunk tells its message is compressed
+    pub fn is_compressed(&self) -> bool {
+        self.envelope().is_some_and(|envelope| envelope.compressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::ParseOptions;
+
+    fn chunk_type() -> ChunkType {
+        ChunkType::try_from(*b"ruSt").unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let message = r#"{"level":"info","message":"hello"}"#.repeat(50);
+        for compression in [Compression::Deflate, Compression::Zstd] {
+            let chunk = Chunk::compressed(chunk_type(), message.as_bytes(), compression);
+
+            assert!(chunk.is_compressed());
+            assert!(chunk.data().len() < message.len() / 5);
+            assert_eq!(chunk.data_as_string().unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn test_legacy_unchanged() {
+        let chunk = Chunk::new(chunk_type(), b"PNGZ\x01 is not compressed".to_vec());
+        let limited = ParseOptions {
+            max_decompressed_size: 0,
+            ..ParseOptions::default()
+        };
+
+        assert!(!chunk.is_compressed());
+        assert_eq!(
+            chunk.message(&limited).unwrap(),
+            "PNGZ\x01 is not compressed"
+        );
+    }
+
+    #[test]
+    fn test_message_limit() {
+        let message = "a".repeat(100);
+        let chunk = Chunk::compressed(chunk_type(), message.as_bytes(), Compression::Zstd);
+        let limited = ParseOptions {
+            max_decompressed_size: 10,
+            ..ParseOptions::default()
+        };
+
+        assert!(matches!(
+            chunk.message(&limited),
+            Err(Error::LimitExceeded { .. })
+        ));
+        assert_eq!(chunk.message(&ParseOptions::default()).unwrap(), message);
+    }
+
+    #[test]
+    fn test_bomb() {
+        let zeros = vec![0; 1 << 20];
+        for compression in [Compression::Deflate, Compression::Zstd] {
+            let compressed = compression.compress(&zeros);
+            assert!(matches!(
+                Compression::decompress(&compressed, 1024),
+                Err(Error::LimitExceeded {
+                    limit: Limit::DecompressedSize,
+                    ..
+                })
+            ));
+
+            // lying about length does not help
+            let mut lying = compressed.clone();
+            lying[5..9].copy_from_slice(&16u32.to_be_bytes());
+            assert!(matches!(
+                Compression::decompress(&lying, 1024),
+                Err(Error::InvalidCompressedData(_)) | Err(Error::LimitExceeded { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let mut compressed = Compression::Zstd.compress(b"some message");
+        compressed.truncate(compressed.len() - 3);
+        assert!(matches!(
+            Compression::decompress(&compressed, 1024),
+            Err(Error::InvalidCompressedData(_))
+        ));
+
+        assert!(matches!(
+            Compression::decompress(b"some message", 1024),
+            Err(Error::InvalidCompressedData(_))
+        ));
+
+        let mut unknown = Compression::Deflate.compress(b"some message");
+        unknown[4] = 9;
+        assert!(matches!(
+            Compression::decompress(&unknown, 1024),
+            Err(Error::InvalidCompressedData(_))
+        ));
+    }
+}
@@ -0,0 +1,331 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::envelope::{authenticated, sealed_chunk, ContentType, Envelope};
+use crate::{Error, Result};
+
+const MAGIC: &[u8; 4] = b"PNGE";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const CHECK_LEN: usize = 8;
+const KEY_LEN: usize = 32;
+/// Magic, version, kdf parameters, salt, passphrase check and nonce
+const HEADER_LEN: usize = 4 + 1 + 12 + SALT_LEN + CHECK_LEN + NONCE_LEN;
+const TAG_LEN: usize = 16;
+
+/// Parameters of Argon2id key derivation, stored with encrypted message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Memory used by derivation in KiB
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    /// Largest parameters accepted when decrypting, so hostile message cannot make
+    /// derivation take whole memory or hours
+    pub const MAX: KdfParams = KdfParams {
+        memory_kib: 1 << 20,
+        iterations: 64,
+        parallelism: 16,
+    };
+
+    fn check(&self) -> Result<Params> {
+        if self.memory_kib > Self::MAX.memory_kib
+            || self.iterations > Self::MAX.iterations
+            || self.parallelism > Self::MAX.parallelism
+        {
+            return Err(Error::CorruptedCiphertext(format!(
+                "key derivation parameters {:?} exceed {:?}",
+                self,
+                Self::MAX
+            )));
+        }
+        Params::new(
+            self.memory_kib,
+            self.iterations,
+            self.parallelism,
+            Some(KEY_LEN + CHECK_LEN),
+        )
+        .map_err(|err| Error::CorruptedCiphertext(format!("invalid key derivation: {}", err)))
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+/// Encryption key and short value which tells wrong passphrase apart from corrupted data
+fn derive(
+    passphrase: &str,
+    salt: &[u8],
+    params: &KdfParams,
+) -> Result<([u8; KEY_LEN], [u8; CHECK_LEN])> {
+    let mut output = [0; KEY_LEN + CHECK_LEN];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params.check()?)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut output)
+        .map_err(|err| Error::CorruptedCiphertext(format!("invalid key derivation: {}", err)))?;
+
+    let mut key = [0; KEY_LEN];
+    let mut check = [0; CHECK_LEN];
+    key.copy_from_slice(&output[..KEY_LEN]);
+    check.copy_from_slice(&output[KEY_LEN..]);
+    Ok((key, check))
+}
+
+/// Whether data starts like message encrypted with [`Chunk::encrypted`]
+pub(crate) fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encrypt data with key derived from passphrase, authenticating chunk type, envelope
+/// (see [`Envelope::authenticated`]) and header
+pub(crate) fn encrypt(
+    data: &[u8],
+    passphrase: &str,
+    chunk_type: &ChunkType,
+    envelope: &[u8],
+    params: &KdfParams,
+) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+    let (key, check) = derive(passphrase, &salt, params)?;
+
+    let mut sealed = Vec::with_capacity(HEADER_LEN + data.len() + TAG_LEN);
+    sealed.extend_from_slice(MAGIC);
+    sealed.push(VERSION);
+    for value in [params.memory_kib, params.iterations, params.parallelism] {
+        sealed.extend_from_slice(&value.to_be_bytes());
+    }
+    sealed.extend_from_slice(&salt);
+    sealed.extend_from_slice(&check);
+    sealed.extend_from_slice(&nonce);
+
+    let aad = [&chunk_type.bytes()[..], envelope, &sealed].concat();
+    let ciphertext = XChaCha20Poly1305::new(&key.into())
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: data,
+                aad: &aad,
+            },
+        )
+        .expect("encryption of message fits in memory");
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypt data produced by [`encrypt`] for the same chunk type and envelope
+pub(crate) fn decrypt(
+    sealed: &[u8],
+    passphrase: &str,
+    chunk_type: &ChunkType,
+    envelope: &[u8],
+) -> Result<Vec<u8>> {
+    if !is_encrypted(sealed) {
+        return Err(Error::CorruptedCiphertext(
+            "message is not encrypted".to_string(),
+        ));
+    }
+    if sealed.len() < HEADER_LEN + TAG_LEN {
+        return Err(Error::CorruptedCiphertext(format!(
+            "encrypted message needs at least {} bytes, but it has {}",
+            HEADER_LEN + TAG_LEN,
+            sealed.len()
+        )));
+    }
+    if sealed[4] != VERSION {
+        return Err(Error::CorruptedCiphertext(format!(
+            "unknown encryption version {}",
+            sealed[4]
+        )));
+    }
+
+    let value =
+        |i: usize| u32::from_be_bytes([sealed[i], sealed[i + 1], sealed[i + 2], sealed[i + 3]]);
+    let params = KdfParams {
+        memory_kib: value(5),
+        iterations: value(9),
+        parallelism: value(13),
+    };
+    let salt = &sealed[17..17 + SALT_LEN];
+    let check = &sealed[17 + SALT_LEN..17 + SALT_LEN + CHECK_LEN];
+    let nonce = &sealed[HEADER_LEN - NONCE_LEN..HEADER_LEN];
+
+    let (key, expected_check) = derive(passphrase, salt, &params)?;
+    if check != expected_check {
+        return Err(Error::WrongPassphrase);
+    }
+
+    let aad = [&chunk_type.bytes()[..], envelope, &sealed[..HEADER_LEN]].concat();
+    XChaCha20Poly1305::new(&key.into())
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: &sealed[HEADER_LEN..],
+                aad: &aad,
+            },
+        )
+        .map_err(|_| {
+            Error::CorruptedCiphertext(
+                "authentication failed, message or its envelope was modified or moved to \
+                 another chunk type"
+                    .to_string(),
+            )
+        })
+}
+
+impl Chunk {
+    /// Create chunk with data encrypted by key derived from passphrase (Argon2id and
+    /// XChaCha20-Poly1305). Chunk type and envelope are authenticated, so message moved to
+    /// chunk of another type or with changed envelope cannot be decrypted
+    pub fn encrypted(
+        chunk_type: ChunkType,
+        data: &[u8],
+        passphrase: &str,
+        params: &KdfParams,
+    ) -> Result<Chunk> {
+        let envelope = Envelope {
+            encrypted: true,
+            ..Envelope::new(ContentType::Text)
+        };
+        let encrypted = encrypt(
+            data,
+            passphrase,
+            &chunk_type,
+            &envelope.authenticated()?,
+            params,
+        )?;
+        Ok(sealed_chunk(chunk_type, envelope, &encrypted))
+    }
+
+    /// Decrypt data of chunk created with [`Chunk::encrypted`]. Fails with
+    /// [`Error::WrongPassphrase`] or [`Error::CorruptedCiphertext`]
+    pub fn decrypt(&self, passphrase: &str) -> Result<Vec<u8>> {
+        decrypt(
+            self.payload(),
+            passphrase,
+            self.chunk_type(),
+            authenticated(self.data()),
+        )
+    }
+
+    /// Whether envelope of chunk tells its message is encrypted, with passphrase
+    pub fn is_encrypted(&self) -> bool {
+        self.envelope().is_some_and(|envelope| envelope.encrypted) && is_encrypted(self.payload())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const FAST: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn encrypted_chunk() -> Chunk {
+        Chunk::encrypted(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"top secret",
+            "passphrase",
+            &FAST,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let chunk = encrypted_chunk();
+
+        assert!(chunk.is_encrypted());
+        assert_eq!(chunk.payload().len(), HEADER_LEN + 10 + TAG_LEN);
+        assert!(!chunk.data().windows(10).any(|w| w == b"top secret"));
+        assert_eq!(chunk.decrypt("passphrase").unwrap(), b"top secret");
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        assert!(matches!(
+            encrypted_chunk().decrypt("other"),
+            Err(Error::WrongPassphrase)
+        ));
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let chunk = encrypted_chunk();
+        let mut data = chunk.data().to_vec();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        let corrupted = Chunk::new(*chunk.chunk_type(), data);
+        assert!(matches!(
+            corrupted.decrypt("passphrase"),
+            Err(Error::CorruptedCiphertext(_))
+        ));
+
+        let truncated = Chunk::new(*chunk.chunk_type(), chunk.data()[..20].to_vec());
+        assert!(matches!(
+            truncated.decrypt("passphrase"),
+            Err(Error::CorruptedCiphertext(_))
+        ));
+    }
+
+    #[test]
+    fn test_moved_to_other_chunk_type() {
+        let chunk = encrypted_chunk();
+        let moved = Chunk::new(ChunkType::from_str("abCd").unwrap(), chunk.data().to_vec());
+
+        assert!(matches!(
+            moved.decrypt("passphrase"),
+            Err(Error::CorruptedCiphertext(_))
+        ));
+    }
+
+    #[test]
+    fn test_changed_envelope() {
+        let chunk = encrypted_chunk();
+        let (envelope, payload) = Envelope::open(chunk.data()).unwrap();
+        let changed = Envelope {
+            filename: Some("secret.txt".to_string()),
+            ..envelope
+        };
+        let resealed = Chunk::enveloped(*chunk.chunk_type(), &changed, payload).unwrap();
+
+        assert!(matches!(
+            resealed.decrypt("passphrase"),
+            Err(Error::CorruptedCiphertext(_))
+        ));
+    }
+
+    #[test]
+    fn test_hostile_parameters() {
+        let mut data = encrypted_chunk().data().to_vec();
+        data[5..9].copy_from_slice(&u32::MAX.to_be_bytes());
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), data);
+
+        assert!(matches!(
+            chunk.decrypt("passphrase"),
+            Err(Error::CorruptedCiphertext(_))
+        ));
+    }
+}
@@ -0,0 +1,423 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chunk::{Chunk, CRC_CALCULATOR};
+use crate::chunk_type::ChunkType;
+use crate::compression::Compression;
+use crate::reader::ParseOptions;
+use crate::recipient::Identity;
+use crate::{Error, Result};
+
+const MAGIC: &[u8; 4] = b"PNGM";
+/// Newest format version this pngme reads and the one it writes
+pub const FORMAT_VERSION: u8 = 1;
+/// Magic, version, flags, content type and length of filename
+const PREFIX_LEN: usize = 4 + 1 + 1 + 1 + 2;
+/// Timestamp, payload length and checksum following the filename
+const SUFFIX_LEN: usize = 8 + 4 + 4;
+/// Length of timestamp, the last field authenticated by signature and encryption
+const CREATED_LEN: usize = 8;
+/// Size of envelope without filename
+pub(crate) const ENVELOPE_OVERHEAD: usize = PREFIX_LEN + SUFFIX_LEN;
+
+const COMPRESSED: u8 = 1;
+const ENCRYPTED: u8 = 1 << 1;
+const SIGNED: u8 = 1 << 2;
+
+/// What hidden message holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    Text,
+    /// Contents of a file, usually with its original name
+    File,
+}
+
+impl ContentType {
+    fn code(self) -> u8 {
+        match self {
+            ContentType::Text => 0,
+            ContentType::File => 1,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<ContentType> {
+        match code {
+            0 => Some(ContentType::Text),
+            1 => Some(ContentType::File),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ContentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentType::Text => write!(f, "text"),
+            ContentType::File => write!(f, "file"),
+        }
+    }
+}
+
+/// Self-describing header stored in front of hidden payload. Its checksum catches accidental
+/// damage only, authenticity comes from signature and encryption of the payload, which cover
+/// every field up to creation time as well
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    /// Format version of pngme which wrote the message
+    pub version: u8,
+    pub content_type: ContentType,
+    pub filename: Option<String>,
+    /// Creation time in seconds since unix epoch
+    pub created: u64,
+    /// Whether payload is compressed (before encryption)
+    pub compressed: bool,
+    /// Whether payload is encrypted with passphrase or for recipients
+    pub encrypted: bool,
+    pub signed: bool,
+}
+
+impl Envelope {
+    /// Envelope of current format version created now, with no flags set
+    pub fn new(content_type: ContentType) -> Envelope {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        Envelope {
+            version: FORMAT_VERSION,
+            content_type,
+            filename: None,
+            created,
+            compressed: false,
+            encrypted: false,
+            signed: false,
+        }
+    }
+
+    /// Payload with this envelope in front
+    pub fn seal(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        let payload_len = u32::try_from(payload.len())
+            .map_err(|_| Error::InvalidEnvelope(format!("payload has {} bytes", payload.len())))?;
+
+        let mut sealed = self.authenticated()?;
+        sealed.reserve(SUFFIX_LEN - CREATED_LEN + payload.len());
+        sealed.extend_from_slice(&payload_len.to_be_bytes());
+        let checksum = checksum(&sealed, payload);
+        sealed.extend_from_slice(&checksum.to_be_bytes());
+        sealed.extend_from_slice(payload);
+        Ok(sealed)
+    }
+
+    /// Leading bytes of sealed envelope, from magic up to creation time, which signature and
+    /// encryption authenticate. Payload length and checksum depend on payload, so they are
+    /// left out
+    pub(crate) fn authenticated(&self) -> Result<Vec<u8>> {
+        let filename = self.filename.as_deref().unwrap_or_default().as_bytes();
+        let filename_len = u16::try_from(filename.len()).map_err(|_| {
+            Error::InvalidEnvelope(format!("filename has {} bytes", filename.len()))
+        })?;
+        let flags = [
+            (self.compressed, COMPRESSED),
+            (self.encrypted, ENCRYPTED),
+            (self.signed, SIGNED),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, flag)| flags | flag);
+
+        let mut bytes = Vec::with_capacity(PREFIX_LEN + filename.len() + CREATED_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(self.version);
+        bytes.push(flags);
+        bytes.push(self.content_type.code());
+        bytes.extend_from_slice(&filename_len.to_be_bytes());
+        bytes.extend_from_slice(filename);
+        bytes.extend_from_slice(&self.created.to_be_bytes());
+        Ok(bytes)
+    }
+
+    /// Envelope and payload of data written by [`Envelope::seal`]. Data which does not hold
+    /// valid envelope of known version with matching checksum is legacy raw message, so
+    /// `None` is returned for it, even if it happens to start with envelope magic
+    pub fn open(data: &[u8]) -> Option<(Envelope, &[u8])> {
+        if !data.starts_with(MAGIC) || data.len() < PREFIX_LEN {
+            return None;
+        }
+        let version = data[4];
+        if version == 0 || version > FORMAT_VERSION {
+            return None;
+        }
+        let flags = data[5];
+        let content_type = ContentType::from_code(data[6])?;
+        let filename_len = u16::from_be_bytes([data[7], data[8]]) as usize;
+        let header_len = PREFIX_LEN + filename_len + SUFFIX_LEN;
+        if data.len() < header_len {
+            return None;
+        }
+
+        let suffix = &data[PREFIX_LEN + filename_len..header_len];
+        let created = u64::from_be_bytes(suffix[..8].try_into().expect("8 bytes"));
+        let payload_len = u32::from_be_bytes(suffix[8..12].try_into().expect("4 bytes")) as usize;
+        let stored_checksum = u32::from_be_bytes(suffix[12..].try_into().expect("4 bytes"));
+        let payload = &data[header_len..];
+        if payload.len() != payload_len
+            || checksum(&data[..header_len - 4], payload) != stored_checksum
+        {
+            return None;
+        }
+
+        let filename = &data[PREFIX_LEN..PREFIX_LEN + filename_len];
+        let filename = match filename {
+            [] => None,
+            _ => Some(String::from_utf8(filename.to_vec()).ok()?),
+        };
+        let envelope = Envelope {
+            version,
+            content_type,
+            filename,
+            created,
+            compressed: flags & COMPRESSED != 0,
+            encrypted: flags & ENCRYPTED != 0,
+            signed: flags & SIGNED != 0,
+        };
+        Some((envelope, payload))
+    }
+}
+
+impl fmt::Display for Envelope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "format {}, {}", self.version, self.content_type)?;
+        if let Some(filename) = &self.filename {
+            write!(f, " '{}'", filename)?;
+        }
+        write!(f, ", created {}", utc_time(self.created))?;
+        for (set, name) in [
+            (self.compressed, "compressed"),
+            (self.encrypted, "encrypted"),
+            (self.signed, "signed"),
+        ] {
+            if set {
+                write!(f, ", {}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn checksum(header: &[u8], payload: &[u8]) -> u32 {
+    let mut digest = CRC_CALCULATOR.digest();
+    digest.update(header);
+    digest.update(payload);
+    digest.finalize()
+}
+
+/// Seconds since unix epoch as UTC date and time (civil from days algorithm of Howard Hinnant)
+fn utc_time(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64;
+    let time = seconds % 86400;
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Data inside envelope, whole data for legacy raw message
+pub(crate) fn contents(data: &[u8]) -> &[u8] {
+    Envelope::open(data).map_or(data, |(_, payload)| payload)
+}
+
+/// Authenticated bytes of envelope as they are stored in data (see
+/// [`Envelope::authenticated`]), empty for legacy raw message
+pub(crate) fn authenticated(data: &[u8]) -> &[u8] {
+    if Envelope::open(data).is_none() {
+        return &[];
+    }
+    let filename_len = u16::from_be_bytes([data[7], data[8]]) as usize;
+    &data[..PREFIX_LEN + filename_len + CREATED_LEN]
+}
+
+/// Chunk with payload in envelope of text message with given flags
+pub(crate) fn sealed_chunk(chunk_type: ChunkType, envelope: Envelope, payload: &[u8]) -> Chunk {
+    Chunk::enveloped(chunk_type, &envelope, payload).expect("message fits in chunk")
+}
+
+/// Secret which opens encrypted message
+#[derive(Debug, Clone, Copy)]
+pub enum Secret<'a> {
+    Passphrase(&'a str),
+    Identity(&'a Identity),
+}
+
+impl Chunk {
+    /// Create chunk with payload in given envelope
+    pub fn enveloped(chunk_type: ChunkType, envelope: &Envelope, payload: &[u8]) -> Result<Chunk> {
+        Ok(Chunk::new(chunk_type, envelope.seal(payload)?))
+    }
+
+    /// Envelope of chunk data, `None` for legacy chunks holding raw message
+    pub fn envelope(&self) -> Option<Envelope> {
+        Envelope::open(self.data()).map(|(envelope, _)| envelope)
+    }
+
+    /// Hidden message with every layer named by its envelope removed: signature is stripped
+    /// (see [`Chunk::verification`]), payload decrypted with given secret and decompressed
+    /// within limit of options. Legacy chunks without envelope hold message as it is
+    pub fn message_bytes(&self, secret: Option<Secret>, options: &ParseOptions) -> Result<Vec<u8>> {
+        let Some(envelope) = self.envelope() else {
+            return Ok(self.data().to_vec());
+        };
+        let payload = match secret {
+            _ if !envelope.encrypted => self.payload().to_vec(),
+            Some(Secret::Passphrase(passphrase)) => self.decrypt(passphrase)?,
+            Some(Secret::Identity(identity)) => self.decrypt_with(identity)?,
+            None => {
+                return Err(Error::InvalidKey(
+                    "message is encrypted, passphrase or identity is needed to read it".to_string(),
+                ))
+            }
+        };
+        if envelope.compressed {
+            Compression::decompress(&payload, options.max_decompressed_size)
+        } else {
+            Ok(payload)
+        }
+    }
+
+    /// Hidden message of unencrypted chunk as text, see [`Chunk::message_bytes`]
+    pub fn message(&self, options: &ParseOptions) -> Result<String> {
+        let string = String::from_utf8(self.message_bytes(None, options)?)?;
+        Ok(string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signature::Verification;
+
+    fn chunk_type() -> ChunkType {
+        ChunkType::try_from(*b"ruSt").unwrap()
+    }
+
+    fn file_envelope() -> Envelope {
+        Envelope {
+            filename: Some("report.json".to_string()),
+            created: 1_700_000_000,
+            compressed: true,
+            signed: true,
+            ..Envelope::new(ContentType::File)
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let envelope = file_envelope();
+        let chunk = Chunk::enveloped(chunk_type(), &envelope, b"{}").unwrap();
+
+        assert_eq!(chunk.envelope(), Some(envelope));
+        assert_eq!(contents(chunk.data()), b"{}");
+        assert_eq!(chunk.payload(), b"{}");
+        assert_eq!(chunk.data().len(), PREFIX_LEN + 11 + SUFFIX_LEN + 2);
+    }
+
+    #[test]
+    fn test_legacy() {
+        let chunk = Chunk::new(chunk_type(), b"raw message".to_vec());
+
+        assert_eq!(chunk.envelope(), None);
+        assert_eq!(chunk.data_as_string().unwrap(), "raw message");
+    }
+
+    #[test]
+    fn test_text_is_transparent() {
+        let chunk =
+            Chunk::enveloped(chunk_type(), &Envelope::new(ContentType::Text), b"hello").unwrap();
+
+        assert_eq!(chunk.data_as_string().unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_damaged_is_legacy() {
+        let sealed = file_envelope().seal(b"payload").unwrap();
+
+        let mut flipped = sealed.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        let mut newer = sealed.clone();
+        newer[4] = FORMAT_VERSION + 1;
+        for damaged in [&flipped, &sealed[..sealed.len() - 1], &sealed[..12], &newer] {
+            assert_eq!(Envelope::open(damaged), None);
+            assert_eq!(contents(damaged), damaged);
+        }
+    }
+
+    #[test]
+    fn test_legacy_with_magic() {
+        let signed_like = [&b"PNGS"[..], &[1; 120]].concat();
+        for data in [
+            &b"PNGME rocks"[..],
+            b"PNGE encrypted?",
+            b"PNGK for recipients?",
+            b"PNGZ\x01compressed?",
+            &signed_like,
+        ] {
+            let chunk = Chunk::new(chunk_type(), data.to_vec());
+
+            assert_eq!(chunk.envelope(), None);
+            assert_eq!(chunk.payload(), data);
+            assert!(!chunk.is_compressed() && !chunk.is_encrypted() && !chunk.is_signed());
+            assert!(!chunk.is_encrypted_for_recipients());
+            assert_eq!(chunk.verification(), Verification::Unsigned);
+            assert_eq!(
+                chunk.message_bytes(None, &ParseOptions::default()).unwrap(),
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn test_layers_follow_flags() {
+        // payload looks compressed, but envelope does not say so
+        let compressed = Compression::Deflate.compress(b"hello");
+        let chunk =
+            Chunk::enveloped(chunk_type(), &Envelope::new(ContentType::Text), &compressed).unwrap();
+        assert_eq!(
+            chunk.message_bytes(None, &ParseOptions::default()).unwrap(),
+            compressed
+        );
+
+        let envelope = Envelope {
+            compressed: true,
+            ..Envelope::new(ContentType::Text)
+        };
+        let chunk = Chunk::enveloped(chunk_type(), &envelope, &compressed).unwrap();
+        assert_eq!(chunk.message(&ParseOptions::default()).unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            file_envelope().to_string(),
+            "format 1, file 'report.json', created 2023-11-14 22:13:20 UTC, compressed, signed"
+        );
+        assert_eq!(utc_time(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(utc_time(951_782_400), "2000-02-29 00:00:00 UTC");
+    }
+}
@@ -0,0 +1,296 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::string::FromUtf8Error;
+
+use crate::png::Png;
+
+/// Position of a chunk inside png file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// Index of chunk in png (0 is the chunk right after signature)
+    pub index: usize,
+    /// Byte offset of chunk start (its length field) from the beginning of file
+    pub offset: usize,
+}
+
+/// Resource limit of [`ParseOptions`](crate::ParseOptions)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    ChunkLength,
+    ChunkCount,
+    FileSize,
+    DecompressedSize,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Limit::ChunkLength => "chunk length",
+            Limit::ChunkCount => "number of chunks",
+            Limit::FileSize => "file size",
+            Limit::DecompressedSize => "decompressed size",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Input is shorter than png signature
+    MissingSignature {
+        len: usize,
+    },
+    /// Input does not start with png signature
+    InvalidSignature {
+        found: [u8; 8],
+    },
+    /// Chunk needs more bytes than are left in input
+    TruncatedChunk {
+        needed: usize,
+        available: usize,
+        location: Option<Location>,
+    },
+    /// Stored crc does not match crc calculated from chunk type and data
+    CrcMismatch {
+        chunk_type: String,
+        expected: u32,
+        actual: u32,
+        location: Option<Location>,
+    },
+    /// Chunk type contains byte which is not an ascii letter
+    InvalidChunkTypeByte {
+        byte: u8,
+        /// Position of byte inside chunk type (0..4)
+        position: usize,
+        location: Option<Location>,
+    },
+    /// Chunk type is not 4 bytes long
+    InvalidChunkTypeLength {
+        len: usize,
+    },
+    /// Input exceeds one of parsing resource limits
+    LimitExceeded {
+        limit: Limit,
+        value: u64,
+        max: u64,
+        location: Option<Location>,
+    },
+    /// Chunk would break chunk ordering rules of png specification
+    InvalidChunkPosition {
+        chunk_type: String,
+        reason: &'static str,
+    },
+    /// No chunk with given type is present in png
+    ChunkNotFound {
+        chunk_type: String,
+    },
+    /// Png breaks rules of png specification (see [`Png::validate`])
+    ValidationFailed {
+        errors: usize,
+    },
+    /// IHDR chunk is missing, repeated or contains invalid values
+    InvalidHeader(String),
+    /// tEXt, zTXt or iTXt chunk has invalid keyword, text or layout
+    InvalidText(String),
+    /// No text chunk with given keyword is present in png
+    TextNotFound {
+        keyword: String,
+    },
+    /// Zlib stream stored in chunk cannot be decompressed
+    InvalidCompressedData(String),
+    /// Decompressed IDAT data does not match image header
+    InvalidImageData(String),
+    /// PLTE or tRNS chunk has invalid layout
+    InvalidPalette(String),
+    /// Image or its selected channels cannot carry hidden data
+    UnsupportedCarrier(String),
+    /// Hidden data does not fit into carrier
+    CapacityExceeded {
+        needed: usize,
+        available: usize,
+    },
+    /// Passphrase does not match the one used to encrypt message
+    WrongPassphrase,
+    /// Encrypted message is malformed or fails authentication
+    CorruptedCiphertext(String),
+    /// Key or key file cannot be used
+    InvalidKey(String),
+    /// Message is not encrypted for public key of given identity
+    NotRecipient,
+    /// Signature of message is missing, does not match or was made by another key
+    UnverifiedMessage(String),
+    /// Filename or payload does not fit into envelope of hidden message
+    InvalidEnvelope(String),
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    Utf8(FromUtf8Error),
+}
+
+impl Error {
+    /// Attach chunk location to error if it describes chunk, but doesnt know where it is
+    pub fn at(mut self, chunk_location: Location) -> Self {
+        match &mut self {
+            Error::TruncatedChunk { location, .. }
+            | Error::CrcMismatch { location, .. }
+            | Error::InvalidChunkTypeByte { location, .. }
+            | Error::LimitExceeded { location, .. } => {
+                location.get_or_insert(chunk_location);
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Attach file path to io error
+    pub fn with_path<P: Into<PathBuf>>(mut self, file_path: P) -> Self {
+        if let Error::Io { path, .. } = &mut self {
+            path.get_or_insert_with(|| file_path.into());
+        }
+        self
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            Error::TruncatedChunk { location, .. }
+            | Error::CrcMismatch { location, .. }
+            | Error::InvalidChunkTypeByte { location, .. }
+            | Error::LimitExceeded { location, .. } => *location,
+            _ => None,
+        }
+    }
+
+    fn fmt_location(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = self.location() {
+            write!(
+                f,
+                " (chunk {} at offset {})",
+                location.index, location.offset
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingSignature { len } => write!(
+                f,
+                "Valid png must have at least 8 bytes (missing header) but only {} were provided",
+                len
+            ),
+            Error::InvalidSignature { found } => write!(
+                f,
+                "Valid png must contain valid signature header ({:?}), but {:?} header was provided",
+                Png::STANDARD_HEADER,
+                found
+            ),
+            Error::TruncatedChunk {
+                needed, available, ..
+            } => {
+                write!(
+                    f,
+                    "Chunk is truncated, it needs {} bytes but only {} were provided",
+                    needed, available
+                )?;
+                self.fmt_location(f)
+            }
+            Error::CrcMismatch {
+                chunk_type,
+                expected,
+                actual,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Input crc of chunk {} is incorrect, got {}, but {} is correct crc",
+                    chunk_type, actual, expected
+                )?;
+                self.fmt_location(f)
+            }
+            Error::InvalidChunkTypeByte { byte, position, .. } => {
+                write!(
+                    f,
+                    "{} is not a valid png byte (it must be a letter) at position {} of chunk type",
+                    byte, position
+                )?;
+                self.fmt_location(f)
+            }
+            Error::InvalidChunkTypeLength { len } => {
+                write!(f, "Required 4 byte string got {} bytes", len)
+            }
+            Error::LimitExceeded {
+                limit, value, max, ..
+            } => {
+                write!(f, "{} {} exceeds limit of {}", limit, value, max)?;
+                self.fmt_location(f)
+            }
+            Error::InvalidChunkPosition { chunk_type, reason } => {
+                write!(f, "Cannot place chunk {} there, it {}", chunk_type, reason)
+            }
+            Error::ChunkNotFound { chunk_type } => {
+                write!(f, "No chunk with type '{}' was found", chunk_type)
+            }
+            Error::ValidationFailed { errors } => {
+                write!(f, "Png is not valid, found {} errors", errors)
+            }
+            Error::InvalidHeader(reason) => write!(f, "Invalid image header: {}", reason),
+            Error::InvalidText(reason) => write!(f, "Invalid text chunk: {}", reason),
+            Error::TextNotFound { keyword } => {
+                write!(f, "No text chunk with keyword '{}' was found", keyword)
+            }
+            Error::InvalidCompressedData(reason) => {
+                write!(f, "Invalid compressed data: {}", reason)
+            }
+            Error::InvalidImageData(reason) => write!(f, "Invalid image data: {}", reason),
+            Error::WrongPassphrase => write!(f, "Wrong passphrase, message cannot be decrypted"),
+            Error::CorruptedCiphertext(reason) => {
+                write!(f, "Encrypted message is corrupted: {}", reason)
+            }
+            Error::InvalidKey(reason) => write!(f, "Invalid key: {}", reason),
+            Error::NotRecipient => write!(f, "Message is not encrypted for this identity"),
+            Error::UnverifiedMessage(reason) => {
+                write!(f, "Message signature cannot be verified: {}", reason)
+            }
+            Error::InvalidEnvelope(reason) => write!(f, "Invalid message envelope: {}", reason),
+            Error::InvalidPalette(reason) => write!(f, "Invalid palette: {}", reason),
+            Error::UnsupportedCarrier(reason) => write!(f, "Unsupported carrier: {}", reason),
+            Error::CapacityExceeded { needed, available } => write!(
+                f,
+                "Hidden data needs {} bytes, but carrier can hold only {} bytes",
+                needed, available
+            ),
+            Error::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "Cannot access file {}, cause: {}", path.display(), source),
+            Error::Io { path: None, source } => write!(f, "I/O error: {}", source),
+            Error::Utf8(err) => write!(f, "Invalid message data: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Utf8(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io { path: None, source }
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(err: FromUtf8Error) -> Self {
+        Error::Utf8(err)
+    }
+}
@@ -0,0 +1,217 @@
+use std::fmt;
+
+use crate::{Error, Result};
+
+/// Filter applied to single scanline of image data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    None,
+    Sub,
+    Up,
+    Average,
+    Paeth,
+}
+
+impl FilterType {
+    pub const ALL: [FilterType; 5] = [
+        FilterType::None,
+        FilterType::Sub,
+        FilterType::Up,
+        FilterType::Average,
+        FilterType::Paeth,
+    ];
+
+    pub fn value(&self) -> u8 {
+        match self {
+            FilterType::None => 0,
+            FilterType::Sub => 1,
+            FilterType::Up => 2,
+            FilterType::Average => 3,
+            FilterType::Paeth => 4,
+        }
+    }
+}
+
+impl TryFrom<u8> for FilterType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        FilterType::ALL
+            .get(value as usize)
+            .copied()
+            .ok_or_else(|| Error::InvalidImageData(format!("unknown filter type {}", value)))
+    }
+}
+
+impl fmt::Display for FilterType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FilterType::None => "none",
+            FilterType::Sub => "sub",
+            FilterType::Up => "up",
+            FilterType::Average => "average",
+            FilterType::Paeth => "paeth",
+        };
+        write!(f, "{} ({})", name, self.value())
+    }
+}
+
+fn paeth(left: u8, above: u8, upper_left: u8) -> u8 {
+    let estimate = left as i16 + above as i16 - upper_left as i16;
+    let distance_left = (estimate - left as i16).abs();
+    let distance_above = (estimate - above as i16).abs();
+    let distance_upper_left = (estimate - upper_left as i16).abs();
+
+    if distance_left <= distance_above && distance_left <= distance_upper_left {
+        left
+    } else if distance_above <= distance_upper_left {
+        above
+    } else {
+        upper_left
+    }
+}
+
+/// Reverse filter of scanline in place. `previous` is already unfiltered previous scanline
+/// (zeros for the first one) and `bpp` is number of bytes per complete pixel (at least 1)
+pub(crate) fn unfilter(filter: FilterType, row: &mut [u8], previous: &[u8], bpp: usize) {
+    match filter {
+        FilterType::None => {}
+        FilterType::Sub => {
+            for i in bpp..row.len() {
+                row[i] = row[i].wrapping_add(row[i - bpp]);
+            }
+        }
+        FilterType::Up => {
+            for (byte, above) in row.iter_mut().zip(previous) {
+                *byte = byte.wrapping_add(*above);
+            }
+        }
+        FilterType::Average => {
+            for i in 0..row.len() {
+                let left = if i >= bpp { row[i - bpp] } else { 0 };
+                let average = ((left as u16 + previous[i] as u16) / 2) as u8;
+                row[i] = row[i].wrapping_add(average);
+            }
+        }
+        FilterType::Paeth => {
+            for i in 0..row.len() {
+                let (left, upper_left) = if i >= bpp {
+                    (row[i - bpp], previous[i - bpp])
+                } else {
+                    (0, 0)
+                };
+                row[i] = row[i].wrapping_add(paeth(left, previous[i], upper_left));
+            }
+        }
+    }
+}
+
+/// Filter scanline into output. `previous` is unfiltered previous scanline
+/// (zeros for the first one) and `bpp` is number of bytes per complete pixel (at least 1)
+pub(crate) fn filter(
+    filter: FilterType,
+    row: &[u8],
+    previous: &[u8],
+    bpp: usize,
+    output: &mut Vec<u8>,
+) {
+    let left = |i: usize| if i >= bpp { row[i - bpp] } else { 0 };
+    let upper_left = |i: usize| if i >= bpp { previous[i - bpp] } else { 0 };
+
+    output.extend(row.iter().enumerate().map(|(i, &byte)| {
+        let predictor = match filter {
+            FilterType::None => 0,
+            FilterType::Sub => left(i),
+            FilterType::Up => previous[i],
+            FilterType::Average => ((left(i) as u16 + previous[i] as u16) / 2) as u8,
+            FilterType::Paeth => paeth(left(i), previous[i], upper_left(i)),
+        };
+        byte.wrapping_sub(predictor)
+    }));
+}
+
+/// Pick filter which gives the smallest sum of absolute differences (treating filtered
+/// bytes as signed), heuristic recommended by png specification
+pub(crate) fn select_filter(row: &[u8], previous: &[u8], bpp: usize) -> FilterType {
+    let mut filtered = Vec::with_capacity(row.len());
+    FilterType::ALL
+        .into_iter()
+        .min_by_key(|&filter_type| {
+            filtered.clear();
+            filter(filter_type, row, previous, bpp, &mut filtered);
+            filtered
+                .iter()
+                .map(|&byte| (byte as i8).unsigned_abs() as u64)
+                .sum::<u64>()
+        })
+        .unwrap_or(FilterType::None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_type_from_u8() {
+        assert_eq!(FilterType::try_from(4).unwrap(), FilterType::Paeth);
+        assert!(matches!(
+            FilterType::try_from(5),
+            Err(Error::InvalidImageData(_))
+        ));
+    }
+
+    #[test]
+    fn test_paeth() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 30), 10);
+        assert_eq!(paeth(100, 50, 200), 50);
+    }
+
+    #[test]
+    fn test_unfilter() {
+        let previous = [10, 20, 30, 40];
+
+        let mut row = [1, 2, 3, 4];
+        unfilter(FilterType::Sub, &mut row, &previous, 2);
+        assert_eq!(row, [1, 2, 4, 6]);
+
+        let mut row = [1, 2, 3, 4];
+        unfilter(FilterType::Up, &mut row, &previous, 2);
+        assert_eq!(row, [11, 22, 33, 44]);
+
+        let mut row = [1, 2, 3, 4];
+        unfilter(FilterType::Average, &mut row, &previous, 2);
+        assert_eq!(row, [6, 12, 3 + (6 + 30) / 2, 4 + (12 + 40) / 2]);
+
+        let mut row = [1, 2, 255, 4];
+        unfilter(FilterType::Paeth, &mut row, &previous, 2);
+        assert_eq!(row, [11, 22, 255u8.wrapping_add(30), 44]);
+    }
+
+    #[test]
+    fn test_filter_roundtrip() {
+        let previous = [200, 3, 17, 90, 255, 0];
+        let row = [1, 250, 3, 77, 128, 64];
+
+        for filter_type in FilterType::ALL {
+            let mut filtered = Vec::new();
+            filter(filter_type, &row, &previous, 3, &mut filtered);
+            unfilter(filter_type, &mut filtered, &previous, 3);
+            assert_eq!(filtered, row);
+        }
+    }
+
+    #[test]
+    fn test_select_filter() {
+        assert_eq!(select_filter(&[0; 8], &[0; 8], 1), FilterType::None);
+        assert_eq!(
+            select_filter(&[9, 9, 9, 9], &[9, 9, 9, 9], 1),
+            FilterType::Up
+        );
+        assert_eq!(
+            select_filter(&[10, 20, 30, 40], &[0; 4], 1),
+            FilterType::Sub
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use std::fmt;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    /// Number of samples in a single pixel
+    pub fn channels(&self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        matches!(self, ColorType::GrayscaleAlpha | ColorType::Rgba)
+    }
+
+    /// Bit depths allowed for color type by png specification
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(Error::InvalidHeader(format!(
+                "{} is not a valid color type",
+                value
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ColorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "grayscale",
+            ColorType::Rgb => "RGB",
+            ColorType::Indexed => "indexed",
+            ColorType::GrayscaleAlpha => "grayscale with alpha",
+            ColorType::Rgba => "RGB with alpha",
+        };
+        write!(f, "{} ({})", name, self.value())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterlaceMethod {
+    None,
+    Adam7,
+}
+
+impl fmt::Display for InterlaceMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterlaceMethod::None => write!(f, "none (0)"),
+            InterlaceMethod::Adam7 => write!(f, "Adam7 (1)"),
+        }
+    }
+}
+
+/// Image header stored in IHDR chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: InterlaceMethod,
+}
+
+impl Ihdr {
+    /// Largest width or height allowed by png specification
+    pub const MAX_DIMENSION: u32 = (1 << 31) - 1;
+
+    /// Create header with standard compression, filter and no interlacing, validating it
+    pub fn new(width: u32, height: u32, bit_depth: u8, color_type: ColorType) -> Result<Ihdr> {
+        let ihdr = Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        };
+        ihdr.validate()?;
+        Ok(ihdr)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (name, dimension) in [("width", self.width), ("height", self.height)] {
+            if dimension == 0 || dimension > Self::MAX_DIMENSION {
+                return Err(Error::InvalidHeader(format!(
+                    "image {} must be between 1 and {}, but it is {}",
+                    name,
+                    Self::MAX_DIMENSION,
+                    dimension
+                )));
+            }
+        }
+        if !self
+            .color_type
+            .allowed_bit_depths()
+            .contains(&self.bit_depth)
+        {
+            return Err(Error::InvalidHeader(format!(
+                "bit depth {} is not allowed for {} color type",
+                self.bit_depth, self.color_type
+            )));
+        }
+        if self.compression_method != 0 {
+            return Err(Error::InvalidHeader(format!(
+                "unknown compression method {}",
+                self.compression_method
+            )));
+        }
+        if self.filter_method != 0 {
+            return Err(Error::InvalidHeader(format!(
+                "unknown filter method {}",
+                self.filter_method
+            )));
+        }
+        Ok(())
+    }
+
+    /// Number of bits used by a single pixel
+    pub fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.bit_depth as usize
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(13);
+        data.extend_from_slice(&self.width.to_be_bytes());
+        data.extend_from_slice(&self.height.to_be_bytes());
+        data.push(self.bit_depth);
+        data.push(self.color_type.value());
+        data.push(self.compression_method);
+        data.push(self.filter_method);
+        data.push(match self.interlace_method {
+            InterlaceMethod::None => 0,
+            InterlaceMethod::Adam7 => 1,
+        });
+
+        Chunk::new(ChunkType::IHDR, data)
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if *chunk.chunk_type() != ChunkType::IHDR {
+            return Err(Error::InvalidHeader(format!(
+                "expected IHDR chunk, but got {}",
+                chunk.chunk_type()
+            )));
+        }
+        let data = chunk.data();
+        if data.len() != 13 {
+            return Err(Error::InvalidHeader(format!(
+                "IHDR must have 13 bytes of data, but it has {}",
+                data.len()
+            )));
+        }
+
+        let interlace_method = match data[12] {
+            0 => InterlaceMethod::None,
+            1 => InterlaceMethod::Adam7,
+            method => {
+                return Err(Error::InvalidHeader(format!(
+                    "unknown interlace method {}",
+                    method
+                )))
+            }
+        };
+        let ihdr = Ihdr {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            bit_depth: data[8],
+            color_type: ColorType::try_from(data[9])?,
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method,
+        };
+        ihdr.validate()?;
+
+        Ok(ihdr)
+    }
+}
+
+impl fmt::Display for Ihdr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Ihdr {{")?;
+        writeln!(f, "   Width: {}", self.width)?;
+        writeln!(f, "   Height: {}", self.height)?;
+        writeln!(f, "   Bit depth: {}", self.bit_depth)?;
+        writeln!(f, "   Color type: {}", self.color_type)?;
+        writeln!(f, "   Compression method: {}", self.compression_method)?;
+        writeln!(f, "   Filter method: {}", self.filter_method)?;
+        writeln!(f, "   Interlace method: {}", self.interlace_method)?;
+        writeln!(f, "   }}")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr_chunk(bit_depth: u8, color_type: u8) -> Chunk {
+        let mut data = Vec::new();
+        data.extend_from_slice(&50_u32.to_be_bytes());
+        data.extend_from_slice(&20_u32.to_be_bytes());
+        data.extend_from_slice(&[bit_depth, color_type, 0, 0, 1]);
+        Chunk::new(ChunkType::IHDR, data)
+    }
+
+    #[test]
+    fn test_parse_ihdr() {
+        let ihdr = Ihdr::try_from(&ihdr_chunk(16, 6)).unwrap();
+
+        assert_eq!(ihdr.width, 50);
+        assert_eq!(ihdr.height, 20);
+        assert_eq!(ihdr.bit_depth, 16);
+        assert_eq!(ihdr.color_type, ColorType::Rgba);
+        assert_eq!(ihdr.interlace_method, InterlaceMethod::Adam7);
+        assert_eq!(ihdr.bits_per_pixel(), 64);
+    }
+
+    #[test]
+    fn test_invalid_bit_depth_for_color_type() {
+        assert!(Ihdr::try_from(&ihdr_chunk(4, 2)).is_err());
+        assert!(Ihdr::try_from(&ihdr_chunk(16, 3)).is_err());
+        assert!(Ihdr::try_from(&ihdr_chunk(3, 0)).is_err());
+        assert!(Ihdr::try_from(&ihdr_chunk(8, 5)).is_err());
+    }
+
+    #[test]
+    fn test_zero_dimension() {
+        assert!(Ihdr::new(0, 10, 8, ColorType::Rgb).is_err());
+        assert!(Ihdr::new(10, 10, 8, ColorType::Rgb).is_ok());
+    }
+
+    #[test]
+    fn test_to_chunk_roundtrip() {
+        let ihdr = Ihdr::new(7, 3, 2, ColorType::Indexed).unwrap();
+        assert_eq!(Ihdr::try_from(&ihdr.to_chunk()).unwrap(), ihdr);
+    }
+}
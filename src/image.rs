@@ -0,0 +1,614 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::Limit;
+use crate::filter::{filter, select_filter, unfilter, FilterType};
+use crate::ihdr::{Ihdr, InterlaceMethod};
+use crate::png::Png;
+use crate::reader::ParseOptions;
+use crate::zlib::{deflate, inflate_prefix};
+use crate::{Error, Result};
+
+/// Samples of image, one element per sample (channel of pixel), row by row.
+/// Samples of bit depth below 8 are unpacked, so each of them takes a whole byte
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Samples {
+    /// Samples of bit depth 1, 2, 4 or 8
+    Eight(Vec<u8>),
+    /// Samples of bit depth 16
+    Sixteen(Vec<u16>),
+}
+
+impl Samples {
+    pub fn len(&self) -> usize {
+        match self {
+            Samples::Eight(samples) => samples.len(),
+            Samples::Sixteen(samples) => samples.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> u16 {
+        match self {
+            Samples::Eight(samples) => samples[index] as u16,
+            Samples::Sixteen(samples) => samples[index],
+        }
+    }
+
+    /// Set sample at index (value is truncated to 8 bits for [`Samples::Eight`])
+    pub fn set(&mut self, index: usize, value: u16) {
+        match self {
+            Samples::Eight(samples) => samples[index] = value as u8,
+            Samples::Sixteen(samples) => samples[index] = value,
+        }
+    }
+}
+
+/// Decoded pixels of png image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub header: Ihdr,
+    pub samples: Samples,
+}
+
+/// How filter type of every scanline is chosen when image is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterSelection {
+    /// Pick filter giving the smallest sum of absolute differences for each scanline
+    #[default]
+    Adaptive,
+    /// Use the same filter for every scanline
+    Fixed(FilterType),
+}
+
+/// Parameters of writing image into IDAT chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdatOptions {
+    pub filter: FilterSelection,
+    /// Zlib compression level (0-9)
+    pub compression_level: u32,
+    /// Maximum data length of single IDAT chunk
+    pub chunk_size: usize,
+}
+
+impl Default for IdatOptions {
+    fn default() -> Self {
+        IdatOptions {
+            filter: FilterSelection::Adaptive,
+            compression_level: 6,
+            chunk_size: 8192,
+        }
+    }
+}
+
+/// Subimage of interlaced image (the whole image for non interlaced one)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Pass {
+    pub(crate) x: usize,
+    pub(crate) y: usize,
+    pub(crate) dx: usize,
+    pub(crate) dy: usize,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+}
+
+/// Starting column, row and their steps of every Adam7 pass
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Passes of image in order they are stored, passes without pixels are skipped
+pub(crate) fn passes(header: &Ihdr) -> Vec<Pass> {
+    let (width, height) = (header.width as usize, header.height as usize);
+    let steps: &[_] = match header.interlace_method {
+        InterlaceMethod::None => &[(0, 0, 1, 1)],
+        InterlaceMethod::Adam7 => &ADAM7,
+    };
+
+    steps
+        .iter()
+        .map(|&(x, y, dx, dy)| Pass {
+            x,
+            y,
+            dx,
+            dy,
+            width: (width + dx - 1 - x) / dx,
+            height: (height + dy - 1 - y) / dy,
+        })
+        .filter(|pass| pass.width > 0 && pass.height > 0)
+        .collect()
+}
+
+/// Number of bytes in scanline (without filter byte) of given width
+pub(crate) fn row_len(header: &Ihdr, width: usize) -> usize {
+    (width * header.bits_per_pixel()).div_ceil(8)
+}
+
+/// Size of decompressed image data, computed without overflow for any valid header
+fn data_len(header: &Ihdr) -> u64 {
+    passes(header)
+        .iter()
+        .map(|pass| pass.height as u64 * (1 + row_len(header, pass.width) as u64))
+        .sum()
+}
+
+impl Image {
+    /// Create image with all samples set to zero
+    pub fn new(header: Ihdr) -> Result<Image> {
+        header.validate()?;
+        let len = header.width as usize * header.height as usize * header.color_type.channels();
+        let samples = if header.bit_depth == 16 {
+            Samples::Sixteen(vec![0; len])
+        } else {
+            Samples::Eight(vec![0; len])
+        };
+
+        Ok(Image { header, samples })
+    }
+
+    pub fn width(&self) -> usize {
+        self.header.width as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.header.height as usize
+    }
+
+    pub fn channels(&self) -> usize {
+        self.header.color_type.channels()
+    }
+
+    /// Largest value of sample at image bit depth
+    pub fn max_sample(&self) -> u16 {
+        ((1u32 << self.header.bit_depth) - 1) as u16
+    }
+
+    fn index(&self, x: usize, y: usize, channel: usize) -> usize {
+        (y * self.width() + x) * self.channels() + channel
+    }
+
+    pub fn sample(&self, x: usize, y: usize, channel: usize) -> u16 {
+        self.samples.get(self.index(x, y, channel))
+    }
+
+    pub fn set_sample(&mut self, x: usize, y: usize, channel: usize, value: u16) {
+        let index = self.index(x, y, channel);
+        self.samples.set(index, value);
+    }
+
+    /// All samples of pixel
+    pub fn pixel(&self, x: usize, y: usize) -> Vec<u16> {
+        (0..self.channels())
+            .map(|channel| self.sample(x, y, channel))
+            .collect()
+    }
+
+    /// Decode pixels from decompressed image data (filtered scanlines of every pass)
+    pub(crate) fn decode(header: Ihdr, data: &[u8]) -> Result<Image> {
+        let mut image = Image::new(header)?;
+        let bpp = header.bits_per_pixel().div_ceil(8);
+        let depth = header.bit_depth as usize;
+        let channels = image.channels();
+
+        let mut data = data;
+        for pass in passes(&header) {
+            let row_len = row_len(&header, pass.width);
+            let mut previous = vec![0; row_len];
+            let mut row = vec![0; row_len];
+
+            for pass_y in 0..pass.height {
+                let (line, rest) = match data.split_first() {
+                    Some((&filter, rest)) if rest.len() >= row_len => (filter, rest),
+                    _ => {
+                        return Err(Error::InvalidImageData(
+                            "image data is shorter than image size requires".to_string(),
+                        ))
+                    }
+                };
+                row.copy_from_slice(&rest[..row_len]);
+                data = &rest[row_len..];
+                unfilter(FilterType::try_from(line)?, &mut row, &previous, bpp);
+
+                let y = pass.y + pass_y * pass.dy;
+                for pass_x in 0..pass.width {
+                    let x = pass.x + pass_x * pass.dx;
+                    for channel in 0..channels {
+                        let sample_index = pass_x * channels + channel;
+                        let value = match depth {
+                            16 => u16::from_be_bytes([
+                                row[sample_index * 2],
+                                row[sample_index * 2 + 1],
+                            ]),
+                            8 => row[sample_index] as u16,
+                            _ => {
+                                let bit = sample_index * depth;
+                                let shift = 8 - depth - bit % 8;
+                                ((row[bit / 8] >> shift) & ((1 << depth) - 1) as u8) as u16
+                            }
+                        };
+                        image.set_sample(x, y, channel, value);
+                    }
+                }
+                std::mem::swap(&mut row, &mut previous);
+            }
+        }
+
+        Ok(image)
+    }
+}
+
+impl Image {
+    fn check_samples(&self) -> Result<()> {
+        let len = self.width() * self.height() * self.channels();
+        if self.samples.len() != len {
+            return Err(Error::InvalidImageData(format!(
+                "image needs {} samples, but it has {}",
+                len,
+                self.samples.len()
+            )));
+        }
+        let max = self.max_sample();
+        if let Some(index) = (0..len).find(|&index| self.samples.get(index) > max) {
+            return Err(Error::InvalidImageData(format!(
+                "sample {} at index {} does not fit in bit depth {}",
+                self.samples.get(index),
+                index,
+                self.header.bit_depth
+            )));
+        }
+        Ok(())
+    }
+
+    /// Filtered scanlines of every pass, ready to be compressed
+    pub(crate) fn encode(&self, selection: FilterSelection) -> Result<Vec<u8>> {
+        self.check_samples()?;
+        let header = self.header;
+        let bpp = header.bits_per_pixel().div_ceil(8);
+        let depth = header.bit_depth as usize;
+        let channels = self.channels();
+
+        let mut data = Vec::with_capacity(data_len(&header) as usize);
+        for pass in passes(&header) {
+            let row_len = row_len(&header, pass.width);
+            let mut previous = vec![0; row_len];
+            let mut row = vec![0; row_len];
+
+            for pass_y in 0..pass.height {
+                row.fill(0);
+                let y = pass.y + pass_y * pass.dy;
+                for pass_x in 0..pass.width {
+                    let x = pass.x + pass_x * pass.dx;
+                    for channel in 0..channels {
+                        let sample_index = pass_x * channels + channel;
+                        let value = self.sample(x, y, channel);
+                        match depth {
+                            16 => row[sample_index * 2..sample_index * 2 + 2]
+                                .copy_from_slice(&value.to_be_bytes()),
+                            8 => row[sample_index] = value as u8,
+                            _ => {
+                                let bit = sample_index * depth;
+                                row[bit / 8] |= (value as u8) << (8 - depth - bit % 8);
+                            }
+                        }
+                    }
+                }
+
+                let filter_type = match selection {
+                    FilterSelection::Adaptive => select_filter(&row, &previous, bpp),
+                    FilterSelection::Fixed(filter_type) => filter_type,
+                };
+                data.push(filter_type.value());
+                filter(filter_type, &row, &previous, bpp, &mut data);
+                std::mem::swap(&mut row, &mut previous);
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Filter, compress and split image into IDAT chunks
+    pub fn to_idat_chunks(&self, options: &IdatOptions) -> Result<Vec<Chunk>> {
+        let compressed = deflate(&self.encode(options.filter)?, options.compression_level);
+        let chunk_size = options.chunk_size.clamp(1, Chunk::MAX_LENGTH as usize);
+
+        Ok(compressed
+            .chunks(chunk_size)
+            .map(|data| Chunk::new(ChunkType::IDAT, data.to_vec()))
+            .collect())
+    }
+}
+
+impl Png {
+    /// Replace image data with pixels of given image, leaving every other chunk untouched.
+    /// Image must have the same header as png
+    pub fn set_image(&mut self, image: &Image, options: &IdatOptions) -> Result<()> {
+        let header = self.header_info()?;
+        if header != image.header {
+            return Err(Error::InvalidImageData(
+                "image header does not match IHDR of png".to_string(),
+            ));
+        }
+
+        self.replace_image_data(image.to_idat_chunks(options)?);
+        Ok(())
+    }
+
+    /// Concatenated data of all IDAT chunks
+    pub fn image_data(&self) -> Vec<u8> {
+        self.chunks()
+            .iter()
+            .filter(|chunk| *chunk.chunk_type() == ChunkType::IDAT)
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect()
+    }
+
+    /// Decode pixels stored in IDAT chunks
+    pub fn decode_image(&self) -> Result<Image> {
+        self.decode_image_with(&ParseOptions::default())
+    }
+
+    /// Decode pixels stored in IDAT chunks, limiting decompressed and decoded size
+    pub fn decode_image_with(&self, options: &ParseOptions) -> Result<Image> {
+        let header = self.header_info()?;
+        let expected = data_len(&header);
+        options.check(Limit::DecompressedSize, expected)?;
+        let bytes_per_sample = if header.bit_depth == 16 { 2 } else { 1 };
+        options.check(
+            Limit::DecompressedSize,
+            header.width as u64
+                * header.height as u64
+                * header.color_type.channels() as u64
+                * bytes_per_sample,
+        )?;
+
+        // data after the last scanline is ignored
+        let data = inflate_prefix(&self.image_data(), expected)?;
+
+        Image::decode(header, &data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::ColorType;
+    use crate::png::ChunkPosition;
+    use crate::zlib::deflate;
+
+    fn testing_png(header: Ihdr, data: &[u8]) -> Png {
+        Png::from_chunks(vec![
+            header.to_chunk(),
+            Chunk::new(ChunkType::IDAT, deflate(data, 6)),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_decode_rgb() {
+        let header = Ihdr::new(2, 2, 8, ColorType::Rgb).unwrap();
+        let data = [
+            0, 1, 2, 3, 4, 5, 6, //
+            2, 1, 1, 1, 1, 1, 1,
+        ];
+        let image = testing_png(header, &data).decode_image().unwrap();
+
+        assert_eq!(image.pixel(0, 0), [1, 2, 3]);
+        assert_eq!(image.pixel(1, 0), [4, 5, 6]);
+        assert_eq!(image.pixel(0, 1), [2, 3, 4]);
+        assert_eq!(image.pixel(1, 1), [5, 6, 7]);
+    }
+
+    #[test]
+    fn test_decode_low_bit_depth() {
+        let header = Ihdr::new(10, 2, 1, ColorType::Grayscale).unwrap();
+        let data = [0, 0b1010_0000, 0b0100_0000, 0, 0b1111_1111, 0b1100_0000];
+        let image = testing_png(header, &data).decode_image().unwrap();
+
+        assert_eq!(
+            image.samples,
+            Samples::Eight(vec![
+                1, 0, 1, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+            ])
+        );
+
+        let header = Ihdr::new(3, 1, 2, ColorType::Indexed).unwrap();
+        let image = testing_png(header, &[0, 0b1110_0100])
+            .decode_image()
+            .unwrap();
+        assert_eq!(image.samples, Samples::Eight(vec![3, 2, 1]));
+    }
+
+    #[test]
+    fn test_decode_sixteen_bit() {
+        let header = Ihdr::new(1, 1, 16, ColorType::GrayscaleAlpha).unwrap();
+        let image = testing_png(header, &[0, 0x12, 0x34, 0xff, 0xfe])
+            .decode_image()
+            .unwrap();
+
+        assert_eq!(image.samples, Samples::Sixteen(vec![0x1234, 0xfffe]));
+        assert_eq!(image.max_sample(), 0xffff);
+    }
+
+    fn interlaced(width: u32, height: u32) -> (Png, Image) {
+        let header = Ihdr {
+            interlace_method: InterlaceMethod::Adam7,
+            ..Ihdr::new(width, height, 8, ColorType::Grayscale).unwrap()
+        };
+        let mut expected = Image::new(header).unwrap();
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                expected.set_sample(x, y, 0, (y * width as usize + x) as u16);
+            }
+        }
+
+        let mut data = Vec::new();
+        for pass in passes(&header) {
+            for pass_y in 0..pass.height {
+                data.push(0);
+                for pass_x in 0..pass.width {
+                    let value =
+                        expected.sample(pass.x + pass_x * pass.dx, pass.y + pass_y * pass.dy, 0);
+                    data.push(value as u8);
+                }
+            }
+        }
+        (testing_png(header, &data), expected)
+    }
+
+    #[test]
+    fn test_decode_adam7() {
+        for (width, height) in [(8, 8), (3, 3), (1, 1), (13, 5)] {
+            let (png, expected) = interlaced(width, height);
+            assert_eq!(png.decode_image().unwrap(), expected);
+        }
+        assert_eq!(passes(&interlaced(1, 1).1.header).len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_image_data() {
+        let header = Ihdr::new(2, 2, 8, ColorType::Grayscale).unwrap();
+
+        assert!(matches!(
+            testing_png(header, &[0, 1, 2]).decode_image(),
+            Err(Error::InvalidImageData(_))
+        ));
+        assert!(matches!(
+            testing_png(header, &[0, 1, 2, 7, 1, 2]).decode_image(),
+            Err(Error::InvalidImageData(_))
+        ));
+        // extra data after last scanline is ignored
+        assert!(testing_png(header, &[0, 1, 2, 0, 1, 2, 9])
+            .decode_image()
+            .is_ok());
+
+        let options = ParseOptions {
+            max_decompressed_size: 5,
+            ..ParseOptions::default()
+        };
+        assert!(matches!(
+            testing_png(header, &[0, 1, 2, 0, 1, 2]).decode_image_with(&options),
+            Err(Error::LimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let headers = [
+            Ihdr::new(7, 5, 1, ColorType::Grayscale).unwrap(),
+            Ihdr::new(7, 5, 4, ColorType::Indexed).unwrap(),
+            Ihdr::new(7, 5, 8, ColorType::Rgb).unwrap(),
+            Ihdr::new(7, 5, 16, ColorType::Rgba).unwrap(),
+            Ihdr {
+                interlace_method: InterlaceMethod::Adam7,
+                ..Ihdr::new(11, 9, 2, ColorType::Grayscale).unwrap()
+            },
+            Ihdr {
+                interlace_method: InterlaceMethod::Adam7,
+                ..Ihdr::new(11, 9, 16, ColorType::GrayscaleAlpha).unwrap()
+            },
+        ];
+        let selections = [
+            FilterSelection::Adaptive,
+            FilterSelection::Fixed(FilterType::Average),
+            FilterSelection::Fixed(FilterType::Paeth),
+        ];
+
+        for header in headers {
+            let mut image = Image::new(header).unwrap();
+            let max = image.max_sample() as usize;
+            for index in 0..image.samples.len() {
+                image
+                    .samples
+                    .set(index, ((index * 7919) % (max + 1)) as u16);
+            }
+
+            for filter in selections {
+                let mut png = testing_png(header, &[]);
+                let options = IdatOptions {
+                    filter,
+                    ..IdatOptions::default()
+                };
+                png.set_image(&image, &options).unwrap();
+                assert_eq!(png.decode_image().unwrap(), image);
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_image_splits_idat() {
+        let header = Ihdr::new(20, 20, 8, ColorType::Rgb).unwrap();
+        let mut png = testing_png(header, &[]);
+        png.insert_chunk(
+            Chunk::from_strings("ruSt", "message").unwrap(),
+            ChunkPosition::AfterIdat,
+        )
+        .unwrap();
+        let mut image = Image::new(header).unwrap();
+        image.set_sample(3, 4, 1, 200);
+
+        let options = IdatOptions {
+            compression_level: 0,
+            chunk_size: 100,
+            ..IdatOptions::default()
+        };
+        png.set_image(&image, &options).unwrap();
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        let idats = types
+            .iter()
+            .filter(|chunk_type| *chunk_type == "IDAT")
+            .count();
+        assert!(idats > 10);
+        assert_eq!(types[1..=idats], vec!["IDAT".to_string(); idats]);
+        assert_eq!(types[idats + 1..], ["ruSt", "IEND"]);
+        assert_eq!(png.decode_image().unwrap(), image);
+    }
+
+    #[test]
+    fn test_set_image_errors() {
+        let header = Ihdr::new(2, 2, 4, ColorType::Grayscale).unwrap();
+        let mut png = testing_png(header, &[]);
+
+        let mut image = Image::new(header).unwrap();
+        image.set_sample(0, 0, 0, 16);
+        assert!(matches!(
+            png.set_image(&image, &IdatOptions::default()),
+            Err(Error::InvalidImageData(_))
+        ));
+
+        let image = Image::new(Ihdr::new(2, 3, 4, ColorType::Grayscale).unwrap()).unwrap();
+        assert!(png.set_image(&image, &IdatOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_decode_image_file() {
+        let png = Png::from_file("tests/inputs/valid.png").unwrap();
+        let image = png.decode_image().unwrap();
+
+        assert_eq!((image.width(), image.height()), (50, 50));
+        assert_eq!(image.samples.len(), 50 * 50 * 4);
+
+        let mut reencoded = png.clone();
+        reencoded
+            .set_image(&image, &IdatOptions::default())
+            .unwrap();
+        assert_eq!(reencoded.decode_image().unwrap(), image);
+        for (old, new) in png.chunks().iter().zip(reencoded.chunks()) {
+            if *old.chunk_type() != ChunkType::IDAT {
+                assert_eq!(old.as_bytes(), new.as_bytes());
+            }
+        }
+    }
+}
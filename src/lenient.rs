@@ -0,0 +1,382 @@
+use std::fs;
+use std::path::Path;
+
+use crate::chunk::{Chunk, CRC_CALCULATOR};
+use crate::chunk_type::ChunkType;
+use crate::error::{Limit, Location};
+use crate::png::Png;
+use crate::reader::ParseOptions;
+use crate::validate::{Issue, Severity};
+use crate::{Error, Result};
+
+/// Resync hashes at most this many times size of input (plus minimum below), so input
+/// crafted with fake headers of long chunks at every offset cannot make recovery quadratic
+const HASH_BUDGET_FACTOR: usize = 4;
+const MIN_HASH_BUDGET: usize = 1 << 20;
+
+/// Chunks recovered from possibly corrupted input, with problems found on the way
+pub(crate) struct Recovery<'a> {
+    bytes: &'a [u8],
+    options: ParseOptions,
+    pub(crate) chunks: Vec<Chunk>,
+    /// Offset of every recovered chunk in input
+    pub(crate) offsets: Vec<usize>,
+    pub(crate) issues: Vec<Issue>,
+    /// Offset right after the last recovered chunk, never past the end of input
+    pub(crate) chunks_end: usize,
+    offset: usize,
+    /// Bytes which may still be hashed while looking for intact chunks
+    hash_budget: usize,
+}
+
+impl<'a> Recovery<'a> {
+    /// Recover chunks from whole input, checking its signature first
+    pub(crate) fn run(bytes: &'a [u8], options: ParseOptions) -> Result<Recovery<'a>> {
+        options.check(Limit::FileSize, bytes.len() as u64)?;
+        if bytes.len() < Png::STANDARD_HEADER.len() {
+            return Err(Error::MissingSignature { len: bytes.len() });
+        }
+        if bytes[..8] != Png::STANDARD_HEADER {
+            let mut found = [0; 8];
+            found.copy_from_slice(&bytes[..8]);
+            return Err(Error::InvalidSignature { found });
+        }
+
+        let mut recovery = Recovery {
+            bytes,
+            options,
+            chunks: Vec::new(),
+            offsets: Vec::new(),
+            issues: Vec::new(),
+            chunks_end: Png::STANDARD_HEADER.len(),
+            offset: Png::STANDARD_HEADER.len(),
+            hash_budget: HASH_BUDGET_FACTOR * bytes.len() + MIN_HASH_BUDGET,
+        };
+        recovery.recover_chunks()?;
+        Ok(recovery)
+    }
+
+    fn location(&self) -> Location {
+        Location {
+            index: self.chunks.len(),
+            offset: self.offset,
+        }
+    }
+
+    fn report(&mut self, code: &'static str, message: String) {
+        self.issues.push(Issue {
+            severity: Severity::Error,
+            code,
+            location: Some(self.location()),
+            message,
+        });
+    }
+
+    /// Length and type of chunk starting at offset, if they look like a real chunk header
+    fn header_at(&self, offset: usize) -> Option<(usize, ChunkType)> {
+        let header = self.bytes.get(offset..offset + 8)?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let chunk_type = ChunkType::try_from([header[4], header[5], header[6], header[7]]).ok()?;
+
+        self.options
+            .check(Limit::ChunkLength, len as u64)
+            .ok()
+            .map(|_| (len as usize, chunk_type))
+    }
+
+    fn fits(&self, offset: usize, len: usize) -> bool {
+        offset + 12 + len <= self.bytes.len()
+    }
+
+    /// Whether there is a complete chunk with correct crc at offset. Once hash budget is
+    /// spent, no more chunks are considered intact
+    fn is_intact_chunk(&mut self, offset: usize) -> bool {
+        match self.header_at(offset) {
+            Some((len, _)) if self.fits(offset, len) && len + 4 <= self.hash_budget => {
+                self.hash_budget -= len + 4;
+                let crc_start = offset + 8 + len;
+                let stored = &self.bytes[crc_start..crc_start + 4];
+                CRC_CALCULATOR
+                    .checksum(&self.bytes[offset + 4..crc_start])
+                    .to_be_bytes()
+                    == stored
+            }
+            _ => false,
+        }
+    }
+
+    /// Offset of the first intact chunk after given offset
+    fn resync(&mut self, from: usize) -> Option<usize> {
+        (from..self.bytes.len().saturating_sub(11)).find(|&offset| self.is_intact_chunk(offset))
+    }
+
+    fn skip_garbage(&mut self, end: usize) {
+        self.report(
+            "garbage",
+            format!(
+                "skipped {} bytes which do not form a valid chunk",
+                end - self.offset
+            ),
+        );
+        self.offset = end;
+    }
+
+    fn read_chunk(&mut self, len: usize, chunk_type: ChunkType) -> Result<()> {
+        let (chunk, expected_crc) =
+            Chunk::read_body(&mut &self.bytes[self.offset + 8..], len as u32, chunk_type)?;
+        if chunk.crc() != expected_crc {
+            self.report(
+                "crc-mismatch",
+                format!(
+                    "crc of chunk {} is {}, but {} is correct crc",
+                    chunk_type,
+                    chunk.crc(),
+                    expected_crc
+                ),
+            );
+        }
+        self.push(chunk, 12 + len);
+        Ok(())
+    }
+
+    fn recover_truncated(&mut self, len: usize, chunk_type: ChunkType) {
+        // cut off crc bytes are not part of data
+        let data_start = self.offset + 8;
+        let data = self.bytes[data_start..(data_start + len).min(self.bytes.len())].to_vec();
+        let missing = if data.len() == len {
+            "crc is missing".to_string()
+        } else {
+            format!("recovered {} of its {} data bytes", data.len(), len)
+        };
+        self.report(
+            "truncated-chunk",
+            format!(
+                "chunk {} needs {} bytes but only {} were provided, {}",
+                chunk_type,
+                12 + len,
+                self.bytes.len() - self.offset,
+                missing
+            ),
+        );
+        self.push(Chunk::new(chunk_type, data), self.bytes.len() - self.offset);
+    }
+
+    /// Add chunk which took `consumed` bytes of input
+    fn push(&mut self, chunk: Chunk, consumed: usize) {
+        self.offsets.push(self.offset);
+        self.offset += consumed;
+        self.chunks_end = self.offset;
+        self.chunks.push(chunk);
+    }
+
+    fn recover_chunks(&mut self) -> Result<()> {
+        while self.offset < self.bytes.len() {
+            let location = self.location();
+            self.options
+                .check(Limit::ChunkCount, self.chunks.len() as u64 + 1)
+                .map_err(|err| err.at(location))?;
+
+            let next = self.offset + 1;
+            match self.header_at(self.offset) {
+                Some((len, chunk_type)) if self.fits(self.offset, len) => {
+                    self.read_chunk(len, chunk_type)?
+                }
+                // length pointing past the end is either corrupted or chunk was cut off
+                Some((len, chunk_type)) => match self.resync(next) {
+                    Some(next) => self.skip_garbage(next),
+                    None => self.recover_truncated(len, chunk_type),
+                },
+                None => {
+                    let end = self.resync(next).unwrap_or(self.bytes.len());
+                    self.skip_garbage(end);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Png {
+    /// Parse png without failing on corrupted chunks.
+    ///
+    /// Chunks with wrong crc are kept (together with their stored crc), garbage between chunks
+    /// is skipped up to the next intact chunk and data of truncated final chunk is recovered.
+    /// Every such problem is returned as an [`Issue`]. Only invalid signature and exceeded
+    /// resource limits still cause an error.
+    pub fn parse_lenient(bytes: &[u8], options: ParseOptions) -> Result<(Png, Vec<Issue>)> {
+        let recovery = Recovery::run(bytes, options)?;
+        Ok((Png::from_chunks(recovery.chunks), recovery.issues))
+    }
+
+    /// Read png file from given path in lenient mode (see [`Png::parse_lenient`])
+    pub fn from_file_lenient<P: AsRef<Path>>(
+        path: P,
+        options: ParseOptions,
+    ) -> Result<(Png, Vec<Issue>)> {
+        Png::parse_lenient(&read_file(path.as_ref(), options)?, options)
+    }
+}
+
+/// Read whole file into memory, checking its size against limits first
+pub(crate) fn read_file(path: &Path, options: ParseOptions) -> Result<Vec<u8>> {
+    let read = || -> Result<Vec<u8>> {
+        options.check(Limit::FileSize, fs::metadata(path)?.len())?;
+        Ok(fs::read(path)?)
+    };
+    read().map_err(|err| err.with_path(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::from_strings("FrSt", "I am the first chunk").unwrap(),
+            Chunk::from_strings("miDl", "I am another chunk").unwrap(),
+            Chunk::from_strings("LASt", "I am the last chunk").unwrap(),
+        ])
+    }
+
+    fn codes(issues: &[Issue]) -> Vec<&str> {
+        issues.iter().map(|issue| issue.code).collect()
+    }
+
+    #[test]
+    fn test_intact_png() {
+        let bytes = testing_png().as_bytes();
+        let (png, issues) = Png::parse_lenient(&bytes, ParseOptions::default()).unwrap();
+
+        assert!(issues.is_empty());
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_crc_mismatch_is_kept() {
+        let mut bytes = testing_png().as_bytes();
+        // last byte of crc of the second chunk
+        bytes[8 + 32 + 29] ^= 0xff;
+
+        assert!(Png::try_from(&bytes[..]).is_err());
+        let (png, issues) = Png::parse_lenient(&bytes, ParseOptions::default()).unwrap();
+
+        assert_eq!(codes(&issues), ["crc-mismatch"]);
+        assert_eq!(
+            issues[0].location,
+            Some(Location {
+                index: 1,
+                offset: 8 + 32
+            })
+        );
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(
+            png.chunks()[1].data_as_string().unwrap(),
+            "I am another chunk"
+        );
+        assert!(!png.chunks()[1].has_valid_crc());
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_garbage_between_chunks() {
+        let png = testing_png();
+        let mut bytes = png.as_bytes();
+        bytes.splice(8 + 32..8 + 32, b"garbage!".iter().copied());
+
+        let (recovered, issues) = Png::parse_lenient(&bytes, ParseOptions::default()).unwrap();
+
+        assert_eq!(codes(&issues), ["garbage"]);
+        assert!(issues[0].message.contains("skipped 8 bytes"));
+        assert_eq!(recovered.as_bytes(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_corrupted_length() {
+        let png = testing_png();
+        let mut bytes = png.as_bytes();
+        // length of the second chunk now points past the end of input
+        bytes[8 + 32] = 0x10;
+
+        let (recovered, issues) = Png::parse_lenient(&bytes, ParseOptions::default()).unwrap();
+
+        assert_eq!(codes(&issues), ["garbage"]);
+        let types: Vec<String> = recovered
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["FrSt", "LASt"]);
+    }
+
+    #[test]
+    fn test_truncated_final_chunk() {
+        let bytes = testing_png().as_bytes();
+        let (png, issues) =
+            Png::parse_lenient(&bytes[..bytes.len() - 6], ParseOptions::default()).unwrap();
+
+        assert_eq!(codes(&issues), ["truncated-chunk"]);
+        assert_eq!(issues[0].location.unwrap().index, 2);
+        assert_eq!(
+            png.chunks()[2].data_as_string().unwrap(),
+            "I am the last chu"
+        );
+    }
+
+    #[test]
+    fn test_truncated_crc() {
+        let bytes = testing_png().as_bytes();
+        let recovery = Recovery::run(&bytes[..bytes.len() - 2], ParseOptions::default()).unwrap();
+
+        assert_eq!(codes(&recovery.issues), ["truncated-chunk"]);
+        assert!(recovery.issues[0].message.contains("crc is missing"));
+        assert_eq!(
+            recovery.chunks[2].data_as_string().unwrap(),
+            "I am the last chunk"
+        );
+        assert_eq!(recovery.chunks_end, bytes.len() - 2);
+    }
+
+    #[test]
+    fn test_trailing_garbage() {
+        let mut bytes = testing_png().as_bytes();
+        bytes.extend_from_slice(&[0, 1, 2]);
+
+        let (png, issues) = Png::parse_lenient(&bytes, ParseOptions::default()).unwrap();
+
+        assert_eq!(codes(&issues), ["garbage"]);
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_resync_work_is_bounded() {
+        // fake header of long chunk at every 8 bytes, each of them would hash about 1 MiB
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        for _ in 0..(1 << 18) {
+            bytes.extend_from_slice(&[0, 0x0f, 0, 0, b'r', b'u', b'S', b't']);
+        }
+        bytes[8] = 0xff;
+
+        let start = std::time::Instant::now();
+        let (png, issues) = Png::parse_lenient(&bytes, ParseOptions::default()).unwrap();
+        assert!(start.elapsed().as_secs() < 10);
+        assert!(png.chunks().is_empty());
+        assert_eq!(codes(&issues), ["garbage"]);
+    }
+
+    #[test]
+    fn test_limits_still_apply() {
+        let bytes = testing_png().as_bytes();
+        let options = ParseOptions {
+            max_chunks: 2,
+            ..ParseOptions::default()
+        };
+
+        assert!(matches!(
+            Png::parse_lenient(&bytes, options),
+            Err(Error::LimitExceeded {
+                limit: Limit::ChunkCount,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,563 @@
+use std::str::FromStr;
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::Limit;
+use crate::ihdr::ColorType;
+use crate::image::Image;
+use crate::reader::ParseOptions;
+use crate::{Error, Result};
+
+/// Channels of pixels which carry hidden bits.
+/// For grayscale images any of red, green and blue selects the gray channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Channels {
+    pub red: bool,
+    pub green: bool,
+    pub blue: bool,
+    pub alpha: bool,
+}
+
+impl Channels {
+    pub const RGB: Channels = Channels {
+        red: true,
+        green: true,
+        blue: true,
+        alpha: false,
+    };
+
+    /// Indices of selected channels inside pixel of given color type
+    fn indices(&self, color_type: ColorType) -> Result<Vec<usize>> {
+        let color = self.red || self.green || self.blue;
+        let mut indices =
+            match color_type {
+                ColorType::Indexed => return Err(Error::UnsupportedCarrier(
+                    "samples of indexed image are palette indices, so their bits cannot be changed"
+                        .to_string(),
+                )),
+                ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                    if color {
+                        vec![0]
+                    } else {
+                        vec![]
+                    }
+                }
+                ColorType::Rgb | ColorType::Rgba => [self.red, self.green, self.blue]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, selected)| **selected)
+                    .map(|(index, _)| index)
+                    .collect(),
+            };
+
+        if self.alpha {
+            if !color_type.has_alpha() {
+                return Err(Error::UnsupportedCarrier(format!(
+                    "{} image has no alpha channel",
+                    color_type
+                )));
+            }
+            indices.push(color_type.channels() - 1);
+        }
+        if indices.is_empty() {
+            return Err(Error::UnsupportedCarrier(
+                "no channel was selected".to_string(),
+            ));
+        }
+        Ok(indices)
+    }
+}
+
+impl FromStr for Channels {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut channels = Channels {
+            red: false,
+            green: false,
+            blue: false,
+            alpha: false,
+        };
+        for c in s.chars() {
+            let channel = match c.to_ascii_lowercase() {
+                'r' => &mut channels.red,
+                'g' => &mut channels.green,
+                'b' => &mut channels.blue,
+                'a' => &mut channels.alpha,
+                _ => {
+                    return Err(Error::UnsupportedCarrier(format!(
+                        "'{}' is not a channel, use letters r, g, b and a",
+                        c
+                    )))
+                }
+            };
+            *channel = true;
+        }
+        Ok(channels)
+    }
+}
+
+/// Where hidden bits are stored in image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsbOptions {
+    pub channels: Channels,
+    /// Number of least significant bits of every used sample which carry hidden data
+    pub bits_per_sample: u8,
+    /// Passphrase scattering hidden bits over pseudo-random samples instead of writing
+    /// them in order. Fully transparent pixels are then skipped
+    pub key: Option<String>,
+}
+
+impl Default for LsbOptions {
+    fn default() -> Self {
+        LsbOptions {
+            channels: Channels::RGB,
+            bits_per_sample: 1,
+            key: None,
+        }
+    }
+}
+
+/// Seed of samples permutation derived from passphrase
+fn seed(key: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"pngme lsb key\0");
+    hasher.update(key.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Fisher–Yates shuffle driven by ChaCha20 stream of given seed. Written out instead of using
+/// `SliceRandom::shuffle`, whose algorithm is not guaranteed to stay the same across rand
+/// versions, but it decides where keyed messages are stored.
+///
+/// Going from the last element, element `i` is swapped with element `j` drawn uniformly from
+/// `0..=i`: `j` is `next_u64() % (i + 1)`, where draws below `2^64 % (i + 1)` are rejected
+/// so every `j` is equally likely.
+fn shuffle(samples: &mut [usize], seed: [u8; 32]) {
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    for i in (1..samples.len()).rev() {
+        let bound = i as u64 + 1;
+        let threshold = bound.wrapping_neg() % bound;
+        let j = loop {
+            let draw = rng.next_u64();
+            if draw >= threshold {
+                break draw % bound;
+            }
+        };
+        samples.swap(i, j as usize);
+    }
+}
+
+/// Samples carrying hidden bits, in order in which bits are written
+pub(crate) struct Carrier {
+    samples: Vec<usize>,
+    bits_per_sample: usize,
+}
+
+impl Carrier {
+    pub(crate) fn new(image: &Image, options: &LsbOptions) -> Result<Carrier> {
+        let depth = image.header.bit_depth;
+        if !(1..=depth).contains(&options.bits_per_sample) {
+            return Err(Error::UnsupportedCarrier(format!(
+                "cannot hide {} bits in samples of bit depth {}",
+                options.bits_per_sample, depth
+            )));
+        }
+
+        // transparent pixels are recognized by alpha bits which do not carry data
+        if options.key.is_some() && options.channels.alpha && options.bits_per_sample == depth {
+            return Err(Error::UnsupportedCarrier(format!(
+                "keyed embedding into alpha channel must leave at least one of {} alpha bits \
+                 unchanged",
+                depth
+            )));
+        }
+
+        let indices = options.channels.indices(image.header.color_type)?;
+        let channels = image.channels();
+        let pixels = image.width() * image.height();
+        let mut samples: Vec<usize> = (0..pixels)
+            .filter(|&pixel| options.key.is_none() || !is_transparent(image, pixel, options))
+            .flat_map(|pixel| {
+                indices
+                    .iter()
+                    .map(move |channel| pixel * channels + channel)
+            })
+            .collect();
+
+        if let Some(key) = &options.key {
+            shuffle(&mut samples, seed(key));
+        }
+
+        Ok(Carrier {
+            samples,
+            bits_per_sample: options.bits_per_sample as usize,
+        })
+    }
+
+    /// Number of bits which can be hidden
+    pub(crate) fn bit_count(&self) -> usize {
+        self.samples.len() * self.bits_per_sample
+    }
+
+    /// Number of whole bytes which can be hidden
+    fn capacity(&self) -> usize {
+        self.bit_count() / 8
+    }
+
+    /// Sample index and bit position inside it of n-th hidden bit (bits of every
+    /// byte go from the most significant one)
+    fn position(&self, bit: usize) -> (usize, usize) {
+        let sample = self.samples[bit / self.bits_per_sample];
+        (
+            sample,
+            self.bits_per_sample - 1 - bit % self.bits_per_sample,
+        )
+    }
+
+    pub(crate) fn bit(&self, image: &Image, bit: usize) -> u8 {
+        let (sample, shift) = self.position(bit);
+        ((image.samples.get(sample) >> shift) & 1) as u8
+    }
+
+    /// Flip n-th hidden bit, returning index of changed sample
+    pub(crate) fn flip(&self, image: &mut Image, bit: usize) -> usize {
+        let (sample, shift) = self.position(bit);
+        image
+            .samples
+            .set(sample, image.samples.get(sample) ^ (1 << shift));
+        sample
+    }
+
+    /// Write data from the first hidden bit, returning number of changed samples
+    fn write(&self, image: &mut Image, data: &[u8]) -> usize {
+        let mut changed = Vec::new();
+        for bit in 0..data.len() * 8 {
+            let value = (data[bit / 8] >> (7 - bit % 8)) & 1;
+            if self.bit(image, bit) != value {
+                changed.push(self.flip(image, bit));
+            }
+        }
+        count_distinct(changed)
+    }
+
+    fn read(&self, image: &Image, start: usize, len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        for bit in 0..len * 8 {
+            data[bit / 8] |= self.bit(image, start * 8 + bit) << (7 - bit % 8);
+        }
+        data
+    }
+}
+
+/// Number of distinct sample indices
+pub(crate) fn count_distinct(mut samples: Vec<usize>) -> usize {
+    samples.sort_unstable();
+    samples.dedup();
+    samples.len()
+}
+
+/// Whether pixel is fully transparent. Bits which may carry hidden data are ignored,
+/// so embedding into alpha channel does not change which pixels are skipped
+fn is_transparent(image: &Image, pixel: usize, options: &LsbOptions) -> bool {
+    if !image.header.color_type.has_alpha() {
+        return false;
+    }
+    let channels = image.channels();
+    let alpha = image.samples.get(pixel * channels + channels - 1);
+    if options.channels.alpha {
+        alpha
+            .checked_shr(options.bits_per_sample as u32)
+            .unwrap_or(0)
+            == 0
+    } else {
+        alpha == 0
+    }
+}
+
+impl Image {
+    /// Number of bytes which can be hidden in image, including 12 bytes of chunk framing
+    pub fn lsb_capacity(&self, options: &LsbOptions) -> Result<usize> {
+        Ok(Carrier::new(self, options)?.capacity())
+    }
+
+    /// Hide chunk (with its length, type and crc) in least significant bits of samples,
+    /// returning number of changed samples
+    pub fn embed_lsb(&mut self, chunk: &Chunk, options: &LsbOptions) -> Result<usize> {
+        let carrier = Carrier::new(self, options)?;
+        let data = chunk.as_bytes();
+        if data.len() > carrier.capacity() {
+            return Err(Error::CapacityExceeded {
+                needed: data.len(),
+                available: carrier.capacity(),
+            });
+        }
+
+        Ok(carrier.write(self, &data))
+    }
+
+    /// Read chunk hidden with [`Image::embed_lsb`]. Returns `None` if bits do not form
+    /// a valid chunk (image holds no message or it was hidden with different options)
+    pub fn extract_lsb(
+        &self,
+        options: &LsbOptions,
+        parse_options: &ParseOptions,
+    ) -> Result<Option<Chunk>> {
+        let carrier = Carrier::new(self, options)?;
+        read_hidden_chunk(
+            |len| carrier.read(self, 0, len),
+            carrier.capacity(),
+            parse_options,
+        )
+    }
+}
+
+/// Parse hidden chunk from carrier of `capacity` bytes, whose first bytes are given by `read`.
+/// Returns `None` if carrier does not start with valid chunk
+pub(crate) fn read_hidden_chunk(
+    read: impl Fn(usize) -> Vec<u8>,
+    capacity: usize,
+    parse_options: &ParseOptions,
+) -> Result<Option<Chunk>> {
+    if capacity < 12 {
+        return Ok(None);
+    }
+
+    let header = read(8);
+    let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    if ChunkType::try_from([header[4], header[5], header[6], header[7]]).is_err()
+        || 12 + len as usize > capacity
+    {
+        return Ok(None);
+    }
+    parse_options.check(Limit::ChunkLength, len as u64)?;
+
+    let bytes = read(12 + len as usize);
+    Ok(Chunk::try_from(&bytes[..]).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::Ihdr;
+
+    fn testing_image(color_type: ColorType, bit_depth: u8) -> Image {
+        let header = Ihdr::new(20, 10, bit_depth, color_type).unwrap();
+        let mut image = Image::new(header).unwrap();
+        let max = image.max_sample() as usize;
+        for index in 0..image.samples.len() {
+            image.samples.set(index, ((index * 31) % (max + 1)) as u16);
+        }
+        image
+    }
+
+    #[test]
+    fn test_channels_from_str() {
+        assert_eq!("rgb".parse::<Channels>().unwrap(), Channels::RGB);
+        let channels: Channels = "Ba".parse().unwrap();
+        assert!(channels.blue && channels.alpha && !channels.red);
+        assert!("rgx".parse::<Channels>().is_err());
+    }
+
+    #[test]
+    fn test_channel_indices() {
+        assert_eq!(Channels::RGB.indices(ColorType::Rgba).unwrap(), [0, 1, 2]);
+        assert_eq!(
+            Channels::RGB.indices(ColorType::GrayscaleAlpha).unwrap(),
+            [0]
+        );
+        let alpha: Channels = "ga".parse().unwrap();
+        assert_eq!(alpha.indices(ColorType::Rgba).unwrap(), [1, 3]);
+        assert!(alpha.indices(ColorType::Rgb).is_err());
+        assert!(Channels::RGB.indices(ColorType::Indexed).is_err());
+    }
+
+    #[test]
+    fn test_embed_and_extract() {
+        let chunk = Chunk::from_strings("ruSt", "hidden message").unwrap();
+        let cases = [
+            (ColorType::Rgb, 8, LsbOptions::default()),
+            (ColorType::Rgba, 16, LsbOptions::default()),
+            (
+                ColorType::GrayscaleAlpha,
+                8,
+                LsbOptions {
+                    channels: "ra".parse().unwrap(),
+                    bits_per_sample: 3,
+                    key: None,
+                },
+            ),
+            (
+                ColorType::Grayscale,
+                2,
+                LsbOptions {
+                    channels: Channels::RGB,
+                    bits_per_sample: 2,
+                    key: Some("key".to_string()),
+                },
+            ),
+        ];
+
+        for (color_type, depth, options) in cases {
+            let original = testing_image(color_type, depth);
+            let mut image = original.clone();
+            image.embed_lsb(&chunk, &options).unwrap();
+
+            let extracted = image
+                .extract_lsb(&options, &ParseOptions::default())
+                .unwrap()
+                .unwrap();
+            assert_eq!(extracted.as_bytes(), chunk.as_bytes());
+
+            let mask = (1 << options.bits_per_sample) - 1;
+            for index in 0..image.samples.len() {
+                assert_eq!(
+                    image.samples.get(index) & !mask,
+                    original.samples.get(index) & !mask
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut image = testing_image(ColorType::Rgb, 8);
+        let options = LsbOptions::default();
+        assert_eq!(image.lsb_capacity(&options).unwrap(), 20 * 10 * 3 / 8);
+
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![7; 64]);
+        assert!(matches!(
+            image.embed_lsb(&chunk, &options),
+            Err(Error::CapacityExceeded {
+                needed: 76,
+                available: 75
+            })
+        ));
+
+        let too_many_bits = LsbOptions {
+            bits_per_sample: 9,
+            ..options
+        };
+        assert!(image.lsb_capacity(&too_many_bits).is_err());
+    }
+
+    #[test]
+    fn test_extract_without_message() {
+        let image = testing_image(ColorType::Rgb, 8);
+        assert!(image
+            .extract_lsb(&LsbOptions::default(), &ParseOptions::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_shuffle_is_stable() {
+        // stored layout of keyed messages depends on it, so it must never change
+        let mut samples: Vec<usize> = (0..16).collect();
+        shuffle(&mut samples, seed("correct horse"));
+        assert_eq!(
+            samples,
+            [7, 6, 9, 13, 12, 5, 2, 11, 14, 15, 8, 4, 3, 10, 1, 0]
+        );
+    }
+
+    #[test]
+    fn test_keyed_embedding() {
+        let chunk = Chunk::from_strings("ruSt", "hidden message").unwrap();
+        let keyed = LsbOptions {
+            key: Some("correct horse".to_string()),
+            ..LsbOptions::default()
+        };
+        let original = testing_image(ColorType::Rgb, 8);
+        let mut image = original.clone();
+        image.embed_lsb(&chunk, &keyed).unwrap();
+
+        let extracted = image.extract_lsb(&keyed, &ParseOptions::default());
+        assert_eq!(extracted.unwrap().unwrap().as_bytes(), chunk.as_bytes());
+
+        let wrong_key = LsbOptions {
+            key: Some("wrong horse".to_string()),
+            ..LsbOptions::default()
+        };
+        for options in [wrong_key, LsbOptions::default()] {
+            assert!(image
+                .extract_lsb(&options, &ParseOptions::default())
+                .unwrap()
+                .is_none());
+        }
+
+        // changed samples are scattered over the whole image
+        let last_changed = (0..image.samples.len())
+            .filter(|&index| image.samples.get(index) != original.samples.get(index))
+            .max()
+            .unwrap();
+        assert!(last_changed > image.samples.len() / 2);
+    }
+
+    #[test]
+    fn test_keyed_embedding_rgba16() {
+        let chunk = Chunk::from_strings("ruSt", "hidden").unwrap();
+        let mut image = testing_image(ColorType::Rgba, 16);
+        // only the most significant alpha bit is left when hiding 15 bits
+        for pixel in 0..200 {
+            let alpha = if pixel < 100 { 0x7fff } else { 0x8000 };
+            image.set_sample(pixel % 20, pixel / 20, 3, alpha);
+        }
+
+        for (bits_per_sample, transparent) in [(8, 0), (15, 100)] {
+            let options = LsbOptions {
+                channels: "rgba".parse().unwrap(),
+                bits_per_sample,
+                key: Some("key".to_string()),
+            };
+            let samples = (200 - transparent) * 4;
+            assert_eq!(
+                image.lsb_capacity(&options).unwrap(),
+                samples * bits_per_sample as usize / 8
+            );
+            let mut embedded = image.clone();
+            embedded.embed_lsb(&chunk, &options).unwrap();
+            let extracted = embedded.extract_lsb(&options, &ParseOptions::default());
+            assert_eq!(extracted.unwrap().unwrap().as_bytes(), chunk.as_bytes());
+        }
+
+        let all_bits = LsbOptions {
+            channels: "rgba".parse().unwrap(),
+            bits_per_sample: 16,
+            key: Some("key".to_string()),
+        };
+        assert!(matches!(
+            image.clone().embed_lsb(&chunk, &all_bits),
+            Err(Error::UnsupportedCarrier(_))
+        ));
+    }
+
+    #[test]
+    fn test_keyed_embedding_skips_transparent_pixels() {
+        let chunk = Chunk::from_strings("ruSt", "hidden").unwrap();
+        let mut image = testing_image(ColorType::Rgba, 8);
+        for pixel in 0..200 {
+            let alpha = if pixel < 100 { 0 } else { 255 };
+            image.set_sample(pixel % 20, pixel / 20, 3, alpha);
+        }
+        let original = image.clone();
+
+        let options = LsbOptions {
+            channels: "rgba".parse().unwrap(),
+            key: Some("key".to_string()),
+            ..LsbOptions::default()
+        };
+        assert_eq!(image.lsb_capacity(&options).unwrap(), 100 * 4 / 8);
+        image.embed_lsb(&chunk, &options).unwrap();
+
+        for index in 0..400 {
+            assert_eq!(image.samples.get(index), original.samples.get(index));
+        }
+        let extracted = image.extract_lsb(&options, &ParseOptions::default());
+        assert_eq!(extracted.unwrap().unwrap().as_bytes(), chunk.as_bytes());
+    }
+}
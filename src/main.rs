@@ -1,5 +1,13 @@
-use pngme::{get_args, run};
+use std::process::ExitCode;
 
-fn main() -> pngme::Result<()> {
-    run(get_args())
+use pngme::cli::{exit_code, get_args, run};
+
+fn main() -> ExitCode {
+    match run(get_args()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(exit_code(&err))
+        }
+    }
 }
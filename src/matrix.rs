@@ -0,0 +1,228 @@
+use crate::chunk::Chunk;
+use crate::image::Image;
+use crate::lsb::{count_distinct, read_hidden_chunk, Carrier, LsbOptions};
+use crate::reader::ParseOptions;
+use crate::{Error, Result};
+
+/// Hamming code of matrix embedding: every block of `2^k - 1` carrier bits holds `k` message
+/// bits (as syndrome of the block), so hiding them changes at most one carrier bit
+struct HammingCode {
+    k: usize,
+}
+
+impl HammingCode {
+    const MAX_BITS: u8 = 16;
+
+    fn new(code_bits: u8) -> Result<HammingCode> {
+        if !(1..=Self::MAX_BITS).contains(&code_bits) {
+            return Err(Error::UnsupportedCarrier(format!(
+                "matrix code must hold between 1 and {} bits, got {}",
+                Self::MAX_BITS,
+                code_bits
+            )));
+        }
+        Ok(HammingCode {
+            k: code_bits as usize,
+        })
+    }
+
+    fn block_len(&self) -> usize {
+        (1 << self.k) - 1
+    }
+
+    /// Number of whole bytes which can be hidden in carrier
+    fn capacity(&self, carrier: &Carrier) -> usize {
+        carrier.bit_count() / self.block_len() * self.k / 8
+    }
+
+    /// Xor of (1-based) positions of set bits in block
+    fn syndrome(&self, image: &Image, carrier: &Carrier, block: usize) -> usize {
+        let start = block * self.block_len();
+        (0..self.block_len())
+            .filter(|i| carrier.bit(image, start + i) == 1)
+            .fold(0, |syndrome, i| syndrome ^ (i + 1))
+    }
+
+    /// Write data from the first block, returning number of changed samples
+    fn write(&self, image: &mut Image, carrier: &Carrier, data: &[u8]) -> usize {
+        let bits = data.len() * 8;
+        let mut changed = Vec::new();
+        for block in 0..bits.div_ceil(self.k) {
+            // message bits of block, the first one being the most significant (zero padded)
+            let value = (0..self.k).fold(0, |value, i| {
+                let bit = block * self.k + i;
+                let bit_value = if bit < bits {
+                    (data[bit / 8] >> (7 - bit % 8)) & 1
+                } else {
+                    0
+                };
+                (value << 1) | bit_value as usize
+            });
+
+            let difference = self.syndrome(image, carrier, block) ^ value;
+            if difference != 0 {
+                let bit = block * self.block_len() + difference - 1;
+                changed.push(carrier.flip(image, bit));
+            }
+        }
+        count_distinct(changed)
+    }
+
+    fn read(&self, image: &Image, carrier: &Carrier, len: usize) -> Vec<u8> {
+        let bits = len * 8;
+        let mut data = vec![0; len];
+        for block in 0..bits.div_ceil(self.k) {
+            let syndrome = self.syndrome(image, carrier, block);
+            for i in 0..self.k {
+                let bit = block * self.k + i;
+                if bit < bits {
+                    let value = ((syndrome >> (self.k - 1 - i)) & 1) as u8;
+                    data[bit / 8] |= value << (7 - bit % 8);
+                }
+            }
+        }
+        data
+    }
+}
+
+impl Image {
+    /// Number of bytes which can be hidden with matrix embedding using `code_bits` bits long
+    /// Hamming code, including 12 bytes of chunk framing
+    pub fn matrix_capacity(&self, options: &LsbOptions, code_bits: u8) -> Result<usize> {
+        let code = HammingCode::new(code_bits)?;
+        Ok(code.capacity(&Carrier::new(self, options)?))
+    }
+
+    /// Hide chunk in least significant bits of samples with matrix embedding, which changes
+    /// fewer samples than [`Image::embed_lsb`] at the cost of capacity (larger `code_bits`
+    /// means fewer changes). Returns number of changed samples
+    pub fn embed_matrix(
+        &mut self,
+        chunk: &Chunk,
+        options: &LsbOptions,
+        code_bits: u8,
+    ) -> Result<usize> {
+        let code = HammingCode::new(code_bits)?;
+        let carrier = Carrier::new(self, options)?;
+        let data = chunk.as_bytes();
+        if data.len() > code.capacity(&carrier) {
+            return Err(Error::CapacityExceeded {
+                needed: data.len(),
+                available: code.capacity(&carrier),
+            });
+        }
+
+        Ok(code.write(self, &carrier, &data))
+    }
+
+    /// Read chunk hidden with [`Image::embed_matrix`]. Returns `None` if image holds no message
+    /// or it was hidden with different options
+    pub fn extract_matrix(
+        &self,
+        options: &LsbOptions,
+        code_bits: u8,
+        parse_options: &ParseOptions,
+    ) -> Result<Option<Chunk>> {
+        let code = HammingCode::new(code_bits)?;
+        let carrier = Carrier::new(self, options)?;
+        read_hidden_chunk(
+            |len| code.read(self, &carrier, len),
+            code.capacity(&carrier),
+            parse_options,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::{ColorType, Ihdr};
+
+    fn testing_image() -> Image {
+        let header = Ihdr::new(40, 30, 8, ColorType::Rgb).unwrap();
+        let mut image = Image::new(header).unwrap();
+        for index in 0..image.samples.len() {
+            image.samples.set(index, ((index * 7919) % 251) as u16);
+        }
+        image
+    }
+
+    #[test]
+    fn test_embed_and_extract() {
+        let chunk = Chunk::from_strings("ruSt", "matrix embedded message").unwrap();
+        let keyed = LsbOptions {
+            key: Some("key".to_string()),
+            ..LsbOptions::default()
+        };
+
+        for options in [LsbOptions::default(), keyed] {
+            for code_bits in [1, 2, 3, 5] {
+                let mut image = testing_image();
+                image.embed_matrix(&chunk, &options, code_bits).unwrap();
+
+                let extracted = image
+                    .extract_matrix(&options, code_bits, &ParseOptions::default())
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(extracted.as_bytes(), chunk.as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn test_changes_fewer_samples() {
+        let chunk = Chunk::from_strings("ruSt", "matrix embedded message").unwrap();
+        let options = LsbOptions::default();
+
+        let original = testing_image();
+        let mut lsb = original.clone();
+        let lsb_changed = lsb.embed_lsb(&chunk, &options).unwrap();
+        let mut matrix = original.clone();
+        let matrix_changed = matrix.embed_matrix(&chunk, &options, 3).unwrap();
+
+        // at most one change per block of 3 message bits
+        assert!(matrix_changed <= (chunk.as_bytes().len() * 8).div_ceil(3));
+        assert!(matrix_changed < lsb_changed);
+        let differing = (0..original.samples.len())
+            .filter(|&index| matrix.samples.get(index) != original.samples.get(index))
+            .count();
+        assert_eq!(differing, matrix_changed);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let image = testing_image();
+        let options = LsbOptions::default();
+        let bits = 40 * 30 * 3;
+
+        assert_eq!(
+            image.matrix_capacity(&options, 1).unwrap(),
+            image.lsb_capacity(&options).unwrap()
+        );
+        assert_eq!(
+            image.matrix_capacity(&options, 3).unwrap(),
+            bits / 7 * 3 / 8
+        );
+        assert!(image.matrix_capacity(&options, 0).is_err());
+        assert!(image.matrix_capacity(&options, 17).is_err());
+
+        let chunk = Chunk::from_strings("ruSt", &"x".repeat(200)).unwrap();
+        assert!(matches!(
+            testing_image().embed_matrix(&chunk, &options, 4),
+            Err(Error::CapacityExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_extract_with_different_code() {
+        let chunk = Chunk::from_strings("ruSt", "message").unwrap();
+        let options = LsbOptions::default();
+        let mut image = testing_image();
+        image.embed_matrix(&chunk, &options, 3).unwrap();
+
+        assert!(image
+            .extract_matrix(&options, 4, &ParseOptions::default())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::ColorType;
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// Colors of PLTE chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub entries: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    pub const MAX_ENTRIES: usize = 256;
+
+    pub fn new(entries: Vec<[u8; 3]>) -> Result<Palette> {
+        let palette = Palette { entries };
+        palette.validate()?;
+        Ok(palette)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.entries.is_empty() || self.entries.len() > Self::MAX_ENTRIES {
+            return Err(Error::InvalidPalette(format!(
+                "PLTE must contain from 1 to {} entries, but it has {}",
+                Self::MAX_ENTRIES,
+                self.entries.len()
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::PLTE, self.entries.concat())
+    }
+}
+
+impl TryFrom<&Chunk> for Palette {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if *chunk.chunk_type() != ChunkType::PLTE {
+            return Err(Error::InvalidPalette(format!(
+                "expected PLTE chunk, but got {}",
+                chunk.chunk_type()
+            )));
+        }
+        let data = chunk.data();
+        if !data.len().is_multiple_of(3) {
+            return Err(Error::InvalidPalette(format!(
+                "PLTE length must be divisible by 3, but it is {}",
+                data.len()
+            )));
+        }
+
+        Palette::new(
+            data.chunks_exact(3)
+                .map(|entry| [entry[0], entry[1], entry[2]])
+                .collect(),
+        )
+    }
+}
+
+/// Contents of tRNS chunk, whose layout depends on color type of image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transparency {
+    /// Alpha of the first palette entries, the rest of them is opaque
+    Indexed(Vec<u8>),
+    /// Gray sample value which is fully transparent
+    Gray(u16),
+    /// Red, green and blue sample values of fully transparent color
+    Rgb([u16; 3]),
+}
+
+impl Transparency {
+    pub fn from_chunk(chunk: &Chunk, color_type: ColorType) -> Result<Transparency> {
+        if *chunk.chunk_type() != ChunkType::TRNS {
+            return Err(Error::InvalidPalette(format!(
+                "expected tRNS chunk, but got {}",
+                chunk.chunk_type()
+            )));
+        }
+        let data = chunk.data();
+        let sample = |i: usize| u16::from_be_bytes([data[2 * i], data[2 * i + 1]]);
+
+        match color_type {
+            ColorType::Indexed if data.len() <= Palette::MAX_ENTRIES => {
+                Ok(Transparency::Indexed(data.to_vec()))
+            }
+            ColorType::Grayscale if data.len() == 2 => Ok(Transparency::Gray(sample(0))),
+            ColorType::Rgb if data.len() == 6 => {
+                Ok(Transparency::Rgb([sample(0), sample(1), sample(2)]))
+            }
+            ColorType::GrayscaleAlpha | ColorType::Rgba => Err(Error::InvalidPalette(
+                "image with alpha channel must not contain tRNS".to_string(),
+            )),
+            _ => Err(Error::InvalidPalette(format!(
+                "tRNS of {} image cannot have {} bytes",
+                color_type,
+                data.len()
+            ))),
+        }
+    }
+
+    /// Alpha of palette entry, opaque if image is not indexed
+    pub fn alpha(&self, index: usize) -> u8 {
+        match self {
+            Transparency::Indexed(alphas) => alphas.get(index).copied().unwrap_or(255),
+            _ => 255,
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = match self {
+            Transparency::Indexed(alphas) => alphas.clone(),
+            Transparency::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Transparency::Rgb(rgb) => rgb.iter().flat_map(|sample| sample.to_be_bytes()).collect(),
+        };
+        Chunk::new(ChunkType::TRNS, data)
+    }
+}
+
+impl Png {
+    /// Parse PLTE chunk, if png has one
+    pub fn palette(&self) -> Result<Option<Palette>> {
+        self.chunks()
+            .iter()
+            .find(|chunk| *chunk.chunk_type() == ChunkType::PLTE)
+            .map(Palette::try_from)
+            .transpose()
+    }
+
+    /// Parse tRNS chunk according to color type from IHDR, if png has one
+    pub fn transparency(&self) -> Result<Option<Transparency>> {
+        let Some(chunk) = self
+            .chunks()
+            .iter()
+            .find(|chunk| *chunk.chunk_type() == ChunkType::TRNS)
+        else {
+            return Ok(None);
+        };
+        let color_type = self.header_info()?.color_type;
+        let transparency = Transparency::from_chunk(chunk, color_type)?;
+
+        if let (Transparency::Indexed(alphas), Some(palette)) = (&transparency, self.palette()?) {
+            if alphas.len() > palette.len() {
+                return Err(Error::InvalidPalette(format!(
+                    "tRNS has {} entries, but PLTE only {}",
+                    alphas.len(),
+                    palette.len()
+                )));
+            }
+        }
+        Ok(Some(transparency))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::Ihdr;
+
+    #[test]
+    fn test_palette_from_chunk() {
+        let chunk = Chunk::new(ChunkType::PLTE, vec![1, 2, 3, 4, 5, 6]);
+        let palette = Palette::try_from(&chunk).unwrap();
+
+        assert_eq!(palette.entries, [[1, 2, 3], [4, 5, 6]]);
+        assert_eq!(palette.to_chunk().as_bytes(), chunk.as_bytes());
+        for data in [vec![], vec![1, 2, 3, 4], vec![0; 257 * 3]] {
+            assert!(matches!(
+                Palette::try_from(&Chunk::new(ChunkType::PLTE, data)),
+                Err(Error::InvalidPalette(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_transparency_from_chunk() {
+        let chunk = |data: &[u8]| Chunk::new(ChunkType::TRNS, data.to_vec());
+
+        let indexed = Transparency::from_chunk(&chunk(&[0, 128]), ColorType::Indexed).unwrap();
+        assert_eq!(indexed, Transparency::Indexed(vec![0, 128]));
+        assert_eq!(indexed.alpha(1), 128);
+        assert_eq!(indexed.alpha(2), 255);
+        assert_eq!(
+            Transparency::from_chunk(&chunk(&[1, 2]), ColorType::Grayscale).unwrap(),
+            Transparency::Gray(258)
+        );
+        let rgb = Transparency::from_chunk(&chunk(&[0, 1, 0, 2, 0, 3]), ColorType::Rgb).unwrap();
+        assert_eq!(rgb, Transparency::Rgb([1, 2, 3]));
+        assert_eq!(rgb.to_chunk().data(), [0, 1, 0, 2, 0, 3]);
+
+        assert!(Transparency::from_chunk(&chunk(&[1]), ColorType::Grayscale).is_err());
+        assert!(Transparency::from_chunk(&chunk(&[1, 2]), ColorType::Rgba).is_err());
+    }
+
+    #[test]
+    fn test_png_palette() {
+        let ihdr = Ihdr::new(1, 1, 8, ColorType::Indexed).unwrap();
+        let mut png = Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            Chunk::new(ChunkType::PLTE, vec![1, 2, 3]),
+            Chunk::new(ChunkType::TRNS, vec![0]),
+            Chunk::new(ChunkType::IEND, vec![]),
+        ]);
+
+        assert_eq!(png.palette().unwrap().unwrap().entries, [[1, 2, 3]]);
+        assert_eq!(
+            png.transparency().unwrap(),
+            Some(Transparency::Indexed(vec![0]))
+        );
+
+        png.replace_chunk(2, Chunk::new(ChunkType::TRNS, vec![0, 0]))
+            .unwrap();
+        assert!(png.transparency().is_err());
+    }
+}
@@ -0,0 +1,447 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::ColorType;
+use crate::lsb::read_hidden_chunk;
+use crate::palette::{Palette, Transparency};
+use crate::png::Png;
+use crate::reader::ParseOptions;
+use crate::{Error, IdatOptions, Result};
+
+/// Unsigned integer of any size stored as little endian 32 bit limbs, big enough to number
+/// all orders of 256 palette entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    fn zero() -> BigUint {
+        BigUint { limbs: Vec::new() }
+    }
+
+    /// Interpret bytes as big endian number
+    fn from_bytes(bytes: &[u8]) -> BigUint {
+        let mut number = BigUint::zero();
+        for &byte in bytes {
+            number.mul_add(256, byte as u32);
+        }
+        number
+    }
+
+    /// The lowest `len` bytes of number in big endian order
+    fn to_bytes(&self, len: usize) -> Vec<u8> {
+        let mut number = self.clone();
+        let mut bytes: Vec<u8> = (0..len).map(|_| number.div_rem(256) as u8).collect();
+        bytes.reverse();
+        bytes
+    }
+
+    /// self = self * factor + addend
+    fn mul_add(&mut self, factor: u32, addend: u32) {
+        let mut carry = addend as u64;
+        for limb in &mut self.limbs {
+            let value = *limb as u64 * factor as u64 + carry;
+            *limb = value as u32;
+            carry = value >> 32;
+        }
+        if carry != 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+
+    /// self = self / divisor, returning remainder
+    fn div_rem(&mut self, divisor: u32) -> u32 {
+        let mut remainder = 0u64;
+        for limb in self.limbs.iter_mut().rev() {
+            let value = (remainder << 32) | *limb as u64;
+            *limb = (value / divisor as u64) as u32;
+            remainder = value % divisor as u64;
+        }
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        remainder as u32
+    }
+
+    /// Number of bits needed to write number
+    fn bit_len(&self) -> usize {
+        self.limbs.last().map_or(0, |last| {
+            self.limbs.len() * 32 - last.leading_zeros() as usize
+        })
+    }
+}
+
+/// Number of whole bytes which can be stored in order of `entries` distinct entries,
+/// that is floor(log2(entries!)) / 8
+fn capacity(entries: usize) -> usize {
+    let mut factorial = BigUint::from_bytes(&[1]);
+    for factor in 2..=entries {
+        factorial.mul_add(factor as u32, 0);
+    }
+    (factorial.bit_len() - 1) / 8
+}
+
+/// Palette entries with their alpha from tRNS, which identify color of every index
+struct Entries {
+    colors: Vec<[u8; 4]>,
+}
+
+impl Entries {
+    fn of(png: &Png) -> Result<(Palette, Option<Transparency>, Entries)> {
+        let header = png.header_info()?;
+        if header.color_type != ColorType::Indexed {
+            return Err(Error::UnsupportedCarrier(format!(
+                "palette order can only carry data in indexed color images, not in {} image",
+                header.color_type
+            )));
+        }
+        let palette = png
+            .palette()?
+            .ok_or_else(|| Error::InvalidPalette("indexed image has no PLTE".to_string()))?;
+        // pixels could not refer to entries moved past the largest index of bit depth
+        let max_entries = 1usize << header.bit_depth;
+        if palette.len() > max_entries {
+            return Err(Error::UnsupportedCarrier(format!(
+                "PLTE has {} entries, but pixels of bit depth {} can use only {} of them",
+                palette.len(),
+                header.bit_depth,
+                max_entries
+            )));
+        }
+        let transparency = png.transparency()?;
+
+        let colors: Vec<[u8; 4]> = palette
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, &[r, g, b])| {
+                let alpha = transparency.as_ref().map_or(255, |t| t.alpha(index));
+                [r, g, b, alpha]
+            })
+            .collect();
+        let mut sorted = colors.clone();
+        sorted.sort_unstable();
+        if sorted.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(Error::UnsupportedCarrier(
+                "palette contains repeated colors, so their order cannot be recovered".to_string(),
+            ));
+        }
+
+        Ok((palette, transparency, Entries { colors }))
+    }
+
+    fn sorted(&self) -> Vec<[u8; 4]> {
+        let mut sorted = self.colors.clone();
+        sorted.sort_unstable();
+        sorted
+    }
+
+    /// Indices of entries in order encoding number (as Lehmer code over sorted entries)
+    fn order_of(&self, mut number: BigUint) -> Vec<usize> {
+        let mut remaining = self.sorted();
+        let mut order = Vec::with_capacity(remaining.len());
+        while !remaining.is_empty() {
+            let digit = number.div_rem(remaining.len() as u32) as usize;
+            let color = remaining.remove(digit);
+            order.push(self.index_of(color));
+        }
+        order
+    }
+
+    /// Number encoded by current order of entries
+    fn number(&self) -> BigUint {
+        let mut remaining = self.sorted();
+        let mut digits = Vec::with_capacity(remaining.len());
+        for color in &self.colors {
+            let digit = position(&remaining, *color);
+            remaining.remove(digit);
+            digits.push(digit);
+        }
+
+        let mut number = BigUint::zero();
+        for (i, digit) in digits.iter().enumerate().rev() {
+            number.mul_add((self.colors.len() - i) as u32, *digit as u32);
+        }
+        number
+    }
+
+    fn index_of(&self, color: [u8; 4]) -> usize {
+        position(&self.colors, color)
+    }
+}
+
+fn position(colors: &[[u8; 4]], color: [u8; 4]) -> usize {
+    // entries are checked to be distinct, so every one of them is found
+    colors
+        .iter()
+        .position(|&c| c == color)
+        .expect("palette entry")
+}
+
+impl Png {
+    /// Number of bytes which can be hidden in order of palette entries, including 12 bytes
+    /// of chunk framing
+    pub fn palette_capacity(&self) -> Result<usize> {
+        let (palette, ..) = Entries::of(self)?;
+        Ok(capacity(palette.len()))
+    }
+
+    /// Hide chunk in order of palette entries of indexed image. Pixel indices and tRNS, hIST
+    /// and bKGD chunks are remapped, so the image looks exactly the same. Palette must not
+    /// contain repeated colors
+    pub fn embed_palette(&mut self, chunk: &Chunk, options: &ParseOptions) -> Result<()> {
+        let (palette, transparency, entries) = Entries::of(self)?;
+        let available = capacity(palette.len());
+        let mut data = chunk.as_bytes();
+        if data.len() > available {
+            return Err(Error::CapacityExceeded {
+                needed: data.len(),
+                available,
+            });
+        }
+        data.resize(available, 0);
+
+        let order = entries.order_of(BigUint::from_bytes(&data));
+        let mut new_index = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            new_index[old] = new;
+        }
+
+        let mut image = self.decode_image_with(options)?;
+        for i in 0..image.samples.len() {
+            let index = image.samples.get(i) as usize;
+            let new = new_index.get(index).ok_or_else(|| {
+                Error::InvalidImageData(format!(
+                    "pixel uses palette index {}, but PLTE has only {} entries",
+                    index,
+                    palette.len()
+                ))
+            })?;
+            image.samples.set(i, *new as u16);
+        }
+        // everything which can fail is done before png is changed
+        let idats = image.to_idat_chunks(&IdatOptions::default())?;
+
+        let permuted = Palette::new(order.iter().map(|&old| palette.entries[old]).collect())?;
+        self.replace_chunk_of_type(ChunkType::PLTE, Some(permuted.to_chunk()))?;
+        if let Some(transparency) = transparency {
+            let mut alphas: Vec<u8> = order.iter().map(|&old| transparency.alpha(old)).collect();
+            while alphas.last() == Some(&255) {
+                alphas.pop();
+            }
+            let chunk = (!alphas.is_empty()).then(|| Transparency::Indexed(alphas).to_chunk());
+            self.replace_chunk_of_type(ChunkType::TRNS, chunk)?;
+        }
+        if let Some(hist) = self.chunk_of_type(ChunkType::HIST) {
+            if hist.data().len() == 2 * order.len() {
+                let data = order
+                    .iter()
+                    .flat_map(|&old| [hist.data()[2 * old], hist.data()[2 * old + 1]])
+                    .collect();
+                self.replace_chunk_of_type(
+                    ChunkType::HIST,
+                    Some(Chunk::new(ChunkType::HIST, data)),
+                )?;
+            }
+        }
+        if let Some(bkgd) = self.chunk_of_type(ChunkType::BKGD) {
+            if let [index] = *bkgd.data() {
+                if let Some(&new) = new_index.get(index as usize) {
+                    let chunk = Chunk::new(ChunkType::BKGD, vec![new as u8]);
+                    self.replace_chunk_of_type(ChunkType::BKGD, Some(chunk))?;
+                }
+            }
+        }
+
+        self.replace_image_data(idats);
+        Ok(())
+    }
+
+    /// Read chunk hidden with [`Png::embed_palette`]. Returns `None` if palette order
+    /// does not encode valid chunk
+    pub fn extract_palette(&self, options: &ParseOptions) -> Result<Option<Chunk>> {
+        let (palette, _, entries) = Entries::of(self)?;
+        let available = capacity(palette.len());
+        let data = entries.number().to_bytes(available);
+
+        read_hidden_chunk(|len| data[..len].to_vec(), available, options)
+    }
+
+    fn chunk_of_type(&self, chunk_type: ChunkType) -> Option<Chunk> {
+        self.chunks()
+            .iter()
+            .find(|chunk| *chunk.chunk_type() == chunk_type)
+            .cloned()
+    }
+
+    /// Replace the first chunk of given type, or remove it if `chunk` is `None`
+    fn replace_chunk_of_type(&mut self, chunk_type: ChunkType, chunk: Option<Chunk>) -> Result<()> {
+        let index = self
+            .chunks()
+            .iter()
+            .position(|c| *c.chunk_type() == chunk_type)
+            .ok_or_else(|| Error::ChunkNotFound {
+                chunk_type: chunk_type.to_string(),
+            })?;
+        match chunk {
+            Some(chunk) => {
+                self.replace_chunk(index, chunk)?;
+            }
+            None => {
+                self.remove_chunks_where(|i, _| i == index);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::Ihdr;
+    use crate::image::Image;
+
+    /// Indexed image with `entries` distinct colors (half of them partially transparent),
+    /// whose pixels use all of them
+    fn testing_png(entries: usize) -> Png {
+        let header = Ihdr::new(16, 16, 8, ColorType::Indexed).unwrap();
+        let mut image = Image::new(header).unwrap();
+        for i in 0..image.samples.len() {
+            image.samples.set(i, (i * 7 % entries) as u16);
+        }
+        let palette: Vec<u8> = (0..entries)
+            .flat_map(|i| [i as u8, (i * 3) as u8, 255 - i as u8])
+            .collect();
+        let alphas: Vec<u8> = (0..entries / 2).map(|i| i as u8).collect();
+
+        let mut chunks = vec![
+            header.to_chunk(),
+            Chunk::new(ChunkType::PLTE, palette),
+            Chunk::new(ChunkType::TRNS, alphas),
+            Chunk::new(
+                ChunkType::HIST,
+                (0..entries as u16).flat_map(u16::to_be_bytes).collect(),
+            ),
+            Chunk::new(ChunkType::BKGD, vec![3]),
+        ];
+        chunks.extend(image.to_idat_chunks(&IdatOptions::default()).unwrap());
+        chunks.push(Chunk::new(ChunkType::IEND, vec![]));
+        Png::from_chunks(chunks)
+    }
+
+    /// Colors of all pixels with their alpha
+    fn rendered(png: &Png) -> Vec<[u8; 4]> {
+        let (.., entries) = Entries::of(png).unwrap();
+        let image = png.decode_image().unwrap();
+        (0..image.samples.len())
+            .map(|i| entries.colors[image.samples.get(i) as usize])
+            .collect()
+    }
+
+    #[test]
+    fn test_big_uint() {
+        let mut number = BigUint::from_bytes(&[1, 0, 0, 0, 0, 0, 0, 0, 7]);
+        assert_eq!(number.bit_len(), 65);
+        assert_eq!(number.to_bytes(9), [1, 0, 0, 0, 0, 0, 0, 0, 7]);
+        assert_eq!(number.to_bytes(2), [0, 7]);
+
+        assert_eq!(number.div_rem(256), 7);
+        assert_eq!(number.to_bytes(8), [1, 0, 0, 0, 0, 0, 0, 0]);
+        number.mul_add(3, 2);
+        assert_eq!(number.to_bytes(9), [0, 3, 0, 0, 0, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn test_capacity() {
+        assert_eq!(capacity(1), 0);
+        // 8! = 40320, which takes 16 bits
+        assert_eq!(capacity(8), 1);
+        // log2(256!) is about 1683.996
+        assert_eq!(capacity(256), 210);
+    }
+
+    #[test]
+    fn test_order_roundtrip() {
+        let (.., entries) = Entries::of(&testing_png(20)).unwrap();
+        let number = BigUint::from_bytes(&[12, 34, 56, 78, 90]);
+        let order = entries.order_of(number.clone());
+
+        let permuted = Entries {
+            colors: order.iter().map(|&old| entries.colors[old]).collect(),
+        };
+        assert_eq!(permuted.number(), number);
+    }
+
+    #[test]
+    fn test_embed_and_extract() {
+        let mut png = testing_png(64);
+        let before = rendered(&png);
+        let chunk = Chunk::from_strings("ruSt", "in palette").unwrap();
+        assert_eq!(png.palette_capacity().unwrap(), capacity(64));
+
+        png.embed_palette(&chunk, &ParseOptions::default()).unwrap();
+
+        assert_eq!(rendered(&png), before);
+        let extracted = png.extract_palette(&ParseOptions::default()).unwrap();
+        assert_eq!(extracted.unwrap().as_bytes(), chunk.as_bytes());
+
+        // chunks depending on palette order are remapped
+        let (palette, ..) = Entries::of(&png).unwrap();
+        let background = png.chunk_of_type(ChunkType::BKGD).unwrap().data()[0] as usize;
+        assert_eq!(palette.entries[background], [3, 9, 252]);
+        let hist = png.chunk_of_type(ChunkType::HIST).unwrap();
+        for (i, entry) in palette.entries.iter().enumerate() {
+            let frequency = u16::from_be_bytes([hist.data()[2 * i], hist.data()[2 * i + 1]]);
+            assert_eq!(frequency, entry[0] as u16);
+        }
+    }
+
+    #[test]
+    fn test_extract_without_message() {
+        let png = testing_png(64);
+        assert!(png
+            .extract_palette(&ParseOptions::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_unsupported_palettes() {
+        let chunk = Chunk::from_strings("ruSt", "in palette").unwrap();
+        assert!(matches!(
+            testing_png(8).embed_palette(&chunk, &ParseOptions::default()),
+            Err(Error::CapacityExceeded { .. })
+        ));
+
+        let mut repeated = testing_png(64);
+        let mut palette = repeated.palette().unwrap().unwrap();
+        palette.entries[63] = palette.entries[62];
+        repeated.replace_chunk(1, palette.to_chunk()).unwrap();
+        assert!(matches!(
+            repeated.palette_capacity(),
+            Err(Error::UnsupportedCarrier(_))
+        ));
+    }
+
+    #[test]
+    fn test_palette_larger_than_bit_depth() {
+        // 2 bit indices, but 64 entries which order could move out of their reach
+        let header = Ihdr::new(16, 16, 2, ColorType::Indexed).unwrap();
+        let mut png = testing_png(64);
+        png.replace_chunk(0, header.to_chunk()).unwrap();
+        png.set_image(&Image::new(header).unwrap(), &IdatOptions::default())
+            .unwrap();
+        let before = png.as_bytes();
+
+        assert!(matches!(
+            png.palette_capacity(),
+            Err(Error::UnsupportedCarrier(_))
+        ));
+        let chunk = Chunk::from_strings("ruSt", "in palette").unwrap();
+        assert!(matches!(
+            png.embed_palette(&chunk, &ParseOptions::default()),
+            Err(Error::UnsupportedCarrier(_))
+        ));
+        assert_eq!(png.as_bytes(), before);
+    }
+}
@@ -1,9 +1,53 @@
 use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
 
 use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::Ihdr;
+use crate::reader::{ChunkReader, ParseOptions};
 use crate::{Error, Result};
 
-#[derive(Debug)]
+/// Where a new chunk is inserted by [`Png::insert_chunk`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkPosition {
+    /// Right before IEND (or at the end if png has no IEND)
+    #[default]
+    BeforeIend,
+    /// Right before first IDAT
+    BeforeIdat,
+    /// Right after last IDAT of the first IDAT run
+    AfterIdat,
+}
+
+/// Ordering constraint of chunk type relative to PLTE and IDAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Ordering {
+    /// IHDR, PLTE, IDAT and IEND, their position is fixed by the image itself
+    Critical,
+    BeforePlte,
+    AfterPlteBeforeIdat,
+    BeforeIdat,
+    Anywhere,
+}
+
+impl Ordering {
+    pub(crate) fn of(chunk_type: &ChunkType) -> Ordering {
+        match &chunk_type.bytes() {
+            b"IHDR" | b"PLTE" | b"IDAT" | b"IEND" => Ordering::Critical,
+            b"cHRM" | b"cICP" | b"gAMA" | b"iCCP" | b"mDCV" | b"cLLI" | b"sBIT" | b"sRGB" => {
+                Ordering::BeforePlte
+            }
+            b"bKGD" | b"hIST" | b"tRNS" => Ordering::AfterPlteBeforeIdat,
+            b"pHYs" | b"sPLT" | b"eXIf" => Ordering::BeforeIdat,
+            _ => Ordering::Anywhere,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Png {
     chunks: Vec<Chunk>,
 }
@@ -23,20 +67,149 @@ impl Png {
         self.chunks.push(chunk);
     }
 
+    /// Insert chunk at given position, checking ordering rules of known chunk types.
+    /// Returns index at which chunk was inserted
+    pub fn insert_chunk(&mut self, chunk: Chunk, position: ChunkPosition) -> Result<usize> {
+        let index = match position {
+            ChunkPosition::BeforeIend => self
+                .position_of(&ChunkType::IEND)
+                .unwrap_or(self.chunks.len()),
+            ChunkPosition::BeforeIdat => self.first_idat()?,
+            ChunkPosition::AfterIdat => {
+                let first = self.first_idat()?;
+                first
+                    + self.chunks[first..]
+                        .iter()
+                        .take_while(|chunk| *chunk.chunk_type() == ChunkType::IDAT)
+                        .count()
+            }
+        };
+
+        self.check_position(chunk.chunk_type(), index)?;
+        self.chunks.insert(index, chunk);
+
+        Ok(index)
+    }
+
+    fn first_idat(&self) -> Result<usize> {
+        self.position_of(&ChunkType::IDAT)
+            .ok_or_else(|| Error::ChunkNotFound {
+                chunk_type: ChunkType::IDAT.to_string(),
+            })
+    }
+
+    fn position_of(&self, chunk_type: &ChunkType) -> Option<usize> {
+        self.chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type() == chunk_type)
+    }
+
+    /// Check whether chunk with given type can be inserted at given index
+    pub fn check_position(&self, chunk_type: &ChunkType, index: usize) -> Result<()> {
+        let invalid = |reason| {
+            Err(Error::InvalidChunkPosition {
+                chunk_type: chunk_type.to_string(),
+                reason,
+            })
+        };
+        let plte = self.position_of(&ChunkType::PLTE);
+        let idat = self.position_of(&ChunkType::IDAT);
+
+        if self.position_of(&ChunkType::IHDR) == Some(0) && index == 0 {
+            return invalid("must come after IHDR");
+        }
+        if matches!(self.position_of(&ChunkType::IEND), Some(iend) if index > iend) {
+            return invalid("must come before IEND");
+        }
+        let before_idat = idat.is_none_or(|idat| index <= idat);
+
+        match Ordering::of(chunk_type) {
+            Ordering::Critical => invalid("is a critical chunk with fixed position"),
+            Ordering::BeforePlte if !before_idat || plte.is_some_and(|plte| index > plte) => {
+                invalid("must come before PLTE and IDAT")
+            }
+            Ordering::AfterPlteBeforeIdat if plte.is_some_and(|plte| index <= plte) => {
+                invalid("must come after PLTE")
+            }
+            Ordering::AfterPlteBeforeIdat | Ordering::BeforeIdat if !before_idat => {
+                invalid("must come before IDAT")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Replace chunk at given index, returning the old one.
+    /// Chunk of different type must be allowed at that index by ordering rules
+    pub fn replace_chunk(&mut self, index: usize, chunk: Chunk) -> Result<Chunk> {
+        let old_type = match self.chunks.get(index) {
+            Some(old) => *old.chunk_type(),
+            None => {
+                return Err(Error::InvalidChunkPosition {
+                    chunk_type: chunk.chunk_type().to_string(),
+                    reason: "can only replace existing chunk",
+                })
+            }
+        };
+        if old_type != *chunk.chunk_type() {
+            if old_type.is_critical() {
+                return Err(Error::InvalidChunkPosition {
+                    chunk_type: chunk.chunk_type().to_string(),
+                    reason: "would replace critical chunk of different type",
+                });
+            }
+            let old = self.chunks.remove(index);
+            let allowed = self.check_position(chunk.chunk_type(), index);
+            self.chunks.insert(index, old);
+            allowed?;
+        }
+
+        Ok(std::mem::replace(&mut self.chunks[index], chunk))
+    }
+
+    /// Replace all IDAT chunks with given ones, placed where the first IDAT was
+    /// (or before IEND when png has no image data)
+    pub(crate) fn replace_image_data(&mut self, idats: Vec<Chunk>) {
+        let index = self
+            .position_of(&ChunkType::IDAT)
+            .or_else(|| self.position_of(&ChunkType::IEND))
+            .unwrap_or(self.chunks.len());
+        self.chunks
+            .retain(|chunk| *chunk.chunk_type() != ChunkType::IDAT);
+        self.chunks.splice(index..index, idats);
+    }
+
     pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
         Ok(self.chunks.remove(
             self.chunks
                 .iter()
                 .position(|chunk| chunk.chunk_type().to_string() == chunk_type)
-                .ok_or_else(|| {
-                    Error::from(format!(
-                        "Cannot remove and find chunk with type {}",
-                        chunk_type
-                    ))
+                .ok_or_else(|| Error::ChunkNotFound {
+                    chunk_type: chunk_type.to_string(),
                 })?,
         ))
     }
 
+    /// Remove every chunk for which predicate returns true (predicate gets chunk index and chunk).
+    /// Returns removed chunks together with indices they had before removal
+    pub fn remove_chunks_where<F>(&mut self, mut predicate: F) -> Vec<(usize, Chunk)>
+    where
+        F: FnMut(usize, &Chunk) -> bool,
+    {
+        let mut removed = Vec::new();
+        let mut kept = Vec::with_capacity(self.chunks.len());
+
+        for (index, chunk) in self.chunks.drain(..).enumerate() {
+            if predicate(index, &chunk) {
+                removed.push((index, chunk));
+            } else {
+                kept.push(chunk);
+            }
+        }
+        self.chunks = kept;
+
+        removed
+    }
+
     pub fn header() -> &'static [u8; 8] {
         &Self::STANDARD_HEADER
     }
@@ -45,7 +218,27 @@ impl Png {
         &self.chunks
     }
 
-    /// Get first matched chunk by type, searched in reverse because encoded messages are appended to the end of png
+    /// Parse image header, checking that IHDR is the first chunk and it is not repeated
+    pub fn header_info(&self) -> Result<Ihdr> {
+        let ihdr = self
+            .chunks
+            .first()
+            .filter(|chunk| *chunk.chunk_type() == ChunkType::IHDR)
+            .ok_or_else(|| Error::InvalidHeader("IHDR must be the first chunk".to_string()))?;
+
+        if self.chunks[1..]
+            .iter()
+            .any(|chunk| *chunk.chunk_type() == ChunkType::IHDR)
+        {
+            return Err(Error::InvalidHeader(
+                "png must contain only one IHDR".to_string(),
+            ));
+        }
+
+        Ihdr::try_from(ihdr)
+    }
+
+    /// Get first matched chunk by type, searched in reverse because encoded messages are inserted near the end of png
     pub fn get_chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
         self.chunks
             .iter()
@@ -54,15 +247,82 @@ impl Png {
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
-        Self::STANDARD_HEADER
-            .into_iter()
-            .chain(
-                self.chunks()
-                    .iter()
-                    .flat_map(|chunk| chunk.as_bytes())
-                    .into_iter(),
-            )
-            .collect()
+        let mut bytes = Vec::with_capacity(self.bytes_len());
+        self.write_to(&mut bytes)
+            .expect("writing to vector cannot fail");
+        bytes
+    }
+
+    /// Number of bytes of serialized png (signature and all chunks)
+    pub fn bytes_len(&self) -> usize {
+        Self::STANDARD_HEADER.len() + self.chunks.iter().map(Chunk::bytes_len).sum::<usize>()
+    }
+
+    /// Serialize png chunk by chunk directly into writer
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&Self::STANDARD_HEADER)?;
+        for chunk in &self.chunks {
+            chunk.write_to(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Parse png from bytes with given resource limits
+    pub fn parse(bytes: &[u8], options: ParseOptions) -> Result<Png> {
+        Self::read_chunks(
+            ChunkReader::with_options(bytes, options)?.with_input_len(bytes.len() as u64)?,
+        )
+    }
+
+    /// Read and parse whole png from reader
+    pub fn from_reader<R: Read>(reader: R) -> Result<Png> {
+        Self::from_reader_with(reader, ParseOptions::default())
+    }
+
+    pub fn from_reader_with<R: Read>(reader: R, options: ParseOptions) -> Result<Png> {
+        Self::read_chunks(ChunkReader::with_options(reader, options)?)
+    }
+
+    fn read_chunks<R: Read>(chunks: ChunkReader<R>) -> Result<Png> {
+        Ok(Png::from_chunks(chunks.collect::<Result<Vec<Chunk>>>()?))
+    }
+
+    /// Read and parse png file from given path
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Png> {
+        Self::from_file_with(path, ParseOptions::default())
+    }
+
+    pub fn from_file_with<P: AsRef<Path>>(path: P, options: ParseOptions) -> Result<Png> {
+        Self::read_chunks(Self::open_file_with(path, options)?)
+    }
+
+    /// Open png file for lazy reading of its chunks
+    pub fn open_file<P: AsRef<Path>>(path: P) -> Result<ChunkReader<BufReader<File>>> {
+        Self::open_file_with(path, ParseOptions::default())
+    }
+
+    pub fn open_file_with<P: AsRef<Path>>(
+        path: P,
+        options: ParseOptions,
+    ) -> Result<ChunkReader<BufReader<File>>> {
+        let path = path.as_ref();
+        let open = || -> Result<_> {
+            let file = File::open(path)?;
+            let len = file.metadata()?.len();
+            ChunkReader::with_options(BufReader::new(file), options)?.with_input_len(len)
+        };
+        open().map_err(|err| err.with_path(path))
+    }
+
+    /// Write png to given path, replacing file if it already exists
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let write = || {
+            let mut writer = BufWriter::new(File::create(path)?);
+            self.write_to(&mut writer)?;
+            writer.flush()
+        };
+        write().map_err(|err| Error::from(err).with_path(path))
     }
 }
 
@@ -76,32 +336,7 @@ impl TryFrom<&[u8]> for Png {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> std::result::Result<Self, Self::Error> {
-        let bytes_len = bytes.len();
-        if bytes_len < 8 {
-            return Err(Error::from(format!(
-                "Valid png must have at least 8 bytes (missing header) but only {} were provided",
-                bytes_len
-            )));
-        }
-
-        let header = &bytes[0..8];
-
-        if header != Png::STANDARD_HEADER {
-            return Err(Error::from(format!(
-                        "Valid png must contain valid signature header ({:?}), but {:?} header war provided",
-                        Png::STANDARD_HEADER, header
-                    )));
-        }
-        let mut index = 8;
-        let mut chunks = Vec::new();
-
-        while index < bytes_len {
-            let chunk = Chunk::try_from(&bytes[index..])?;
-            index += chunk.bytes_len();
-            chunks.push(chunk)
-        }
-
-        Ok(Png::from_chunks(chunks))
+        Png::parse(bytes, ParseOptions::default())
     }
 }
 
@@ -179,7 +414,7 @@ mod tests {
 
         let png = Png::try_from(bytes.as_ref());
 
-        assert!(png.is_err());
+        assert!(matches!(png, Err(Error::InvalidSignature { .. })));
     }
 
     #[test]
@@ -204,6 +439,28 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_crc_mismatch_location() {
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        let png = Png::try_from(bytes.as_ref());
+
+        match png {
+            Err(Error::CrcMismatch {
+                chunk_type,
+                location: Some(location),
+                ..
+            }) => {
+                assert_eq!(chunk_type, "LASt");
+                assert_eq!(location.index, 2);
+                assert_eq!(location.offset, 8 + 32 + 30);
+            }
+            other => panic!("expected crc mismatch, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -228,6 +485,91 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    fn testing_image_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::from_strings("IHDR", "header").unwrap(),
+            Chunk::from_strings("gAMA", "gamma").unwrap(),
+            Chunk::from_strings("IDAT", "first").unwrap(),
+            Chunk::from_strings("IDAT", "second").unwrap(),
+            Chunk::from_strings("IEND", "").unwrap(),
+        ])
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_chunk_before_iend() {
+        let mut png = testing_image_png();
+        let index = png
+            .insert_chunk(
+                Chunk::from_strings("ruSt", "Message").unwrap(),
+                ChunkPosition::BeforeIend,
+            )
+            .unwrap();
+        assert_eq!(index, 4);
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "gAMA", "IDAT", "IDAT", "ruSt", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_insert_chunk_around_idat() {
+        let mut png = testing_image_png();
+        png.insert_chunk(
+            Chunk::from_strings("ruSt", "before").unwrap(),
+            ChunkPosition::BeforeIdat,
+        )
+        .unwrap();
+        png.insert_chunk(
+            Chunk::from_strings("ruSt", "after").unwrap(),
+            ChunkPosition::AfterIdat,
+        )
+        .unwrap();
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "gAMA", "ruSt", "IDAT", "IDAT", "ruSt", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_insert_chunk_ordering_rules() {
+        let mut png = testing_image_png();
+        let result = png.insert_chunk(
+            Chunk::from_strings("pHYs", "physical").unwrap(),
+            ChunkPosition::AfterIdat,
+        );
+        assert!(matches!(result, Err(Error::InvalidChunkPosition { .. })));
+
+        let result = png.insert_chunk(
+            Chunk::from_strings("IEND", "").unwrap(),
+            ChunkPosition::BeforeIend,
+        );
+        assert!(matches!(result, Err(Error::InvalidChunkPosition { .. })));
+
+        png.insert_chunk(
+            Chunk::from_strings("pHYs", "physical").unwrap(),
+            ChunkPosition::BeforeIdat,
+        )
+        .unwrap();
+        assert_eq!(png.chunks().len(), 6);
+    }
+
+    #[test]
+    fn test_insert_chunk_without_idat() {
+        let mut png = testing_png();
+        let result = png.insert_chunk(
+            Chunk::from_strings("ruSt", "Message").unwrap(),
+            ChunkPosition::BeforeIdat,
+        );
+        assert!(matches!(result, Err(Error::ChunkNotFound { .. })));
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -237,6 +579,36 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_image_png();
+        let old = png
+            .replace_chunk(1, Chunk::from_strings("tEXt", "new").unwrap())
+            .unwrap();
+
+        assert_eq!(old.chunk_type().to_string(), "gAMA");
+        assert_eq!(png.chunks()[1].data_as_string().unwrap(), "new");
+        assert!(png
+            .replace_chunk(0, Chunk::from_strings("tEXt", "new").unwrap())
+            .is_err());
+        assert!(png
+            .replace_chunk(100, Chunk::from_strings("tEXt", "new").unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_remove_chunks_where() {
+        let mut png = testing_png();
+        png.append_chunk(Chunk::from_strings("TeSt", "Message").unwrap());
+        png.append_chunk(Chunk::from_strings("TeSt", "Another").unwrap());
+
+        let removed = png.remove_chunks_where(|_, chunk| chunk.chunk_type().to_string() == "TeSt");
+
+        let indices: Vec<usize> = removed.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indices, [3, 4]);
+        assert_eq!(png.chunks().len(), 3);
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -251,6 +623,31 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_write_to() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut bytes = Vec::new();
+        png.write_to(&mut bytes).unwrap();
+        assert_eq!(png.bytes_len(), PNG_FILE.len());
+        assert_eq!(bytes, PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_header_info() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let ihdr = png.header_info().unwrap();
+        assert_eq!(ihdr.width, 50);
+        assert_eq!(ihdr.height, 50);
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, crate::ColorType::Rgba);
+
+        assert!(testing_png().header_info().is_err());
+
+        let mut png = png;
+        png.append_chunk(ihdr.to_chunk());
+        assert!(png.header_info().is_err());
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -0,0 +1,314 @@
+use std::io::Read;
+
+use crate::chunk::{read_full, Chunk};
+use crate::error::{Limit, Location};
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// Resource limits applied while parsing untrusted input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Maximum data length of single chunk (it can't be above [`Chunk::MAX_LENGTH`])
+    pub max_chunk_length: u32,
+    /// Maximum number of chunks in png
+    pub max_chunks: usize,
+    /// Maximum size of whole png file in bytes
+    pub max_file_size: u64,
+    /// Maximum size of data inflated from zlib streams stored in chunks
+    pub max_decompressed_size: u64,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            max_chunk_length: Chunk::MAX_LENGTH,
+            max_chunks: usize::MAX,
+            max_file_size: u64::MAX,
+            max_decompressed_size: 1 << 30,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub(crate) fn check(&self, limit: Limit, value: u64) -> Result<()> {
+        let max = match limit {
+            Limit::ChunkLength => self.max_chunk_length.min(Chunk::MAX_LENGTH) as u64,
+            Limit::ChunkCount => self.max_chunks as u64,
+            Limit::FileSize => self.max_file_size,
+            Limit::DecompressedSize => self.max_decompressed_size,
+        };
+        if value > max {
+            return Err(Error::LimitExceeded {
+                limit,
+                value,
+                max,
+                location: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Lazy iterator over chunks of png read from any [`Read`].
+///
+/// Signature is validated when reader is created, chunks are read one by one
+/// (with crc checked while their data is read), so iteration can be stopped at any time
+/// without reading rest of the input. After first error iterator yields nothing more.
+#[derive(Debug)]
+pub struct ChunkReader<R: Read> {
+    reader: R,
+    options: ParseOptions,
+    input_len: Option<u64>,
+    index: usize,
+    offset: usize,
+    finished: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    pub fn new(reader: R) -> Result<ChunkReader<R>> {
+        Self::with_options(reader, ParseOptions::default())
+    }
+
+    pub fn with_options(mut reader: R, options: ParseOptions) -> Result<ChunkReader<R>> {
+        let mut header = [0; 8];
+        let len = read_full(&mut reader, &mut header)?;
+
+        if len < 8 {
+            return Err(Error::MissingSignature { len });
+        }
+        if header != Png::STANDARD_HEADER {
+            return Err(Error::InvalidSignature { found: header });
+        }
+
+        Ok(ChunkReader {
+            reader,
+            options,
+            input_len: None,
+            index: 0,
+            offset: header.len(),
+            finished: false,
+        })
+    }
+
+    /// Set total length of input (including signature), so chunks whose length
+    /// points past the end of input are rejected before any of their data is read
+    pub fn with_input_len(mut self, input_len: u64) -> Result<Self> {
+        self.options.check(Limit::FileSize, input_len)?;
+        self.input_len = Some(input_len);
+        Ok(self)
+    }
+
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// Location of the next chunk to be read
+    pub fn location(&self) -> Location {
+        Location {
+            index: self.index,
+            offset: self.offset,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_chunk(&mut self) -> Result<Option<Chunk>> {
+        let location = self.location();
+        self.read_checked_chunk().map_err(|err| err.at(location))
+    }
+
+    fn read_checked_chunk(&mut self) -> Result<Option<Chunk>> {
+        let (len, chunk_type) = match Chunk::read_header(&mut self.reader)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        self.options
+            .check(Limit::ChunkCount, self.index as u64 + 1)?;
+        self.options.check(Limit::ChunkLength, len as u64)?;
+
+        let chunk_end = self.offset as u64 + 12 + len as u64;
+        self.options.check(Limit::FileSize, chunk_end)?;
+        if let Some(input_len) = self.input_len {
+            if chunk_end > input_len {
+                return Err(Chunk::truncated(
+                    12 + len as usize,
+                    (input_len - self.offset as u64) as usize,
+                ));
+            }
+        }
+
+        let (chunk, expected_crc) = Chunk::read_body(&mut self.reader, len, chunk_type)?;
+        chunk.verify_crc(expected_crc)?;
+
+        self.index += 1;
+        self.offset += chunk.bytes_len();
+
+        Ok(Some(chunk))
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match self.read_chunk() {
+            Ok(Some(chunk)) => Some(Ok(chunk)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_bytes() -> Vec<u8> {
+        Png::from_chunks(vec![
+            Chunk::from_strings("FrSt", "I am the first chunk").unwrap(),
+            Chunk::from_strings("miDl", "I am another chunk").unwrap(),
+            Chunk::from_strings("LASt", "I am the last chunk").unwrap(),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_reads_all_chunks() {
+        let bytes = testing_bytes();
+        let chunks: Vec<Chunk> = ChunkReader::new(&bytes[..])
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].data_as_string().unwrap(), "I am the last chunk");
+    }
+
+    #[test]
+    fn test_stops_early() {
+        let bytes = testing_bytes();
+        let mut reader = ChunkReader::new(&bytes[..]).unwrap();
+
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(first.chunk_type().to_string(), "FrSt");
+        assert_eq!(
+            reader.location(),
+            Location {
+                index: 1,
+                offset: 8 + 32
+            }
+        );
+        assert_eq!(reader.into_inner().len(), 30 + 31);
+    }
+
+    #[test]
+    fn test_hostile_chunk_length() {
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        bytes.extend_from_slice(&0x7fff_fff0_u32.to_be_bytes());
+        bytes.extend_from_slice(b"RuSt");
+        bytes.extend_from_slice(&[0; 14]);
+
+        let mut reader = ChunkReader::new(&bytes[..])
+            .unwrap()
+            .with_input_len(bytes.len() as u64)
+            .unwrap();
+        assert!(matches!(
+            reader.next(),
+            Some(Err(Error::TruncatedChunk { available: 22, .. }))
+        ));
+
+        let options = ParseOptions {
+            max_chunk_length: 1024,
+            ..ParseOptions::default()
+        };
+        let mut reader = ChunkReader::with_options(&bytes[..], options).unwrap();
+        assert!(matches!(
+            reader.next(),
+            Some(Err(Error::LimitExceeded {
+                limit: Limit::ChunkLength,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_chunk_count_and_file_size_limits() {
+        let bytes = testing_bytes();
+
+        let options = ParseOptions {
+            max_chunks: 2,
+            ..ParseOptions::default()
+        };
+        let results: Vec<Result<Chunk>> = ChunkReader::with_options(&bytes[..], options)
+            .unwrap()
+            .collect();
+        assert_eq!(results.len(), 3);
+        assert!(matches!(
+            results[2],
+            Err(Error::LimitExceeded {
+                limit: Limit::ChunkCount,
+                ..
+            })
+        ));
+
+        let options = ParseOptions {
+            max_file_size: 50,
+            ..ParseOptions::default()
+        };
+        let result = ChunkReader::with_options(&bytes[..], options)
+            .unwrap()
+            .with_input_len(bytes.len() as u64);
+        assert!(matches!(
+            result,
+            Err(Error::LimitExceeded {
+                limit: Limit::FileSize,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        let mut bytes = testing_bytes();
+        bytes[0] = 13;
+
+        assert!(matches!(
+            ChunkReader::new(&bytes[..]),
+            Err(Error::InvalidSignature { .. })
+        ));
+        assert!(matches!(
+            ChunkReader::new(&bytes[..3]),
+            Err(Error::MissingSignature { len: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_last_chunk() {
+        let bytes = testing_bytes();
+        let mut reader = ChunkReader::new(&bytes[..bytes.len() - 2]).unwrap();
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_ok());
+        match reader.next() {
+            Some(Err(Error::TruncatedChunk {
+                location: Some(location),
+                ..
+            })) => assert_eq!(location.index, 2),
+            other => panic!("expected truncated chunk, got {:?}", other),
+        }
+        assert!(reader.next().is_none());
+    }
+}
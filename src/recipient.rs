@@ -0,0 +1,430 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::envelope::{authenticated, sealed_chunk, ContentType, Envelope};
+use crate::{Error, Result};
+
+const MAGIC: &[u8; 4] = b"PNGK";
+const VERSION: u8 = 1;
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+/// Content key encrypted for one recipient
+const WRAPPED_LEN: usize = KEY_LEN + TAG_LEN;
+/// Magic, version, ephemeral public key and number of recipients
+const PREFIX_LEN: usize = 4 + 1 + KEY_LEN + 2;
+
+const RECIPIENT_PREFIX: &str = "pngme-x25519-public ";
+const IDENTITY_PREFIX: &str = "pngme-x25519-secret ";
+
+/// X25519 public key which messages can be encrypted for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecipientKey(PublicKey);
+
+impl RecipientKey {
+    /// Read key from file written by keygen
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<RecipientKey> {
+        read_key_file(path.as_ref())
+    }
+
+    pub fn bytes(&self) -> [u8; KEY_LEN] {
+        self.0.to_bytes()
+    }
+
+    /// Short hex digest identifying the key
+    pub fn fingerprint(&self) -> String {
+        fingerprint(self.0.as_bytes())
+    }
+}
+
+impl FromStr for RecipientKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(RecipientKey(PublicKey::from(parse_key(
+            s,
+            RECIPIENT_PREFIX,
+        )?)))
+    }
+}
+
+impl fmt::Display for RecipientKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", RECIPIENT_PREFIX, hex::encode(self.0.as_bytes()))
+    }
+}
+
+/// X25519 secret key which decrypts messages encrypted for its [`RecipientKey`]
+#[derive(Clone)]
+pub struct Identity(StaticSecret);
+
+impl Identity {
+    pub fn generate() -> Identity {
+        Identity(StaticSecret::random_from_rng(OsRng))
+    }
+
+    /// Read key from file written by keygen
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Identity> {
+        read_key_file(path.as_ref())
+    }
+
+    pub fn recipient(&self) -> RecipientKey {
+        RecipientKey(PublicKey::from(&self.0))
+    }
+}
+
+impl FromStr for Identity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Identity(StaticSecret::from(parse_key(s, IDENTITY_PREFIX)?)))
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", IDENTITY_PREFIX, hex::encode(self.0.as_bytes()))
+    }
+}
+
+/// Secret is left out, so it does not end up in logs
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Identity").field(&self.recipient()).finish()
+    }
+}
+
+/// Read key from text file, naming the file in errors
+pub(crate) fn read_key_file<K: FromStr<Err = Error>>(path: &Path) -> Result<K> {
+    let text = fs::read_to_string(path).map_err(|err| Error::from(err).with_path(path))?;
+    text.parse().map_err(|err| match err {
+        Error::InvalidKey(reason) => {
+            Error::InvalidKey(format!("{} in file {}", reason, path.display()))
+        }
+        err => err,
+    })
+}
+
+/// Key written as prefix naming its kind followed by hex of its bytes
+pub(crate) fn parse_key(s: &str, prefix: &str) -> Result<[u8; KEY_LEN]> {
+    let encoded = s.trim().strip_prefix(prefix).ok_or_else(|| {
+        Error::InvalidKey(format!("expected key starting with '{}'", prefix.trim()))
+    })?;
+    let mut key = [0; KEY_LEN];
+    hex::decode_to_slice(encoded, &mut key)
+        .map_err(|err| Error::InvalidKey(format!("key is not {} hex bytes: {}", KEY_LEN, err)))?;
+    Ok(key)
+}
+
+pub(crate) fn fingerprint(key: &[u8]) -> String {
+    hex::encode(&Sha256::digest(key)[..8])
+}
+
+/// Key wrapping content key for one recipient, bound to both public keys
+fn wrapping_key(shared: &[u8], ephemeral: &PublicKey, recipient: &PublicKey) -> [u8; KEY_LEN] {
+    Sha256::new()
+        .chain_update(b"pngme recipient\0")
+        .chain_update(shared)
+        .chain_update(ephemeral.as_bytes())
+        .chain_update(recipient.as_bytes())
+        .finalize()
+        .into()
+}
+
+/// Every wrapping key is used only once, so constant nonce is safe
+fn wrapping_cipher(key: &[u8; KEY_LEN]) -> (XChaCha20Poly1305, XNonce) {
+    (XChaCha20Poly1305::new(key.into()), XNonce::default())
+}
+
+/// Whether data starts like message encrypted with [`Chunk::encrypted_for`]
+pub(crate) fn is_encrypted_for_recipients(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encrypt data with random content key, which is wrapped for every recipient with key
+/// agreed between them and one ephemeral key. Chunk type, envelope (see
+/// [`Envelope::authenticated`]) and whole header are authenticated
+pub(crate) fn encrypt(
+    data: &[u8],
+    recipients: &[RecipientKey],
+    chunk_type: &ChunkType,
+    envelope: &[u8],
+) -> Result<Vec<u8>> {
+    if recipients.is_empty() {
+        return Err(Error::InvalidKey(
+            "message needs at least one recipient".to_string(),
+        ));
+    }
+    let count = u16::try_from(recipients.len())
+        .map_err(|_| Error::InvalidKey(format!("too many recipients: {}", recipients.len())))?;
+
+    let ephemeral = StaticSecret::random_from_rng(OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral);
+    let mut content_key = [0; KEY_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    OsRng.fill_bytes(&mut content_key);
+    OsRng.fill_bytes(&mut nonce);
+
+    let mut sealed =
+        Vec::with_capacity(PREFIX_LEN + recipients.len() * WRAPPED_LEN + NONCE_LEN + data.len());
+    sealed.extend_from_slice(MAGIC);
+    sealed.push(VERSION);
+    sealed.extend_from_slice(ephemeral_public.as_bytes());
+    sealed.extend_from_slice(&count.to_be_bytes());
+
+    for recipient in recipients {
+        let shared = ephemeral.diffie_hellman(&recipient.0);
+        if !shared.was_contributory() {
+            return Err(Error::InvalidKey(format!(
+                "recipient {} is not a valid public key",
+                recipient.fingerprint()
+            )));
+        }
+        let (cipher, wrapping_nonce) = wrapping_cipher(&wrapping_key(
+            shared.as_bytes(),
+            &ephemeral_public,
+            &recipient.0,
+        ));
+        let wrapped = cipher
+            .encrypt(&wrapping_nonce, &content_key[..])
+            .expect("wrapping of key fits in memory");
+        sealed.extend_from_slice(&wrapped);
+    }
+    sealed.extend_from_slice(&nonce);
+
+    let aad = [&chunk_type.bytes()[..], envelope, &sealed].concat();
+    let ciphertext = XChaCha20Poly1305::new(&content_key.into())
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: data,
+                aad: &aad,
+            },
+        )
+        .expect("encryption of message fits in memory");
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypt data produced by [`encrypt`] for the same chunk type and envelope with secret key
+/// of any of its recipients
+pub(crate) fn decrypt(
+    sealed: &[u8],
+    identity: &Identity,
+    chunk_type: &ChunkType,
+    envelope: &[u8],
+) -> Result<Vec<u8>> {
+    if !is_encrypted_for_recipients(sealed) {
+        return Err(Error::CorruptedCiphertext(
+            "message is not encrypted for recipients".to_string(),
+        ));
+    }
+    let too_short =
+        || Error::CorruptedCiphertext(format!("encrypted message has only {} bytes", sealed.len()));
+    if sealed.len() < PREFIX_LEN {
+        return Err(too_short());
+    }
+    if sealed[4] != VERSION {
+        return Err(Error::CorruptedCiphertext(format!(
+            "unknown encryption version {}",
+            sealed[4]
+        )));
+    }
+
+    let mut ephemeral = [0; KEY_LEN];
+    ephemeral.copy_from_slice(&sealed[5..5 + KEY_LEN]);
+    let ephemeral = PublicKey::from(ephemeral);
+    let count = u16::from_be_bytes([sealed[PREFIX_LEN - 2], sealed[PREFIX_LEN - 1]]) as usize;
+    let header_len = PREFIX_LEN + count * WRAPPED_LEN + NONCE_LEN;
+    if sealed.len() < header_len + TAG_LEN {
+        return Err(too_short());
+    }
+
+    let shared = identity.0.diffie_hellman(&ephemeral);
+    if !shared.was_contributory() {
+        return Err(Error::CorruptedCiphertext(
+            "ephemeral key is not a valid public key".to_string(),
+        ));
+    }
+    let (cipher, wrapping_nonce) = wrapping_cipher(&wrapping_key(
+        shared.as_bytes(),
+        &ephemeral,
+        &identity.recipient().0,
+    ));
+    let content_key = sealed[PREFIX_LEN..PREFIX_LEN + count * WRAPPED_LEN]
+        .chunks_exact(WRAPPED_LEN)
+        .find_map(|wrapped| cipher.decrypt(&wrapping_nonce, wrapped).ok())
+        .ok_or(Error::NotRecipient)?;
+
+    let aad = [&chunk_type.bytes()[..], envelope, &sealed[..header_len]].concat();
+    XChaCha20Poly1305::new_from_slice(&content_key)
+        .expect("content key has right length")
+        .decrypt(
+            XNonce::from_slice(&sealed[header_len - NONCE_LEN..header_len]),
+            Payload {
+                msg: &sealed[header_len..],
+                aad: &aad,
+            },
+        )
+        .map_err(|_| {
+            Error::CorruptedCiphertext(
+                "authentication failed, message or its envelope was modified or moved to \
+                 another chunk type"
+                    .to_string(),
+            )
+        })
+}
+
+impl Chunk {
+    /// Create chunk with data encrypted for every given recipient (X25519 and
+    /// XChaCha20-Poly1305), any of them can decrypt it with its [`Identity`]
+    pub fn encrypted_for(
+        chunk_type: ChunkType,
+        data: &[u8],
+        recipients: &[RecipientKey],
+    ) -> Result<Chunk> {
+        let envelope = Envelope {
+            encrypted: true,
+            ..Envelope::new(ContentType::Text)
+        };
+        let encrypted = encrypt(data, recipients, &chunk_type, &envelope.authenticated()?)?;
+        Ok(sealed_chunk(chunk_type, envelope, &encrypted))
+    }
+
+    /// Decrypt data of chunk created with [`Chunk::encrypted_for`]. Fails with
+    /// [`Error::NotRecipient`] or [`Error::CorruptedCiphertext`]
+    pub fn decrypt_with(&self, identity: &Identity) -> Result<Vec<u8>> {
+        decrypt(
+            self.payload(),
+            identity,
+            self.chunk_type(),
+            authenticated(self.data()),
+        )
+    }
+
+    /// Whether envelope of chunk tells its message is encrypted, for recipients
+    pub fn is_encrypted_for_recipients(&self) -> bool {
+        self.envelope().is_some_and(|envelope| envelope.encrypted)
+            && is_encrypted_for_recipients(self.payload())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_type() -> ChunkType {
+        ChunkType::try_from(*b"ruSt").unwrap()
+    }
+
+    #[test]
+    fn test_multiple_recipients() {
+        let identities = [Identity::generate(), Identity::generate()];
+        let recipients: Vec<RecipientKey> = identities.iter().map(Identity::recipient).collect();
+        let chunk = Chunk::encrypted_for(chunk_type(), b"top secret", &recipients).unwrap();
+
+        assert!(chunk.is_encrypted_for_recipients());
+        assert!(!chunk.is_encrypted());
+        assert_eq!(
+            chunk.payload().len(),
+            PREFIX_LEN + 2 * WRAPPED_LEN + NONCE_LEN + 10 + TAG_LEN
+        );
+        for identity in &identities {
+            assert_eq!(chunk.decrypt_with(identity).unwrap(), b"top secret");
+        }
+        assert!(matches!(
+            chunk.decrypt_with(&Identity::generate()),
+            Err(Error::NotRecipient)
+        ));
+    }
+
+    #[test]
+    fn test_tampered() {
+        let identity = Identity::generate();
+        let chunk =
+            Chunk::encrypted_for(chunk_type(), b"top secret", &[identity.recipient()]).unwrap();
+
+        let moved = Chunk::new(
+            ChunkType::try_from(*b"abCd").unwrap(),
+            chunk.data().to_vec(),
+        );
+        assert!(matches!(
+            moved.decrypt_with(&identity),
+            Err(Error::CorruptedCiphertext(_))
+        ));
+
+        let mut data = chunk.data().to_vec();
+        data.truncate(data.len() - 1);
+        let truncated = Chunk::new(chunk_type(), data);
+        assert!(matches!(
+            truncated.decrypt_with(&identity),
+            Err(Error::CorruptedCiphertext(_))
+        ));
+
+        let (envelope, payload) = Envelope::open(chunk.data()).unwrap();
+        let changed = Envelope {
+            filename: Some("secret.txt".to_string()),
+            ..envelope
+        };
+        let resealed = Chunk::enveloped(chunk_type(), &changed, payload).unwrap();
+        assert!(matches!(
+            resealed.decrypt_with(&identity),
+            Err(Error::CorruptedCiphertext(_))
+        ));
+
+        let empty = Chunk::new(chunk_type(), MAGIC.to_vec());
+        assert!(matches!(
+            empty.decrypt_with(&identity),
+            Err(Error::CorruptedCiphertext(_))
+        ));
+    }
+
+    #[test]
+    fn test_key_text() {
+        let identity = Identity::generate();
+        let recipient = identity.recipient();
+
+        let parsed: Identity = identity.to_string().parse().unwrap();
+        assert_eq!(parsed.recipient(), recipient);
+        assert_eq!(
+            format!("{}\n", recipient).parse::<RecipientKey>().unwrap(),
+            recipient
+        );
+        assert_eq!(recipient.fingerprint().len(), 16);
+        assert!(!format!("{:?}", identity).contains(&hex::encode(identity.0.as_bytes())));
+
+        assert!(matches!(
+            recipient.to_string().parse::<Identity>(),
+            Err(Error::InvalidKey(_))
+        ));
+        assert!(matches!(
+            "pngme-x25519-public 1234".parse::<RecipientKey>(),
+            Err(Error::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_recipient() {
+        // low order point makes shared secret zero
+        let weak = RecipientKey(PublicKey::from([0; KEY_LEN]));
+        assert!(matches!(
+            Chunk::encrypted_for(chunk_type(), b"data", &[weak]),
+            Err(Error::InvalidKey(_))
+        ));
+        assert!(matches!(
+            Chunk::encrypted_for(chunk_type(), b"data", &[]),
+            Err(Error::InvalidKey(_))
+        ));
+    }
+}
@@ -0,0 +1,327 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::Location;
+use crate::lenient::Recovery;
+use crate::png::Png;
+use crate::reader::ParseOptions;
+use crate::validate::Issue;
+use crate::{Error, Result};
+
+/// Chunks whose wrong crc is recomputed by [`Png::repair`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrcRepair {
+    #[default]
+    All,
+    /// Only ancillary chunks, so corrupted image data is not silently accepted
+    Ancillary,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepairOptions {
+    pub crc: CrcRepair,
+    /// Append IEND chunk if png does not contain one
+    pub append_iend: bool,
+    /// Remove chunks and bytes after IEND. They are kept (and repaired) by default, as
+    /// hidden messages are often stored there
+    pub strip_after_iend: bool,
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        RepairOptions {
+            crc: CrcRepair::All,
+            append_iend: true,
+            strip_after_iend: false,
+        }
+    }
+}
+
+/// Single change applied by [`Png::repair`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    /// Short identifier of fixed problem, stable for machine processing
+    pub code: &'static str,
+    /// Location of fixed chunk in original input, `None` for changes of png as a whole
+    pub location: Option<Location>,
+    pub message: String,
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.code)?;
+        if let Some(location) = self.location {
+            write!(f, " chunk {} at offset {}", location.index, location.offset)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Png salvaged from corrupted input
+#[derive(Debug, Clone)]
+pub struct Repaired {
+    pub png: Png,
+    /// Bytes after last chunk kept as they are (empty when
+    /// [`RepairOptions::strip_after_iend`] is set)
+    pub trailing: Vec<u8>,
+    /// Every change made to input
+    pub fixes: Vec<Fix>,
+    /// Problems which were found, but left as they are because of repair options
+    pub unfixed: Vec<Issue>,
+}
+
+impl Repaired {
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.png.write_to(writer)?;
+        writer.write_all(&self.trailing)
+    }
+
+    /// Write repaired png to given path, replacing file if it already exists
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let write = || {
+            let mut writer = BufWriter::new(File::create(path)?);
+            self.write_to(&mut writer)?;
+            writer.flush()
+        };
+        write().map_err(|err| Error::from(err).with_path(path))
+    }
+}
+
+impl Png {
+    /// Salvage png from corrupted input.
+    ///
+    /// Input is parsed leniently (see [`Png::parse_lenient`]), so garbage and corrupted length
+    /// fields are skipped up to the next intact chunk. Then wrong crcs are recomputed,
+    /// missing IEND is appended and data after IEND is removed or kept, as selected by options.
+    pub fn repair(bytes: &[u8], options: ParseOptions, repair: RepairOptions) -> Result<Repaired> {
+        let Recovery {
+            mut chunks,
+            offsets,
+            issues,
+            chunks_end,
+            ..
+        } = Recovery::run(bytes, options)?;
+
+        let iend = chunks
+            .iter()
+            .position(|chunk| *chunk.chunk_type() == ChunkType::IEND);
+        // kept data is repaired as well, except for bytes after last chunk. Truncated IEND
+        // takes fewer bytes of input than its length says
+        let kept_end = match iend {
+            Some(index) if repair.strip_after_iend => {
+                (offsets[index] + chunks[index].bytes_len()).min(chunks_end)
+            }
+            _ => chunks_end,
+        };
+
+        let mut fixes = Vec::new();
+        let mut unfixed = Vec::new();
+        for issue in issues
+            .into_iter()
+            .filter(|issue| issue.location.is_none_or(|l| l.offset < kept_end))
+        {
+            if issue.code != "crc-mismatch" {
+                fixes.push(Fix {
+                    code: issue.code,
+                    location: issue.location,
+                    message: issue.message,
+                });
+                continue;
+            }
+
+            let index = issue.location.map_or(0, |location| location.index);
+            let chunk = &chunks[index];
+            let fix = match repair.crc {
+                CrcRepair::All => true,
+                CrcRepair::Ancillary => !chunk.chunk_type().is_critical(),
+                CrcRepair::None => false,
+            };
+            if !fix {
+                unfixed.push(issue);
+                continue;
+            }
+
+            let fixed = Chunk::new(*chunk.chunk_type(), chunk.data().to_vec());
+            fixes.push(Fix {
+                code: issue.code,
+                location: issue.location,
+                message: format!(
+                    "recomputed crc of chunk {} from {} to {}",
+                    chunk.chunk_type(),
+                    chunk.crc(),
+                    fixed.crc()
+                ),
+            });
+            chunks[index] = fixed;
+        }
+
+        if let Some(index) = iend.filter(|_| repair.strip_after_iend) {
+            chunks.truncate(index + 1);
+        }
+        if iend.is_none() && repair.append_iend {
+            chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+            fixes.push(Fix {
+                code: "missing-iend",
+                location: None,
+                message: "appended IEND chunk".to_string(),
+            });
+        }
+
+        let mut trailing = Vec::new();
+        if !repair.strip_after_iend {
+            trailing = bytes[chunks_end..].to_vec();
+        } else if iend.is_some() && kept_end < bytes.len() {
+            fixes.push(Fix {
+                code: "after-iend",
+                location: None,
+                message: format!("removed {} bytes after IEND", bytes.len() - kept_end),
+            });
+        }
+
+        Ok(Repaired {
+            png: Png::from_chunks(chunks),
+            trailing,
+            fixes,
+            unfixed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::from_strings("FrSt", "I am the first chunk").unwrap(),
+            Chunk::from_strings("miDl", "I am another chunk").unwrap(),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    fn codes(fixes: &[Fix]) -> Vec<&str> {
+        fixes.iter().map(|fix| fix.code).collect()
+    }
+
+    #[test]
+    fn test_nothing_to_repair() {
+        let bytes = testing_png().as_bytes();
+        let repaired =
+            Png::repair(&bytes, ParseOptions::default(), RepairOptions::default()).unwrap();
+
+        assert!(repaired.fixes.is_empty());
+        assert_eq!(repaired.png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_repair_crc() {
+        let mut bytes = testing_png().as_bytes();
+        // crc of both first (critical) and second (ancillary) chunk
+        bytes[8 + 31] ^= 1;
+        bytes[8 + 32 + 29] ^= 1;
+
+        let repaired =
+            Png::repair(&bytes, ParseOptions::default(), RepairOptions::default()).unwrap();
+        assert_eq!(codes(&repaired.fixes), ["crc-mismatch", "crc-mismatch"]);
+        assert_eq!(repaired.png.as_bytes(), testing_png().as_bytes());
+
+        let options = RepairOptions {
+            crc: CrcRepair::Ancillary,
+            ..RepairOptions::default()
+        };
+        let repaired = Png::repair(&bytes, ParseOptions::default(), options).unwrap();
+        assert_eq!(codes(&repaired.fixes), ["crc-mismatch"]);
+        assert_eq!(repaired.fixes[0].location.unwrap().index, 1);
+        assert_eq!(repaired.unfixed.len(), 1);
+        assert!(!repaired.png.chunks()[0].has_valid_crc());
+        assert!(Png::try_from(&repaired.png.as_bytes()[..]).is_err());
+    }
+
+    #[test]
+    fn test_append_missing_iend() {
+        let bytes = testing_png().as_bytes();
+        let repaired = Png::repair(
+            &bytes[..bytes.len() - 12],
+            ParseOptions::default(),
+            RepairOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(codes(&repaired.fixes), ["missing-iend"]);
+        assert_eq!(repaired.png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let bytes = testing_png().as_bytes();
+        // inside data of the second chunk and inside crc of IEND
+        for len in [8 + 32 + 20, bytes.len() - 2] {
+            for strip_after_iend in [false, true] {
+                let options = RepairOptions {
+                    strip_after_iend,
+                    ..RepairOptions::default()
+                };
+                let repaired =
+                    Png::repair(&bytes[..len], ParseOptions::default(), options).unwrap();
+
+                assert!(repaired.trailing.is_empty());
+                assert_eq!(
+                    repaired.png.chunks().last().unwrap().chunk_type(),
+                    &ChunkType::IEND
+                );
+                assert!(Png::try_from(&repaired.png.as_bytes()[..]).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_data_after_iend() {
+        let png = testing_png();
+        let mut bytes = png.as_bytes();
+        Chunk::from_strings("ruSt", "hidden")
+            .unwrap()
+            .write_to(&mut bytes)
+            .unwrap();
+        bytes.extend_from_slice(b"raw");
+
+        let repaired =
+            Png::repair(&bytes, ParseOptions::default(), RepairOptions::default()).unwrap();
+        assert!(repaired.fixes.is_empty());
+        let mut written = Vec::new();
+        repaired.write_to(&mut written).unwrap();
+        assert_eq!(written, bytes);
+
+        let options = RepairOptions {
+            strip_after_iend: true,
+            ..RepairOptions::default()
+        };
+        let repaired = Png::repair(&bytes, ParseOptions::default(), options).unwrap();
+        assert_eq!(codes(&repaired.fixes), ["after-iend"]);
+        assert!(repaired.fixes[0].message.contains("removed 21 bytes"));
+        assert_eq!(repaired.png.as_bytes(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_resync_after_corrupted_length() {
+        let png = testing_png();
+        let mut bytes = png.as_bytes();
+        bytes[8] = 0x40;
+
+        let repaired =
+            Png::repair(&bytes, ParseOptions::default(), RepairOptions::default()).unwrap();
+
+        assert_eq!(codes(&repaired.fixes), ["garbage"]);
+        assert_eq!(repaired.png.chunks().len(), 2);
+        assert_eq!(
+            repaired.png.chunks()[0].data_as_string().unwrap(),
+            "I am another chunk"
+        );
+    }
+}
@@ -0,0 +1,408 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use ed25519_dalek::{Signature, Signer, Verifier};
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::envelope::{authenticated, contents, sealed_chunk, ContentType, Envelope};
+use crate::png::Png;
+use crate::recipient::{fingerprint, parse_key, read_key_file};
+use crate::{Error, Result};
+
+const MAGIC: &[u8; 4] = b"PNGS";
+const VERSION: u8 = 1;
+const KEY_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+/// Magic, version, signer public key and signature
+const HEADER_LEN: usize = 4 + 1 + KEY_LEN + SIGNATURE_LEN;
+const CONTEXT: &[u8] = b"pngme signature\0";
+
+const VERIFYING_PREFIX: &str = "pngme-ed25519-public ";
+const SIGNING_PREFIX: &str = "pngme-ed25519-secret ";
+
+/// Ed25519 public key which checks signatures made by its [`SigningKey`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyingKey(ed25519_dalek::VerifyingKey);
+
+impl VerifyingKey {
+    /// Read key from file written by keygen
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<VerifyingKey> {
+        read_key_file(path.as_ref())
+    }
+
+    /// Short hex digest identifying the key
+    pub fn fingerprint(&self) -> String {
+        fingerprint(self.0.as_bytes())
+    }
+}
+
+impl FromStr for VerifyingKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ed25519_dalek::VerifyingKey::from_bytes(&parse_key(s, VERIFYING_PREFIX)?)
+            .map(VerifyingKey)
+            .map_err(|_| Error::InvalidKey("key is not a valid ed25519 public key".to_string()))
+    }
+}
+
+impl fmt::Display for VerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", VERIFYING_PREFIX, hex::encode(self.0.as_bytes()))
+    }
+}
+
+/// Ed25519 secret key which signs messages
+#[derive(Clone)]
+pub struct SigningKey(ed25519_dalek::SigningKey);
+
+impl SigningKey {
+    pub fn generate() -> SigningKey {
+        let mut seed = [0; KEY_LEN];
+        OsRng.fill_bytes(&mut seed);
+        SigningKey(ed25519_dalek::SigningKey::from_bytes(&seed))
+    }
+
+    /// Read key from file written by keygen
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SigningKey> {
+        read_key_file(path.as_ref())
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(self.0.verifying_key())
+    }
+}
+
+impl FromStr for SigningKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(SigningKey(ed25519_dalek::SigningKey::from_bytes(
+            &parse_key(s, SIGNING_PREFIX)?,
+        )))
+    }
+}
+
+impl fmt::Display for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", SIGNING_PREFIX, hex::encode(self.0.as_bytes()))
+    }
+}
+
+/// Secret is left out, so it does not end up in logs
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SigningKey")
+            .field(&self.verifying_key())
+            .finish()
+    }
+}
+
+/// Result of checking signature of a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// Signature matches message, its chunk type and signer
+    Valid(VerifyingKey),
+    /// Signature is malformed, does not match or was made by another key
+    Invalid(String),
+    /// Message carries no signature
+    Unsigned,
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verification::Valid(signer) => {
+                write!(f, "valid signature by {}", signer.fingerprint())
+            }
+            Verification::Invalid(reason) => write!(f, "invalid signature: {}", reason),
+            Verification::Unsigned => write!(f, "unsigned"),
+        }
+    }
+}
+
+/// Signed bytes, binding payload to chunk type and authenticated bytes of its envelope
+fn signed_message(chunk_type: &ChunkType, envelope: &[u8], payload: &[u8]) -> Vec<u8> {
+    [CONTEXT, &chunk_type.bytes(), envelope, payload].concat()
+}
+
+/// Whether data starts like message signed with [`Chunk::signed`]
+pub(crate) fn is_signed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Payload with signature of signer and its public key in front. Envelope is the one
+/// payload will be sealed in, see [`Envelope::authenticated`]
+pub(crate) fn sign(
+    payload: &[u8],
+    key: &SigningKey,
+    chunk_type: &ChunkType,
+    envelope: &[u8],
+) -> Vec<u8> {
+    let signature = key.0.sign(&signed_message(chunk_type, envelope, payload));
+    let mut signed = Vec::with_capacity(HEADER_LEN + payload.len());
+    signed.extend_from_slice(MAGIC);
+    signed.push(VERSION);
+    signed.extend_from_slice(key.0.verifying_key().as_bytes());
+    signed.extend_from_slice(&signature.to_bytes());
+    signed.extend_from_slice(payload);
+    signed
+}
+
+/// Check signature made by [`sign`] for the same chunk type and envelope
+pub(crate) fn verify(data: &[u8], chunk_type: &ChunkType, envelope: &[u8]) -> Verification {
+    if !is_signed(data) {
+        return Verification::Unsigned;
+    }
+    if data.len() < HEADER_LEN {
+        return Verification::Invalid(format!(
+            "signed message needs at least {} bytes, but it has {}",
+            HEADER_LEN,
+            data.len()
+        ));
+    }
+    if data[4] != VERSION {
+        return Verification::Invalid(format!("unknown signature version {}", data[4]));
+    }
+
+    let mut key = [0; KEY_LEN];
+    key.copy_from_slice(&data[5..5 + KEY_LEN]);
+    let Ok(signer) = ed25519_dalek::VerifyingKey::from_bytes(&key) else {
+        return Verification::Invalid("signer is not a valid public key".to_string());
+    };
+    let signature =
+        Signature::from_slice(&data[5 + KEY_LEN..HEADER_LEN]).expect("signature has right length");
+    let message = signed_message(chunk_type, envelope, &data[HEADER_LEN..]);
+    match signer.verify(&message, &signature) {
+        Ok(()) => Verification::Valid(VerifyingKey(signer)),
+        Err(_) => Verification::Invalid(
+            "signature does not match, message or its envelope was modified or moved to \
+             another chunk type"
+                .to_string(),
+        ),
+    }
+}
+
+/// Data without signature, unchanged when it is not signed
+pub(crate) fn strip(data: &[u8]) -> &[u8] {
+    if is_signed(data) && data.len() >= HEADER_LEN {
+        &data[HEADER_LEN..]
+    } else {
+        data
+    }
+}
+
+impl Chunk {
+    /// Create chunk with payload and detached ed25519 signature over chunk type, envelope
+    /// and payload
+    pub fn signed(chunk_type: ChunkType, payload: &[u8], key: &SigningKey) -> Chunk {
+        let envelope = Envelope {
+            signed: true,
+            ..Envelope::new(ContentType::Text)
+        };
+        let authenticated = envelope
+            .authenticated()
+            .expect("text envelope has no filename");
+        let signed = sign(payload, key, &chunk_type, &authenticated);
+        sealed_chunk(chunk_type, envelope, &signed)
+    }
+
+    /// Whether envelope of chunk tells its message is signed
+    pub fn is_signed(&self) -> bool {
+        self.envelope().is_some_and(|envelope| envelope.signed)
+    }
+
+    /// Check signature of chunk, valid result tells who signed it
+    pub fn verification(&self) -> Verification {
+        if !self.is_signed() {
+            return Verification::Unsigned;
+        }
+        verify(
+            contents(self.data()),
+            self.chunk_type(),
+            authenticated(self.data()),
+        )
+    }
+
+    /// Check that chunk is signed by given key
+    pub fn verify(&self, key: &VerifyingKey) -> Verification {
+        match self.verification() {
+            Verification::Valid(signer) if signer != *key => Verification::Invalid(format!(
+                "signed by {}, not by {}",
+                signer.fingerprint(),
+                key.fingerprint()
+            )),
+            verification => verification,
+        }
+    }
+
+    /// Data without envelope and signature, the same as [`Chunk::data`] for legacy chunks
+    pub fn payload(&self) -> &[u8] {
+        match Envelope::open(self.data()) {
+            Some((envelope, payload)) if envelope.signed => strip(payload),
+            Some((_, payload)) => payload,
+            None => self.data(),
+        }
+    }
+}
+
+impl Png {
+    /// Check every signed chunk, returning its index with result
+    pub fn verify_signatures(&self) -> Vec<(usize, Verification)> {
+        self.chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.is_signed())
+            .map(|(index, chunk)| (index, chunk.verification()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_type() -> ChunkType {
+        ChunkType::try_from(*b"ruSt").unwrap()
+    }
+
+    /// Chunk with signed payload changed, but envelope checksum still valid
+    fn tampered(chunk: &Chunk, tamper: impl FnOnce(&mut Vec<u8>)) -> Chunk {
+        let (envelope, payload) = Envelope::open(chunk.data()).unwrap();
+        let mut payload = payload.to_vec();
+        tamper(&mut payload);
+        Chunk::enveloped(*chunk.chunk_type(), &envelope, &payload).unwrap()
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = SigningKey::generate();
+        let chunk = Chunk::signed(chunk_type(), b"top secret", &key);
+
+        assert!(chunk.is_signed());
+        assert_eq!(chunk.payload(), b"top secret");
+        assert_eq!(
+            chunk.verification(),
+            Verification::Valid(key.verifying_key())
+        );
+        assert_eq!(
+            chunk.verify(&key.verifying_key()),
+            Verification::Valid(key.verifying_key())
+        );
+        assert!(matches!(
+            chunk.verify(&SigningKey::generate().verifying_key()),
+            Verification::Invalid(_)
+        ));
+    }
+
+    #[test]
+    fn test_unsigned() {
+        let chunk = Chunk::new(chunk_type(), b"plain".to_vec());
+
+        assert_eq!(chunk.verification(), Verification::Unsigned);
+        assert_eq!(chunk.payload(), b"plain");
+    }
+
+    #[test]
+    fn test_tampered() {
+        let key = SigningKey::generate();
+        let chunk = Chunk::signed(chunk_type(), b"top secret", &key);
+
+        let modified = tampered(&chunk, |payload| *payload.last_mut().unwrap() ^= 1);
+        assert!(matches!(modified.verification(), Verification::Invalid(_)));
+
+        let moved = Chunk::new(
+            ChunkType::try_from(*b"abCd").unwrap(),
+            chunk.data().to_vec(),
+        );
+        assert!(matches!(moved.verification(), Verification::Invalid(_)));
+
+        let truncated = tampered(&chunk, |payload| payload.truncate(40));
+        assert!(matches!(truncated.verification(), Verification::Invalid(_)));
+
+        // damaged envelope makes chunk legacy raw data, which carries no signature
+        let mut data = chunk.data().to_vec();
+        *data.last_mut().unwrap() ^= 1;
+        let damaged = Chunk::new(chunk_type(), data);
+        assert_eq!(damaged.verification(), Verification::Unsigned);
+    }
+
+    #[test]
+    fn test_changed_envelope() {
+        let key = SigningKey::generate();
+        let envelope = Envelope {
+            filename: Some("report.json".to_string()),
+            signed: true,
+            ..Envelope::new(ContentType::File)
+        };
+        let signed = sign(
+            b"{}",
+            &key,
+            &chunk_type(),
+            &envelope.authenticated().unwrap(),
+        );
+        let chunk = Chunk::enveloped(chunk_type(), &envelope, &signed).unwrap();
+        assert_eq!(
+            chunk.verification(),
+            Verification::Valid(key.verifying_key())
+        );
+
+        // envelope checksum is recomputed, so only signature tells the change
+        for changed in [
+            Envelope {
+                filename: Some("invoice.json".to_string()),
+                ..envelope.clone()
+            },
+            Envelope {
+                created: envelope.created + 1,
+                ..envelope.clone()
+            },
+            Envelope {
+                compressed: true,
+                ..envelope.clone()
+            },
+        ] {
+            let resealed = Chunk::enveloped(chunk_type(), &changed, &signed).unwrap();
+            assert!(matches!(resealed.verification(), Verification::Invalid(_)));
+        }
+    }
+
+    #[test]
+    fn test_key_text() {
+        let key = SigningKey::generate();
+        let parsed: SigningKey = key.to_string().parse().unwrap();
+        assert_eq!(parsed.verifying_key(), key.verifying_key());
+        assert_eq!(
+            key.verifying_key()
+                .to_string()
+                .parse::<VerifyingKey>()
+                .unwrap(),
+            key.verifying_key()
+        );
+        assert!(!format!("{:?}", key).contains(&hex::encode(key.0.as_bytes())));
+        assert!(matches!(
+            key.to_string().parse::<VerifyingKey>(),
+            Err(Error::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_signatures() {
+        let key = SigningKey::generate();
+        let mut png = Png::from_chunks(vec![
+            Chunk::new(chunk_type(), b"plain".to_vec()),
+            Chunk::signed(chunk_type(), b"signed", &key),
+        ]);
+        let forged = Chunk::signed(chunk_type(), b"forged", &key);
+        png.append_chunk(tampered(&forged, |payload| payload[HEADER_LEN] ^= 1));
+
+        let results = png.verify_signatures();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], (1, Verification::Valid(key.verifying_key())));
+        assert!(matches!(results[1], (2, Verification::Invalid(_))));
+    }
+}
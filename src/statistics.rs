@@ -0,0 +1,277 @@
+/// Result of chi-square attack on pairs of sample values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ChiSquare {
+    pub(crate) statistic: f64,
+    pub(crate) degrees_of_freedom: usize,
+    /// Probability that samples carry hidden bits in their least significant bit
+    pub(crate) probability: f64,
+}
+
+/// Chi-square attack of Westfeld and Pfitzmann. Embedding random bits into least significant
+/// bits makes counts of values 2k and 2k + 1 equal, so histogram matching the expected
+/// averages of such pairs too well means hidden data. `None` if there are too few samples
+pub(crate) fn chi_square(values: &[u16], max: u16) -> Option<ChiSquare> {
+    let mut histogram = vec![0u64; max as usize + 1];
+    for &value in values {
+        histogram[value as usize] += 1;
+    }
+
+    let mut statistic = 0.0;
+    let mut pairs = 0;
+    for pair in histogram.chunks_exact(2) {
+        let expected = (pair[0] + pair[1]) as f64 / 2.0;
+        // rare values make the test unreliable
+        if expected > 4.0 {
+            statistic += (pair[0] as f64 - expected).powi(2) / expected;
+            pairs += 1;
+        }
+    }
+    if pairs < 2 {
+        return None;
+    }
+
+    let degrees_of_freedom = pairs - 1;
+    Some(ChiSquare {
+        statistic,
+        degrees_of_freedom,
+        probability: upper_gamma(degrees_of_freedom as f64 / 2.0, statistic / 2.0),
+    })
+}
+
+/// Logarithm of gamma function (Lanczos approximation)
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000000000190015, |sum, (i, c)| {
+            sum + c / (x + 1.0 + i as f64)
+        });
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Regularized upper incomplete gamma function Q(a, x)
+fn upper_gamma(a: f64, x: f64) -> f64 {
+    const ITERATIONS: usize = 1000;
+    const EPSILON: f64 = 1e-12;
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let prefix = (-x + a * x.ln() - ln_gamma(a)).exp();
+
+    if x < a + 1.0 {
+        // series of lower function converges quickly here
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..ITERATIONS {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+        (1.0 - sum * prefix).clamp(0.0, 1.0)
+    } else {
+        // continued fraction (modified Lentz method)
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut fraction = d;
+        for i in 1..ITERATIONS {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            fraction *= delta;
+            if (delta - 1.0).abs() < EPSILON {
+                break;
+            }
+        }
+        (prefix * fraction).clamp(0.0, 1.0)
+    }
+}
+
+/// Counts of regular and singular groups (as fractions of all groups) for mask and
+/// negative mask
+struct RsCounts {
+    regular: f64,
+    singular: f64,
+    negative_regular: f64,
+    negative_singular: f64,
+}
+
+impl RsCounts {
+    const MASK: [bool; 4] = [false, true, true, false];
+
+    fn of(values: &[i32]) -> RsCounts {
+        // smoothness of group, it grows with noise
+        let variation =
+            |group: &[i32]| -> i32 { group.windows(2).map(|pair| (pair[1] - pair[0]).abs()).sum() };
+        let flip = |value: i32| value ^ 1;
+        let negative_flip = |value: i32| flip(value + 1) - 1;
+
+        let mut counts = [0usize; 4];
+        let mut groups = 0;
+        for group in values.chunks_exact(Self::MASK.len()) {
+            groups += 1;
+            let original = variation(group);
+            let flipped: Vec<i32> = group
+                .iter()
+                .zip(Self::MASK)
+                .map(|(&value, masked)| if masked { flip(value) } else { value })
+                .collect();
+            let negative: Vec<i32> = group
+                .iter()
+                .zip(Self::MASK)
+                .map(|(&value, masked)| if masked { negative_flip(value) } else { value })
+                .collect();
+
+            for (i, changed) in [variation(&flipped), variation(&negative)]
+                .into_iter()
+                .enumerate()
+            {
+                if changed > original {
+                    counts[2 * i] += 1;
+                } else if changed < original {
+                    counts[2 * i + 1] += 1;
+                }
+            }
+        }
+
+        let fraction = |count: usize| count as f64 / groups.max(1) as f64;
+        RsCounts {
+            regular: fraction(counts[0]),
+            singular: fraction(counts[1]),
+            negative_regular: fraction(counts[2]),
+            negative_singular: fraction(counts[3]),
+        }
+    }
+}
+
+/// RS analysis of Fridrich, Goljan and Du. Estimates fraction of samples (0 to 1) whose least
+/// significant bit carries hidden data from how flipping such bits changes smoothness of
+/// groups of neighbouring samples. `None` if there are too few samples or estimate fails
+pub(crate) fn rs_analysis(values: &[u16]) -> Option<f64> {
+    if values.len() < 64 {
+        return None;
+    }
+    let original: Vec<i32> = values.iter().map(|&value| value as i32).collect();
+    let flipped: Vec<i32> = original.iter().map(|&value| value ^ 1).collect();
+    let counts = RsCounts::of(&original);
+    let flipped_counts = RsCounts::of(&flipped);
+
+    let d0 = counts.regular - counts.singular;
+    let d1 = flipped_counts.regular - flipped_counts.singular;
+    let negative_d0 = counts.negative_regular - counts.negative_singular;
+    let negative_d1 = flipped_counts.negative_regular - flipped_counts.negative_singular;
+
+    let a = 2.0 * (d1 + d0);
+    let b = negative_d0 - negative_d1 - d1 - 3.0 * d0;
+    let c = d0 - negative_d0;
+    let x = if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return None;
+        }
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [(-b + root) / (2.0 * a), (-b - root) / (2.0 * a)]
+            .into_iter()
+            .min_by(|x, y| x.abs().total_cmp(&y.abs()))?
+    };
+
+    Some((x / (x - 0.5)).clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Smooth gradient with a little noise and stretched contrast (leaving gaps in histogram),
+    /// resembling processed photograph
+    fn natural_values() -> Vec<u16> {
+        let mut seed: u32 = 12345;
+        (0..20000)
+            .map(|i| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                let noise = (seed >> 16) % 7;
+                let x = (i % 200) as f64;
+                let y = (i / 200) as f64;
+                let base = (70.0 + 40.0 * (x / 30.0).sin() + 30.0 * (y / 20.0).cos()) as u16;
+                (base + noise as u16) * 3 / 2
+            })
+            .collect()
+    }
+
+    /// Replace least significant bits of the first `fraction` of values with pseudo-random bits
+    fn embedded(values: &[u16], fraction: f64) -> Vec<u16> {
+        let mut seed: u32 = 999;
+        let count = (values.len() as f64 * fraction) as usize;
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                if i < count {
+                    (value & !1) | ((seed >> 16) & 1) as u16
+                } else {
+                    value
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_upper_gamma() {
+        // chi-square with 2 degrees of freedom has survival function exp(-x / 2)
+        assert!((upper_gamma(1.0, 1.5) - (-1.5f64).exp()).abs() < 1e-9);
+        assert!((upper_gamma(1.0, 0.2) - (-0.2f64).exp()).abs() < 1e-9);
+        assert!((upper_gamma(3.0, 0.0) - 1.0).abs() < 1e-12);
+        assert!(upper_gamma(5.0, 100.0) < 1e-9);
+    }
+
+    #[test]
+    fn test_chi_square() {
+        let natural = natural_values();
+        let clean = chi_square(&natural, 255).unwrap();
+        let full = chi_square(&embedded(&natural, 1.0), 255).unwrap();
+
+        assert!(clean.probability < 0.05, "{:?}", clean);
+        assert!(full.probability > 0.5, "{:?}", full);
+        assert!(chi_square(&[1, 2, 3], 255).is_none());
+    }
+
+    #[test]
+    fn test_rs_analysis() {
+        let natural = natural_values();
+        let clean = rs_analysis(&natural).unwrap();
+        let half = rs_analysis(&embedded(&natural, 0.5)).unwrap();
+        let full = rs_analysis(&embedded(&natural, 1.0)).unwrap();
+
+        assert!(clean < 0.1, "{}", clean);
+        assert!((half - 0.5).abs() < 0.2, "{}", half);
+        assert!(full > 0.8, "{}", full);
+        assert!(rs_analysis(&[1, 2, 3]).is_none());
+    }
+}
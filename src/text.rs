@@ -0,0 +1,491 @@
+use std::fmt;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::reader::ParseOptions;
+use crate::zlib::{deflate, inflate};
+use crate::{Error, Result};
+
+/// Zlib level used for zTXt and compressed iTXt chunks
+const COMPRESSION_LEVEL: u32 = 9;
+
+/// Uncompressed Latin-1 text stored in tEXt chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    pub keyword: String,
+    pub text: String,
+}
+
+/// Latin-1 text compressed with zlib stored in zTXt chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedTextChunk {
+    pub keyword: String,
+    pub text: String,
+}
+
+/// UTF-8 text (optionally compressed) with language information stored in iTXt chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalTextChunk {
+    pub keyword: String,
+    pub compressed: bool,
+    /// Language of text like `en` or `pl-PL`, empty if unknown
+    pub language_tag: String,
+    /// Keyword translated to language of text, empty if missing
+    pub translated_keyword: String,
+    pub text: String,
+}
+
+/// Any of text chunks defined by png specification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextMetadata {
+    Text(TextChunk),
+    Compressed(CompressedTextChunk),
+    International(InternationalTextChunk),
+}
+
+fn invalid<T>(reason: String) -> Result<T> {
+    Err(Error::InvalidText(reason))
+}
+
+fn latin1_bytes(value: &str, name: &str) -> Result<Vec<u8>> {
+    value
+        .chars()
+        .map(|c| match u8::try_from(c as u32) {
+            Ok(byte) => Ok(byte),
+            Err(_) => invalid(format!(
+                "{} contains '{}' which is not a Latin-1 character (use iTXt instead)",
+                name, c
+            )),
+        })
+        .collect()
+}
+
+fn latin1_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| byte as char).collect()
+}
+
+fn utf8_string(bytes: Vec<u8>, name: &str) -> Result<String> {
+    String::from_utf8(bytes).or_else(|_| invalid(format!("{} is not valid UTF-8", name)))
+}
+
+/// Check that keyword has 1-79 printable Latin-1 characters without leading,
+/// trailing or consecutive spaces
+pub(crate) fn validate_keyword(keyword: &str) -> Result<()> {
+    let len = keyword.chars().count();
+    if !(1..=79).contains(&len) {
+        return invalid(format!(
+            "keyword must have from 1 to 79 characters, but it has {}",
+            len
+        ));
+    }
+    if let Some(c) = keyword
+        .chars()
+        .find(|&c| !matches!(c as u32, 32..=126 | 161..=255))
+    {
+        return invalid(format!(
+            "keyword contains {:?} which is not a printable Latin-1 character",
+            c
+        ));
+    }
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return invalid("keyword cannot have leading, trailing or consecutive spaces".to_string());
+    }
+    Ok(())
+}
+
+fn validate_language_tag(tag: &str) -> Result<()> {
+    let valid = tag.is_empty()
+        || tag.split('-').all(|part| {
+            (1..=8).contains(&part.len()) && part.bytes().all(|byte| byte.is_ascii_alphanumeric())
+        });
+    if !valid {
+        return invalid(format!(
+            "'{}' is not a valid language tag (like 'en' or 'pl-PL')",
+            tag
+        ));
+    }
+    Ok(())
+}
+
+fn check_type(chunk: &Chunk, expected: ChunkType) -> Result<()> {
+    if *chunk.chunk_type() != expected {
+        return invalid(format!(
+            "expected {} chunk, but got {}",
+            expected,
+            chunk.chunk_type()
+        ));
+    }
+    Ok(())
+}
+
+/// Split data at first null byte, returning bytes before and after it
+fn split_null<'a>(data: &'a [u8], name: &str) -> Result<(&'a [u8], &'a [u8])> {
+    match data.iter().position(|&byte| byte == 0) {
+        Some(index) => Ok((&data[..index], &data[index + 1..])),
+        None => invalid(format!("missing null separator after {}", name)),
+    }
+}
+
+fn read_keyword(data: &[u8]) -> Result<(String, &[u8])> {
+    let (keyword, rest) = split_null(data, "keyword")?;
+    let keyword = latin1_string(keyword);
+    validate_keyword(&keyword)?;
+    Ok((keyword, rest))
+}
+
+fn check_compression_method(method: u8) -> Result<()> {
+    if method != 0 {
+        return invalid(format!("unknown compression method {}", method));
+    }
+    Ok(())
+}
+
+/// Keyword of tEXt, zTXt or iTXt chunk, read without decompressing its text
+pub(crate) fn keyword_of(chunk: &Chunk) -> Option<String> {
+    if ![ChunkType::TEXT, ChunkType::ZTXT, ChunkType::ITXT].contains(chunk.chunk_type()) {
+        return None;
+    }
+    read_keyword(chunk.data()).ok().map(|(keyword, _)| keyword)
+}
+
+impl TextChunk {
+    pub fn new(keyword: &str, text: &str) -> Result<TextChunk> {
+        let text = TextChunk {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        };
+        text.validate()?;
+        Ok(text)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        validate_keyword(&self.keyword)?;
+        if latin1_bytes(&self.text, "text")?.contains(&0) {
+            return invalid("text cannot contain null character".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        self.validate()?;
+        let mut data = latin1_bytes(&self.keyword, "keyword")?;
+        data.push(0);
+        data.extend(latin1_bytes(&self.text, "text")?);
+
+        Ok(Chunk::new(ChunkType::TEXT, data))
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_type(chunk, ChunkType::TEXT)?;
+        let (keyword, text) = read_keyword(chunk.data())?;
+
+        Ok(TextChunk {
+            keyword,
+            text: latin1_string(text),
+        })
+    }
+}
+
+impl CompressedTextChunk {
+    pub fn new(keyword: &str, text: &str) -> Result<CompressedTextChunk> {
+        let text = CompressedTextChunk {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        };
+        text.validate()?;
+        Ok(text)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        validate_keyword(&self.keyword)?;
+        latin1_bytes(&self.text, "text")?;
+        Ok(())
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        self.validate()?;
+        let mut data = latin1_bytes(&self.keyword, "keyword")?;
+        data.extend([0, 0]);
+        data.extend(deflate(
+            &latin1_bytes(&self.text, "text")?,
+            COMPRESSION_LEVEL,
+        ));
+
+        Ok(Chunk::new(ChunkType::ZTXT, data))
+    }
+
+    /// Parse chunk, decompressing at most `max_decompressed_size` bytes of text
+    pub fn from_chunk(chunk: &Chunk, max_decompressed_size: u64) -> Result<Self> {
+        check_type(chunk, ChunkType::ZTXT)?;
+        let (keyword, rest) = read_keyword(chunk.data())?;
+        let (&method, compressed) = rest
+            .split_first()
+            .ok_or_else(|| Error::InvalidText("missing compression method".to_string()))?;
+        check_compression_method(method)?;
+
+        Ok(CompressedTextChunk {
+            keyword,
+            text: latin1_string(&inflate(compressed, max_decompressed_size)?),
+        })
+    }
+}
+
+impl TryFrom<&Chunk> for CompressedTextChunk {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        Self::from_chunk(chunk, ParseOptions::default().max_decompressed_size)
+    }
+}
+
+impl InternationalTextChunk {
+    /// Create uncompressed iTXt chunk without language information
+    pub fn new(keyword: &str, text: &str) -> Result<InternationalTextChunk> {
+        let text = InternationalTextChunk {
+            keyword: keyword.to_string(),
+            compressed: false,
+            language_tag: String::new(),
+            translated_keyword: String::new(),
+            text: text.to_string(),
+        };
+        text.validate()?;
+        Ok(text)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        validate_keyword(&self.keyword)?;
+        validate_language_tag(&self.language_tag)?;
+        if self.translated_keyword.contains('\0') {
+            return invalid("translated keyword cannot contain null character".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        self.validate()?;
+        let mut data = latin1_bytes(&self.keyword, "keyword")?;
+        data.extend([0, self.compressed as u8, 0]);
+        data.extend(self.language_tag.as_bytes());
+        data.push(0);
+        data.extend(self.translated_keyword.as_bytes());
+        data.push(0);
+        if self.compressed {
+            data.extend(deflate(self.text.as_bytes(), COMPRESSION_LEVEL));
+        } else {
+            data.extend(self.text.as_bytes());
+        }
+
+        Ok(Chunk::new(ChunkType::ITXT, data))
+    }
+
+    /// Parse chunk, decompressing at most `max_decompressed_size` bytes of text
+    pub fn from_chunk(chunk: &Chunk, max_decompressed_size: u64) -> Result<Self> {
+        check_type(chunk, ChunkType::ITXT)?;
+        let (keyword, rest) = read_keyword(chunk.data())?;
+        let (flag, method, rest) = match rest {
+            [flag, method, rest @ ..] => (*flag, *method, rest),
+            _ => return invalid("missing compression flag and method".to_string()),
+        };
+        let compressed = match flag {
+            0 => false,
+            1 => true,
+            _ => return invalid(format!("unknown compression flag {}", flag)),
+        };
+        if compressed {
+            check_compression_method(method)?;
+        }
+        let (language_tag, rest) = split_null(rest, "language tag")?;
+        let (translated_keyword, text) = split_null(rest, "translated keyword")?;
+
+        let language_tag = utf8_string(language_tag.to_vec(), "language tag")?;
+        validate_language_tag(&language_tag)?;
+        let text = if compressed {
+            inflate(text, max_decompressed_size)?
+        } else {
+            text.to_vec()
+        };
+
+        Ok(InternationalTextChunk {
+            keyword,
+            compressed,
+            language_tag,
+            translated_keyword: utf8_string(translated_keyword.to_vec(), "translated keyword")?,
+            text: utf8_string(text, "text")?,
+        })
+    }
+}
+
+impl TryFrom<&Chunk> for InternationalTextChunk {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        Self::from_chunk(chunk, ParseOptions::default().max_decompressed_size)
+    }
+}
+
+impl TextMetadata {
+    /// Parse text chunk of any kind, returns `None` if chunk is not a text chunk
+    pub fn from_chunk(chunk: &Chunk, options: &ParseOptions) -> Result<Option<TextMetadata>> {
+        let max = options.max_decompressed_size;
+        let text = match *chunk.chunk_type() {
+            ChunkType::TEXT => TextMetadata::Text(TextChunk::try_from(chunk)?),
+            ChunkType::ZTXT => {
+                TextMetadata::Compressed(CompressedTextChunk::from_chunk(chunk, max)?)
+            }
+            ChunkType::ITXT => {
+                TextMetadata::International(InternationalTextChunk::from_chunk(chunk, max)?)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(text))
+    }
+
+    pub fn keyword(&self) -> &str {
+        match self {
+            TextMetadata::Text(text) => &text.keyword,
+            TextMetadata::Compressed(text) => &text.keyword,
+            TextMetadata::International(text) => &text.keyword,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            TextMetadata::Text(text) => &text.text,
+            TextMetadata::Compressed(text) => &text.text,
+            TextMetadata::International(text) => &text.text,
+        }
+    }
+
+    pub fn set_text(&mut self, new_text: String) {
+        match self {
+            TextMetadata::Text(text) => text.text = new_text,
+            TextMetadata::Compressed(text) => text.text = new_text,
+            TextMetadata::International(text) => text.text = new_text,
+        }
+    }
+
+    pub fn chunk_type(&self) -> ChunkType {
+        match self {
+            TextMetadata::Text(_) => ChunkType::TEXT,
+            TextMetadata::Compressed(_) => ChunkType::ZTXT,
+            TextMetadata::International(_) => ChunkType::ITXT,
+        }
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        match self {
+            TextMetadata::Text(text) => text.to_chunk(),
+            TextMetadata::Compressed(text) => text.to_chunk(),
+            TextMetadata::International(text) => text.to_chunk(),
+        }
+    }
+}
+
+impl fmt::Display for TextMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} '{}'", self.chunk_type(), self.keyword())?;
+        if let TextMetadata::International(text) = self {
+            let mut details = Vec::new();
+            if !text.language_tag.is_empty() {
+                details.push(format!("language '{}'", text.language_tag));
+            }
+            if !text.translated_keyword.is_empty() {
+                details.push(format!("translated '{}'", text.translated_keyword));
+            }
+            if text.compressed {
+                details.push("compressed".to_string());
+            }
+            if !details.is_empty() {
+                write!(f, " ({})", details.join(", "))?;
+            }
+        }
+        write!(f, ": '{}'", self.text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyword_validation() {
+        assert!(validate_keyword("Title").is_ok());
+        assert!(validate_keyword("Za\u{17c}\u{f3}\u{142}\u{107}").is_err());
+        assert!(validate_keyword("Caf\u{e9} menu").is_ok());
+        assert!(validate_keyword("").is_err());
+        assert!(validate_keyword(&"a".repeat(79)).is_ok());
+        assert!(validate_keyword(&"a".repeat(80)).is_err());
+        assert!(validate_keyword(" Title").is_err());
+        assert!(validate_keyword("Two  spaces").is_err());
+        assert!(validate_keyword("Tab\there").is_err());
+    }
+
+    #[test]
+    fn test_text_chunk() {
+        let text = TextChunk::new("Comment", "na\u{ef}ve caf\u{e9}").unwrap();
+        let chunk = text.to_chunk().unwrap();
+
+        assert_eq!(chunk.chunk_type(), &ChunkType::TEXT);
+        assert_eq!(&chunk.data()[..8], b"Comment\0");
+        assert_eq!(chunk.data().len(), 8 + 10);
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+
+        assert!(TextChunk::new("Comment", "\u{1f980}").is_err());
+        assert!(TextChunk::try_from(&Chunk::new(ChunkType::TEXT, b"no null".to_vec())).is_err());
+    }
+
+    #[test]
+    fn test_compressed_text_chunk() {
+        let text = CompressedTextChunk::new("Description", &"long text ".repeat(50)).unwrap();
+        let chunk = text.to_chunk().unwrap();
+
+        assert!(chunk.data().len() < 100);
+        assert_eq!(CompressedTextChunk::try_from(&chunk).unwrap(), text);
+        assert!(matches!(
+            CompressedTextChunk::from_chunk(&chunk, 100),
+            Err(Error::LimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_international_text_chunk() {
+        let mut text = InternationalTextChunk::new("Title", "\u{17c}\u{f3}\u{142}w").unwrap();
+        text.language_tag = "pl-PL".to_string();
+        text.translated_keyword = "Tytu\u{142}".to_string();
+
+        for compressed in [false, true] {
+            text.compressed = compressed;
+            let chunk = text.to_chunk().unwrap();
+            assert_eq!(InternationalTextChunk::try_from(&chunk).unwrap(), text);
+        }
+
+        text.language_tag = "not a tag".to_string();
+        assert!(text.to_chunk().is_err());
+    }
+
+    #[test]
+    fn test_text_metadata() {
+        let chunk = InternationalTextChunk::new("Author", "me")
+            .unwrap()
+            .to_chunk()
+            .unwrap();
+        let mut text = TextMetadata::from_chunk(&chunk, &ParseOptions::default())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(keyword_of(&chunk).unwrap(), "Author");
+        assert_eq!(text.to_string(), "iTXt 'Author': 'me'");
+        text.set_text("you".to_string());
+        assert_eq!(text.text(), "you");
+        assert!(TextMetadata::from_chunk(
+            &Chunk::from_strings("ruSt", "x").unwrap(),
+            &ParseOptions::default()
+        )
+        .unwrap()
+        .is_none());
+    }
+}
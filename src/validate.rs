@@ -0,0 +1,498 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::chunk_type::ChunkType;
+use crate::error::Location;
+use crate::ihdr::{ColorType, Ihdr};
+use crate::png::{Ordering, Png};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Single violation of png specification found by [`Png::validate`]
+/// or problem with input found by [`Png::parse_lenient`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Short identifier of broken rule, stable for machine processing
+    pub code: &'static str,
+    /// Chunk which breaks the rule, `None` for problems of png as a whole
+    pub location: Option<Location>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}]", self.severity, self.code)?;
+        if let Some(location) = self.location {
+            write!(f, " chunk {} at offset {}", location.index, location.offset)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Ancillary chunks which may appear at most once
+const UNIQUE_CHUNKS: [&[u8; 4]; 13] = [
+    b"cHRM", b"cICP", b"gAMA", b"iCCP", b"mDCV", b"cLLI", b"sBIT", b"sRGB", b"bKGD", b"hIST",
+    b"tRNS", b"pHYs", b"tIME",
+];
+
+/// Chunk types defined by png specification (and its extensions registered as public)
+pub(crate) const KNOWN_CHUNKS: [&[u8; 4]; 22] = [
+    b"IHDR", b"PLTE", b"IDAT", b"IEND", b"cHRM", b"cICP", b"gAMA", b"iCCP", b"mDCV", b"cLLI",
+    b"sBIT", b"sRGB", b"bKGD", b"hIST", b"tRNS", b"pHYs", b"sPLT", b"eXIf", b"tIME", b"tEXt",
+    b"zTXt", b"iTXt",
+];
+
+struct Validator<'a> {
+    png: &'a Png,
+    locations: Vec<Location>,
+    issues: Vec<Issue>,
+}
+
+impl Validator<'_> {
+    fn report(
+        &mut self,
+        severity: Severity,
+        code: &'static str,
+        index: Option<usize>,
+        message: String,
+    ) {
+        self.issues.push(Issue {
+            severity,
+            code,
+            location: index.map(|index| self.locations[index]),
+            message,
+        });
+    }
+
+    fn positions(&self, chunk_type: &ChunkType) -> Vec<usize> {
+        self.png
+            .chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.chunk_type() == chunk_type)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn check_header(&mut self) -> Option<Ihdr> {
+        let ihdrs = self.positions(&ChunkType::IHDR);
+        for &index in ihdrs.iter().skip(1) {
+            self.report(
+                Severity::Error,
+                "duplicate-ihdr",
+                Some(index),
+                "png must contain only one IHDR".to_string(),
+            );
+        }
+
+        match ihdrs.first() {
+            Some(0) => match Ihdr::try_from(&self.png.chunks()[0]) {
+                Ok(ihdr) => Some(ihdr),
+                Err(err) => {
+                    self.report(Severity::Error, "invalid-ihdr", Some(0), err.to_string());
+                    None
+                }
+            },
+            Some(&index) => {
+                self.report(
+                    Severity::Error,
+                    "misplaced-ihdr",
+                    Some(index),
+                    "IHDR must be the first chunk".to_string(),
+                );
+                None
+            }
+            None => {
+                self.report(
+                    Severity::Error,
+                    "missing-ihdr",
+                    None,
+                    "png must start with IHDR chunk".to_string(),
+                );
+                None
+            }
+        }
+    }
+
+    fn check_end(&mut self) {
+        let chunks_len = self.png.chunks().len();
+        let iends = self.positions(&ChunkType::IEND);
+
+        match iends.first() {
+            None => self.report(
+                Severity::Error,
+                "missing-iend",
+                None,
+                "png must end with IEND chunk".to_string(),
+            ),
+            Some(&first) => {
+                for &index in iends.iter().skip(1) {
+                    self.report(
+                        Severity::Error,
+                        "duplicate-iend",
+                        Some(index),
+                        "png must contain only one IEND".to_string(),
+                    );
+                }
+                for index in first + 1..chunks_len {
+                    if *self.png.chunks()[index].chunk_type() != ChunkType::IEND {
+                        self.report(
+                            Severity::Error,
+                            "chunk-after-iend",
+                            Some(index),
+                            format!(
+                                "chunk {} is placed after IEND",
+                                self.png.chunks()[index].chunk_type()
+                            ),
+                        );
+                    }
+                }
+                for &index in &iends {
+                    if self.png.chunks()[index].length() != 0 {
+                        self.report(
+                            Severity::Error,
+                            "iend-not-empty",
+                            Some(index),
+                            "IEND must not contain any data".to_string(),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_image_data(&mut self) {
+        let idats = self.positions(&ChunkType::IDAT);
+
+        if idats.is_empty() {
+            self.report(
+                Severity::Error,
+                "missing-idat",
+                None,
+                "png must contain at least one IDAT chunk".to_string(),
+            );
+        }
+        for pair in idats.windows(2) {
+            if pair[1] != pair[0] + 1 {
+                self.report(
+                    Severity::Error,
+                    "non-contiguous-idat",
+                    Some(pair[1]),
+                    "IDAT chunks must be consecutive".to_string(),
+                );
+            }
+        }
+    }
+
+    fn check_palette(&mut self, ihdr: Option<Ihdr>) {
+        let pltes = self.positions(&ChunkType::PLTE);
+        let first_idat = self.positions(&ChunkType::IDAT).first().copied();
+
+        for &index in pltes.iter().skip(1) {
+            self.report(
+                Severity::Error,
+                "duplicate-plte",
+                Some(index),
+                "png must contain only one PLTE".to_string(),
+            );
+        }
+        if let Some(&plte) = pltes.first() {
+            if first_idat.is_some_and(|idat| plte > idat) {
+                self.report(
+                    Severity::Error,
+                    "plte-after-idat",
+                    Some(plte),
+                    "PLTE must come before IDAT".to_string(),
+                );
+            }
+
+            let len = self.png.chunks()[plte].data().len();
+            let max_entries = match ihdr {
+                Some(ihdr) if ihdr.color_type == ColorType::Indexed => 1 << ihdr.bit_depth,
+                _ => 256,
+            };
+            if len == 0 || !len.is_multiple_of(3) || len / 3 > max_entries {
+                self.report(
+                    Severity::Error,
+                    "invalid-plte-length",
+                    Some(plte),
+                    format!(
+                        "PLTE must contain from 1 to {} three byte entries, but it has {} bytes",
+                        max_entries, len
+                    ),
+                );
+            }
+        }
+
+        match ihdr.map(|ihdr| ihdr.color_type) {
+            Some(ColorType::Indexed) if pltes.is_empty() => self.report(
+                Severity::Error,
+                "missing-plte",
+                None,
+                "indexed color image must contain PLTE".to_string(),
+            ),
+            Some(ColorType::Grayscale | ColorType::GrayscaleAlpha) if !pltes.is_empty() => self
+                .report(
+                    Severity::Error,
+                    "plte-not-allowed",
+                    Some(pltes[0]),
+                    "grayscale image must not contain PLTE".to_string(),
+                ),
+            _ => {}
+        }
+
+        if let Some(ihdr) = ihdr {
+            if ihdr.color_type.has_alpha() {
+                for index in self.positions(&ChunkType::TRNS) {
+                    self.report(
+                        Severity::Error,
+                        "trns-not-allowed",
+                        Some(index),
+                        "image with alpha channel must not contain tRNS".to_string(),
+                    );
+                }
+            }
+        }
+        if pltes.is_empty() {
+            for index in self.positions(&ChunkType::HIST) {
+                self.report(
+                    Severity::Error,
+                    "hist-without-plte",
+                    Some(index),
+                    "hIST requires PLTE".to_string(),
+                );
+            }
+        }
+    }
+
+    fn check_chunk_types(&mut self) {
+        let plte = self.positions(&ChunkType::PLTE).first().copied();
+        let idat = self.positions(&ChunkType::IDAT).first().copied();
+        let mut seen = HashSet::new();
+
+        for index in 0..self.png.chunks().len() {
+            let chunk_type = *self.png.chunks()[index].chunk_type();
+            let bytes = chunk_type.bytes();
+
+            if !chunk_type.is_reserved_bit_valid() {
+                self.report(
+                    Severity::Error,
+                    "reserved-bit",
+                    Some(index),
+                    format!("chunk type {} has reserved bit set", chunk_type),
+                );
+            }
+            if !KNOWN_CHUNKS.contains(&&bytes) {
+                let (severity, kind) = if chunk_type.is_critical() {
+                    (Severity::Error, "critical")
+                } else {
+                    (Severity::Warning, "ancillary")
+                };
+                self.report(
+                    severity,
+                    "unknown-chunk",
+                    Some(index),
+                    format!("unknown {} chunk {}", kind, chunk_type),
+                );
+            }
+            if UNIQUE_CHUNKS.contains(&&bytes) && !seen.insert(bytes) {
+                self.report(
+                    Severity::Error,
+                    "duplicate-chunk",
+                    Some(index),
+                    format!("png must contain only one {}", chunk_type),
+                );
+            }
+
+            let before_idat = idat.is_none_or(|idat| index < idat);
+            let misplaced = match Ordering::of(&chunk_type) {
+                Ordering::BeforePlte if !before_idat || plte.is_some_and(|plte| index > plte) => {
+                    Some("before PLTE and IDAT")
+                }
+                Ordering::AfterPlteBeforeIdat if plte.is_some_and(|plte| index < plte) => {
+                    Some("after PLTE")
+                }
+                Ordering::AfterPlteBeforeIdat | Ordering::BeforeIdat if !before_idat => {
+                    Some("before IDAT")
+                }
+                _ => None,
+            };
+            if let Some(rule) = misplaced {
+                self.report(
+                    Severity::Error,
+                    "misplaced-chunk",
+                    Some(index),
+                    format!("{} must come {}", chunk_type, rule),
+                );
+            }
+        }
+
+        if !self.positions(&ChunkType::ICCP).is_empty()
+            && !self.positions(&ChunkType::SRGB).is_empty()
+        {
+            self.report(
+                Severity::Warning,
+                "iccp-and-srgb",
+                None,
+                "png should not contain both iCCP and sRGB".to_string(),
+            );
+        }
+    }
+}
+
+impl Png {
+    /// Location of every chunk when png is serialized
+    pub fn chunk_locations(&self) -> Vec<Location> {
+        let mut offset = Png::STANDARD_HEADER.len();
+        self.chunks()
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let location = Location { index, offset };
+                offset += chunk.bytes_len();
+                location
+            })
+            .collect()
+    }
+
+    /// Check png structure against png specification, returning every found violation
+    /// (ordered by chunk index, problems of png as a whole come first)
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut validator = Validator {
+            png: self,
+            locations: self.chunk_locations(),
+            issues: Vec::new(),
+        };
+
+        let ihdr = validator.check_header();
+        validator.check_end();
+        validator.check_image_data();
+        validator.check_palette(ihdr);
+        validator.check_chunk_types();
+
+        let mut issues = validator.issues;
+        issues.sort_by_key(|issue| issue.location.map(|location| location.index));
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::Chunk;
+
+    use super::*;
+
+    fn ihdr(color_type: ColorType) -> Chunk {
+        Ihdr::new(2, 2, 8, color_type).unwrap().to_chunk()
+    }
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::from_strings(chunk_type, data).unwrap()
+    }
+
+    fn codes(png: &Png) -> Vec<&'static str> {
+        png.validate().iter().map(|issue| issue.code).collect()
+    }
+
+    #[test]
+    fn test_valid_png() {
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Rgb),
+            chunk("gAMA", "abcd"),
+            chunk("IDAT", "data"),
+            chunk("IDAT", "more"),
+            chunk("tEXt", "text"),
+            chunk("IEND", ""),
+        ]);
+        assert!(png.validate().is_empty());
+    }
+
+    #[test]
+    fn test_structure_errors() {
+        let png = Png::from_chunks(vec![
+            chunk("IDAT", "data"),
+            chunk("IEND", ""),
+            chunk("ruSt", "hidden"),
+            chunk("IEND", ""),
+        ]);
+        assert_eq!(
+            codes(&png),
+            [
+                "missing-ihdr",
+                "chunk-after-iend",
+                "unknown-chunk",
+                "duplicate-iend"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_issue_location() {
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Rgb),
+            chunk("IDAT", "data"),
+            chunk("tEXt", "text"),
+            chunk("IDAT", "more"),
+            chunk("IEND", ""),
+        ]);
+        let issues = png.validate();
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].code, "non-contiguous-idat");
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert_eq!(
+            issues[0].location,
+            Some(Location {
+                index: 3,
+                offset: 8 + 25 + 16 + 16
+            })
+        );
+    }
+
+    #[test]
+    fn test_palette_errors() {
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Indexed),
+            chunk("IDAT", "data"),
+            chunk("PLTE", "abcd"),
+            chunk("IEND", ""),
+        ]);
+        assert_eq!(codes(&png), ["plte-after-idat", "invalid-plte-length"]);
+
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Indexed),
+            chunk("IDAT", "data"),
+            chunk("IEND", ""),
+        ]);
+        assert_eq!(codes(&png), ["missing-plte"]);
+    }
+
+    #[test]
+    fn test_ordering_errors() {
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Rgba),
+            chunk("IDAT", "data"),
+            chunk("gAMA", "abcd"),
+            chunk("tRNS", "ab"),
+            chunk("IEND", ""),
+        ]);
+        assert_eq!(
+            codes(&png),
+            ["misplaced-chunk", "trns-not-allowed", "misplaced-chunk"]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Decompress, FlushDecompress, Status};
+
+use crate::error::Limit;
+use crate::{Error, Result};
+
+/// Compress data into zlib stream with given level (0-9)
+pub(crate) fn deflate(data: &[u8], level: u32) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level.min(9)));
+    // writing into vec cannot fail
+    encoder.write_all(data).expect("write into vec");
+    encoder.finish().expect("write into vec")
+}
+
+/// Decompress zlib stream, failing once more than `max_size` bytes would be produced,
+/// so small hostile input cannot exhaust memory
+pub(crate) fn inflate(data: &[u8], max_size: u64) -> Result<Vec<u8>> {
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(data)
+        .take(max_size.saturating_add(1))
+        .read_to_end(&mut inflated)
+        .map_err(|err| Error::InvalidCompressedData(err.to_string()))?;
+
+    if inflated.len() as u64 > max_size {
+        return Err(Error::LimitExceeded {
+            limit: Limit::DecompressedSize,
+            value: inflated.len() as u64,
+            max: max_size,
+            location: None,
+        });
+    }
+    Ok(inflated)
+}
+
+/// Decompress at most `len` bytes from the beginning of zlib stream, ignoring the rest of it
+pub(crate) fn inflate_prefix(data: &[u8], len: u64) -> Result<Vec<u8>> {
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(data)
+        .take(len)
+        .read_to_end(&mut inflated)
+        .map_err(|err| Error::InvalidCompressedData(err.to_string()))?;
+    Ok(inflated)
+}
+
+/// Number of bytes taken by zlib stream at the beginning of data, decompressing (and throwing
+/// away) at most `max_size` bytes. `None` if stream is invalid, unfinished or too large
+pub(crate) fn stream_len(data: &[u8], max_size: u64) -> Option<usize> {
+    let mut decompress = Decompress::new(true);
+    let mut output = vec![0; 32 * 1024];
+    loop {
+        let consumed = decompress.total_in() as usize;
+        let produced = decompress.total_out();
+        let status = decompress
+            .decompress(&data[consumed..], &mut output, FlushDecompress::None)
+            .ok()?;
+        if status == Status::StreamEnd {
+            return Some(decompress.total_in() as usize);
+        }
+        let stuck =
+            decompress.total_in() as usize == consumed && decompress.total_out() == produced;
+        if stuck || decompress.total_out() > max_size {
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let data = b"some text, some text, some text".repeat(10);
+        let compressed = deflate(&data, 9);
+
+        assert!(compressed.len() < data.len());
+        assert_eq!(inflate(&compressed, 1024).unwrap(), data);
+    }
+
+    #[test]
+    fn test_inflate_limit() {
+        let compressed = deflate(&[0; 4096], 9);
+
+        assert!(matches!(
+            inflate(&compressed, 4095),
+            Err(Error::LimitExceeded {
+                limit: Limit::DecompressedSize,
+                ..
+            })
+        ));
+        assert!(inflate(&compressed, 4096).is_ok());
+        assert_eq!(inflate_prefix(&compressed, 10).unwrap(), [0; 10]);
+    }
+
+    #[test]
+    fn test_stream_len() {
+        let compressed = deflate(&[7; 100_000], 6);
+        let mut data = compressed.clone();
+        data.extend_from_slice(b"trailer");
+
+        assert_eq!(stream_len(&data, 1 << 20), Some(compressed.len()));
+        assert_eq!(stream_len(&data, 1000), None);
+        assert_eq!(
+            stream_len(&compressed[..compressed.len() - 1], 1 << 20),
+            None
+        );
+    }
+
+    #[test]
+    fn test_invalid_stream() {
+        assert!(matches!(
+            inflate(b"not zlib", 1024),
+            Err(Error::InvalidCompressedData(_))
+        ));
+    }
+}
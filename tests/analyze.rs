@@ -0,0 +1,96 @@
+use predicates::prelude::predicate;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+use crate::common::{
+    analyze_command, encode_command, temp_copy, VALID_CHUNK_TYPE, VALID_ENCODED1, VALID_FILE,
+};
+
+mod common;
+
+/// Overall score printed in the last line of text report
+fn printed_score(stdout: &[u8]) -> u32 {
+    let stdout = String::from_utf8_lossy(stdout);
+    let last_line = stdout.lines().last().unwrap();
+    last_line
+        .rsplit(' ')
+        .next()
+        .unwrap()
+        .trim_end_matches('%')
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn analyze_clean_file() {
+    let output = analyze_command()
+        .arg(VALID_FILE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[chi-square]"))
+        .stdout(predicate::str::contains("[rs-analysis]"))
+        .get_output()
+        .stdout
+        .clone();
+
+    assert!(printed_score(&output) < 20);
+}
+
+#[test]
+fn analyze_chunks_after_iend() {
+    let output = analyze_command()
+        .arg(VALID_ENCODED1)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "[unknown-private-chunk] chunk 7 at offset 4803: private ancillary chunk ruSt",
+        ))
+        .stdout(predicate::str::contains(
+            "[after-iend] chunk 6 at offset 4791: 30 bytes (1 chunks) follow IEND",
+        ))
+        .get_output()
+        .stdout
+        .clone();
+
+    assert!(printed_score(&output) > 90);
+}
+
+#[test]
+fn analyze_lsb_embedding() {
+    let path = temp_copy(VALID_FILE);
+    let message: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(900)
+        .map(char::from)
+        .collect();
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, &message, "-m", "lsb"])
+        .assert()
+        .success();
+
+    let output = analyze_command()
+        .arg(&path)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert!(printed_score(&output) > 30);
+}
+
+#[test]
+fn analyze_json() {
+    analyze_command()
+        .arg(VALID_ENCODED1)
+        .args(["--format", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "{\"file\":\"tests/inputs/valid_encoded1.png\",\"score\":",
+        ))
+        .stdout(predicate::str::contains(
+            "{\"code\":\"after-iend\",\"score\":0.9000,\"index\":6,\"offset\":4791,",
+        ));
+}
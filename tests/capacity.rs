@@ -0,0 +1,64 @@
+use predicates::prelude::{predicate, PredicateBooleanExt};
+
+use crate::common::{capacity_command, decode_command, encode_command, temp_copy, VALID_FILE};
+
+mod common;
+
+#[test]
+fn capacity_per_method() {
+    capacity_command()
+        .arg(VALID_FILE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "chunk: 2147483622 bytes (with 37 bytes of framing), file grows by 2147483659 bytes",
+        ))
+        .stdout(predicate::str::contains(
+            "lsb (1 bit per sample): 900 bytes (with 37 bytes of framing)",
+        ))
+        .stdout(predicate::str::contains(
+            "lsb (8 bits per sample): 7463 bytes",
+        ))
+        .stdout(predicate::str::contains("matrix (code 3): 364 bytes"))
+        .stdout(predicate::str::is_match("idat tail: .* \\(not supported by encode\\)").unwrap())
+        .stdout(predicate::str::contains("palette").not());
+}
+
+#[test]
+fn reported_capacity_fits() {
+    let path = temp_copy(VALID_FILE);
+    let message = "x".repeat(900);
+    encode_command()
+        .arg(&path)
+        .args(["ruSt", &message, "-m", "lsb"])
+        .assert()
+        .success();
+
+    decode_command()
+        .arg(&path)
+        .args(["ruSt", "-m", "lsb"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(message));
+}
+
+#[test]
+fn capacity_json() {
+    capacity_command()
+        .arg(VALID_FILE)
+        .args(["--channels", "rgba", "--code", "2", "-f", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "{\"file\":\"tests/inputs/valid.png\",\"methods\":[{\"method\":\"chunk\",",
+        ))
+        .stdout(predicate::str::contains(
+            "{\"method\":\"lsb\",\"bits\":1,\"payload\":1213,\"overhead\":37,\"growth\":",
+        ))
+        .stdout(
+            predicate::str::is_match("\"method\":\"idat-tail\",[^}]*\"supported\":false").unwrap(),
+        )
+        .stdout(predicate::str::contains(
+            "{\"method\":\"matrix\",\"code\":2,\"payload\":796,",
+        ));
+}
@@ -1,4 +1,7 @@
-use std::{fs};
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::{env, fs};
 
 use assert_cmd::Command;
 use rand::distributions::Alphanumeric;
@@ -59,6 +62,34 @@ pub fn gen_not_existing_file() -> String {
     }
 }
 
+/// Copy given file into temporary directory, so tests can modify it
+pub fn temp_copy(filename: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("{}.png", gen_not_existing_file()));
+    fs::copy(filename, &path).unwrap();
+
+    path
+}
+
+/// Copy given file into temporary directory, flipping bits of byte at given offset from its end
+pub fn corrupted_copy(filename: &str, offset_from_end: usize) -> PathBuf {
+    let path = temp_copy(filename);
+    let mut bytes = fs::read(&path).unwrap();
+    let len = bytes.len();
+    bytes[len - 1 - offset_from_end] ^= 0xff;
+    fs::write(&path, bytes).unwrap();
+
+    path
+}
+
+/// Copy given file into temporary directory without its last `cut` bytes
+pub fn truncated_copy(filename: &str, cut: usize) -> PathBuf {
+    let path = temp_copy(filename);
+    let bytes = fs::read(&path).unwrap();
+    fs::write(&path, &bytes[..bytes.len() - cut]).unwrap();
+
+    path
+}
+
 pub fn encode_command() -> Command {
     let mut command = command();
     command.arg("encode");
@@ -71,6 +102,54 @@ pub fn decode_command() -> Command {
 
     command
 }
+pub fn print_command() -> Command {
+    let mut command = command();
+    command.arg("print");
+
+    command
+}
+pub fn validate_command() -> Command {
+    let mut command = command();
+    command.arg("validate");
+
+    command
+}
+pub fn repair_command() -> Command {
+    let mut command = command();
+    command.arg("repair");
+
+    command
+}
+pub fn analyze_command() -> Command {
+    let mut command = command();
+    command.arg("analyze");
+
+    command
+}
+pub fn capacity_command() -> Command {
+    let mut command = command();
+    command.arg("capacity");
+
+    command
+}
+pub fn keygen_command() -> Command {
+    let mut command = command();
+    command.arg("keygen");
+
+    command
+}
+pub fn verify_command() -> Command {
+    let mut command = command();
+    command.arg("verify");
+
+    command
+}
+pub fn text_command(subcommand: &str) -> Command {
+    let mut command = command();
+    command.arg("text").arg(subcommand);
+
+    command
+}
 pub fn remove_command() -> Command {
     let mut command = command();
 
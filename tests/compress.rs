@@ -0,0 +1,83 @@
+use std::fs;
+
+use predicates::prelude::predicate;
+
+use crate::common::{decode_command, encode_command, temp_copy, VALID_CHUNK_TYPE, VALID_FILE};
+
+mod common;
+
+fn log_message() -> String {
+    r#"{"level":"info","service":"pngme","message":"request handled"}"#.repeat(40)
+}
+
+#[test]
+fn compress_and_decode() {
+    let message = log_message();
+    for algorithm in ["deflate", "zstd"] {
+        let path = temp_copy(VALID_FILE);
+        let size = fs::metadata(&path).unwrap().len();
+        encode_command()
+            .arg(&path)
+            .args([VALID_CHUNK_TYPE, &message, "--compress", algorithm])
+            .assert()
+            .success();
+
+        let growth = fs::metadata(&path).unwrap().len() - size;
+        assert!(growth < message.len() as u64 / 10, "{}", growth);
+        decode_command()
+            .arg(&path)
+            .arg(VALID_CHUNK_TYPE)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!(
+                "secret message: '{}'",
+                message
+            )));
+    }
+}
+
+#[test]
+fn compress_encrypted_message() {
+    let path = temp_copy(VALID_FILE);
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, &log_message(), "--compress", "zstd"])
+        .args(["--encrypt", "--passphrase", "pw"])
+        .assert()
+        .success();
+
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "--decrypt", "--passphrase", "pw"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(log_message()));
+}
+
+#[test]
+fn decompression_limit() {
+    let path = temp_copy(VALID_FILE);
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, &log_message(), "--compress", "deflate"])
+        .assert()
+        .success();
+
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "--max-decompressed-size", "100"])
+        .assert()
+        .failure()
+        .code(13)
+        .stderr(predicate::str::contains("decompressed size"));
+}
+
+#[test]
+fn unknown_algorithm() {
+    encode_command()
+        .arg(temp_copy(VALID_FILE))
+        .args([VALID_CHUNK_TYPE, "message", "--compress", "lzma"])
+        .assert()
+        .failure()
+        .code(2);
+}
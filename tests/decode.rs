@@ -1,8 +1,8 @@
 use assert_cmd::assert::Assert;
 
 use crate::common::{
-    decode_command, DIFFERENT_MESSAGE, EMPTY_FILE, INVALID_HEADER_FILE, MESSAGE, SECOND_MESSAGE,
-    VALID_CHUNK_TYPE, VALID_DIFFERENT_CHUNK_TYPE, VALID_ENCODED1, VALID_ENCODED2,
+    corrupted_copy, decode_command, DIFFERENT_MESSAGE, EMPTY_FILE, INVALID_HEADER_FILE, MESSAGE,
+    SECOND_MESSAGE, VALID_CHUNK_TYPE, VALID_DIFFERENT_CHUNK_TYPE, VALID_ENCODED1, VALID_ENCODED2,
     VALID_ENCODED2_DIFFERENT, VALID_FILE,
 };
 
@@ -55,8 +55,9 @@ fn decode_valid2_different_last() {
 fn dies_decode_empty() {
     decode(EMPTY_FILE, VALID_CHUNK_TYPE)
         .failure()
+        .code(4)
         .stderr(predicates::str::contains(
-            "Invalid png file data (Valid png must have at least 8 bytes (missing header) but only 0 were provided",
+            "Valid png must have at least 8 bytes (missing header) but only 0 were provided",
         ));
 }
 
@@ -64,6 +65,7 @@ fn dies_decode_empty() {
 fn dies_decode_invalid() {
     decode(INVALID_HEADER_FILE, VALID_CHUNK_TYPE)
         .failure()
+        .code(5)
         .stderr(predicates::str::contains(
             "Valid png must contain valid signature header",
         ));
@@ -71,11 +73,42 @@ fn dies_decode_invalid() {
 
 #[test]
 fn decode_nothing_from_valid() {
-    let chunk_type = VALID_CHUNK_TYPE;
-    decode(VALID_FILE, chunk_type)
+    assert_no_message(VALID_FILE, VALID_CHUNK_TYPE);
+}
+
+#[test]
+fn dies_decode_above_limits() {
+    decode_command()
+        .arg(VALID_ENCODED1)
+        .arg(VALID_CHUNK_TYPE)
+        .arg("--max-chunk-length")
+        .arg("16")
+        .assert()
+        .failure()
+        .code(13)
+        .stderr(predicates::str::contains("chunk length"));
+}
+
+#[test]
+fn decode_lenient_crc_mismatch() {
+    // last byte of crc of the last chunk
+    let path = corrupted_copy(VALID_ENCODED2, 0);
+
+    decode_command()
+        .arg(&path)
+        .arg(VALID_CHUNK_TYPE)
+        .assert()
+        .failure()
+        .code(7);
+    decode_command()
+        .arg(&path)
+        .arg(VALID_CHUNK_TYPE)
+        .arg("--lenient")
+        .assert()
         .success()
         .stdout(predicates::str::contains(format!(
-            "No chunk with type '{}' was found",
-            chunk_type
-        )));
+            "secret message: '{}'",
+            SECOND_MESSAGE
+        )))
+        .stderr(predicates::str::contains("error [crc-mismatch]"));
 }
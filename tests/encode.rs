@@ -1,11 +1,13 @@
 use assert_cmd::assert::Assert;
 use predicates::prelude::predicate;
 
-use pngme::get_png;
+use std::fs;
+
+use pngme::Png;
 
 use crate::common::{
-    encode_command, gen_not_existing_file, EMPTY_FILE,
-    INVALID_CHUNK_TYPE, INVALID_HEADER_FILE, MESSAGE, OUTPUT_FILE, VALID_CHUNK_TYPE, VALID_FILE,
+    encode_command, gen_not_existing_file, EMPTY_FILE, INVALID_CHUNK_TYPE, INVALID_HEADER_FILE,
+    MESSAGE, OUTPUT_FILE, VALID_CHUNK_TYPE, VALID_FILE,
 };
 
 mod common;
@@ -20,29 +22,29 @@ fn encode(filename: &str, chunk_type: &str, message: &str) -> Assert {
         .assert()
 }
 
-fn has_last_message(message: &str) {
-    let png = get_png(OUTPUT_FILE).unwrap();
-    assert_eq!(
-        message,
-        png.chunks().last().unwrap().data_as_string().unwrap()
-    );
+fn has_message_before_iend(message: &str) {
+    let png = Png::from_file(OUTPUT_FILE).unwrap();
+    let chunks = png.chunks();
+    assert_eq!("IEND", chunks[chunks.len() - 1].chunk_type().to_string());
+    assert_eq!(message, chunks[chunks.len() - 2].data_as_string().unwrap());
 }
 
 fn encode_empty(filename: &str) {
     encode(filename, VALID_CHUNK_TYPE, "abc").success();
 
-    has_last_message("abc")
+    has_message_before_iend("abc")
 }
 fn encode_invalid(filename: &str) {
     encode(filename, INVALID_CHUNK_TYPE, MESSAGE)
         .failure()
+        .code(8)
         .stderr(predicates::str::contains("is not a valid png byte"));
 }
 
 fn encode_valid(filename: &str) {
     encode(filename, VALID_CHUNK_TYPE, MESSAGE).success();
 
-    has_last_message(MESSAGE)
+    has_message_before_iend(MESSAGE)
 }
 
 #[test]
@@ -71,9 +73,11 @@ fn dies_bad_file() {
     let bad_file = gen_not_existing_file();
     encode(&bad_file, VALID_CHUNK_TYPE, MESSAGE)
         .failure()
-        .stderr(predicate::str::contains(
-            "The system cannot find the file specified",
-        ));
+        .code(3)
+        .stderr(predicate::str::contains(format!(
+            "Cannot access file {}",
+            bad_file
+        )));
 }
 
 #[test]
@@ -107,3 +111,35 @@ fn dies_encode_valid_to_invalid_header() {
             "Valid png must contain valid signature header",
         ));
 }
+
+#[test]
+fn encode_before_idat() {
+    let output = std::env::temp_dir().join(gen_not_existing_file());
+    encode_command()
+        .arg(VALID_FILE)
+        .arg(VALID_CHUNK_TYPE)
+        .arg(MESSAGE)
+        .arg("-o")
+        .arg(&output)
+        .arg("--position")
+        .arg("before-idat")
+        .assert()
+        .success();
+
+    let png = Png::from_file(&output).unwrap();
+    fs::remove_file(&output).unwrap();
+    let index = png
+        .chunks()
+        .iter()
+        .position(|chunk| chunk.chunk_type().to_string() == "IDAT")
+        .unwrap();
+    assert_eq!(MESSAGE, png.chunks()[index - 1].data_as_string().unwrap());
+}
+
+#[test]
+fn dies_encode_critical_type() {
+    encode(VALID_FILE, "IEND", MESSAGE)
+        .failure()
+        .code(12)
+        .stderr(predicate::str::contains("Cannot place chunk IEND there"));
+}
@@ -0,0 +1,108 @@
+use pngme::{Chunk, Envelope, Png};
+use predicates::prelude::{predicate, PredicateBooleanExt};
+
+use crate::common::{
+    decode_command, encode_command, print_command, temp_copy, MESSAGE, VALID_CHUNK_TYPE, VALID_FILE,
+};
+
+mod common;
+
+const PASSPHRASE: &str = "correct horse battery staple";
+
+fn encrypted_copy() -> std::path::PathBuf {
+    let path = temp_copy(VALID_FILE);
+    encode_command()
+        .arg(&path)
+        .args([
+            VALID_CHUNK_TYPE,
+            MESSAGE,
+            "--encrypt",
+            "--passphrase",
+            PASSPHRASE,
+        ])
+        .assert()
+        .success();
+    path
+}
+
+#[test]
+fn encrypt_and_decrypt() {
+    let path = encrypted_copy();
+
+    print_command()
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(MESSAGE).not());
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "--decrypt", "--passphrase", PASSPHRASE])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "secret message: '{}'",
+            MESSAGE
+        )));
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "--decrypt"])
+        .env("PNGME_PASSPHRASE", PASSPHRASE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(MESSAGE));
+}
+
+#[test]
+fn decode_encrypted_without_decrypt() {
+    decode_command()
+        .arg(encrypted_copy())
+        .arg(VALID_CHUNK_TYPE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("use --decrypt"));
+}
+
+#[test]
+fn wrong_passphrase() {
+    decode_command()
+        .arg(encrypted_copy())
+        .args([VALID_CHUNK_TYPE, "--decrypt", "--passphrase", "wrong"])
+        .assert()
+        .failure()
+        .code(23)
+        .stderr(predicate::str::contains("Wrong passphrase"));
+}
+
+#[test]
+fn corrupted_ciphertext() {
+    let path = encrypted_copy();
+    // flip the last byte of authentication tag, keeping envelope checksum valid
+    let mut png = Png::from_file(&path).unwrap();
+    let index = png.chunks().len() - 2;
+    let chunk = &png.chunks()[index];
+    let (envelope, payload) = Envelope::open(chunk.data()).unwrap();
+    let mut payload = payload.to_vec();
+    *payload.last_mut().unwrap() ^= 1;
+    let tampered = Chunk::enveloped(*chunk.chunk_type(), &envelope, &payload).unwrap();
+    png.replace_chunk(index, tampered).unwrap();
+    png.write_file(&path).unwrap();
+
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "--decrypt", "--passphrase", PASSPHRASE])
+        .assert()
+        .failure()
+        .code(24)
+        .stderr(predicate::str::contains("corrupted"));
+}
+
+#[test]
+fn encrypt_requires_passphrase() {
+    encode_command()
+        .arg(temp_copy(VALID_FILE))
+        .args([VALID_CHUNK_TYPE, MESSAGE, "--encrypt"])
+        .env_remove("PNGME_PASSPHRASE")
+        .assert()
+        .failure()
+        .code(2);
+}
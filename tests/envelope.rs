@@ -0,0 +1,126 @@
+use std::env;
+use std::fs;
+
+use pngme::{Chunk, Png};
+use predicates::prelude::{predicate, PredicateBooleanExt};
+
+use crate::common::{
+    decode_command, encode_command, gen_not_existing_file, temp_copy, MESSAGE, VALID_CHUNK_TYPE,
+    VALID_ENCODED1, VALID_FILE,
+};
+
+mod common;
+
+#[test]
+fn decode_prints_envelope() {
+    let path = temp_copy(VALID_FILE);
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, MESSAGE, "--compress", "deflate"])
+        .assert()
+        .success();
+
+    decode_command()
+        .arg(&path)
+        .arg(VALID_CHUNK_TYPE)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                "envelope: format 1, text, created [0-9-]+ [0-9:]+ UTC, compressed\n",
+            )
+            .unwrap(),
+        )
+        .stdout(predicate::str::contains(format!(
+            "secret message: '{}'",
+            MESSAGE
+        )));
+}
+
+#[test]
+fn decode_legacy_message() {
+    decode_command()
+        .arg(VALID_ENCODED1)
+        .arg(VALID_CHUNK_TYPE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("envelope").not())
+        .stdout(predicate::str::contains(MESSAGE));
+}
+
+#[test]
+fn hide_file() {
+    let input = env::temp_dir().join(format!("{}.json", gen_not_existing_file()));
+    let contents = b"{\"binary\":\"\xff\x00\"}";
+    fs::write(&input, contents).unwrap();
+    let path = temp_copy(VALID_FILE);
+    encode_command()
+        .arg(&path)
+        .arg(VALID_CHUNK_TYPE)
+        .arg("--file")
+        .arg(&input)
+        .assert()
+        .success();
+
+    let filename = input.file_name().unwrap().to_str().unwrap();
+    decode_command()
+        .arg(&path)
+        .arg(VALID_CHUNK_TYPE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("file '{}'", filename)))
+        .stdout(predicate::str::contains("use --output-file"));
+
+    let output = env::temp_dir().join(gen_not_existing_file());
+    decode_command()
+        .arg(&path)
+        .arg(VALID_CHUNK_TYPE)
+        .arg("--output-file")
+        .arg(&output)
+        .assert()
+        .success();
+    assert_eq!(fs::read(&output).unwrap(), contents);
+}
+
+#[test]
+fn message_or_file_required() {
+    encode_command()
+        .arg(temp_copy(VALID_FILE))
+        .arg(VALID_CHUNK_TYPE)
+        .assert()
+        .failure()
+        .code(2);
+    encode_command()
+        .arg(temp_copy(VALID_FILE))
+        .args([VALID_CHUNK_TYPE, MESSAGE, "--file", VALID_FILE])
+        .assert()
+        .failure()
+        .code(2);
+}
+
+#[test]
+fn decode_legacy_message_with_magic() {
+    let signed_like = format!("PNGS{}", "s".repeat(120));
+    for message in [
+        "PNGME rocks",
+        "PNGE is not encrypted",
+        "PNGZ is not compressed",
+        &signed_like,
+    ] {
+        let path = temp_copy(VALID_FILE);
+        let mut png = Png::from_file(&path).unwrap();
+        png.append_chunk(Chunk::from_strings(VALID_CHUNK_TYPE, message).unwrap());
+        png.write_file(&path).unwrap();
+
+        decode_command()
+            .arg(&path)
+            .arg(VALID_CHUNK_TYPE)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("envelope").not())
+            .stdout(predicate::str::contains(format!(
+                "secret message: '{}'",
+                message
+            )));
+    }
+}
@@ -0,0 +1,164 @@
+use predicates::prelude::predicate;
+
+use pngme::Png;
+
+use crate::common::{
+    decode_command, encode_command, temp_copy, MESSAGE, VALID_CHUNK_TYPE, VALID_FILE,
+};
+
+mod common;
+
+#[test]
+fn encode_and_decode_lsb() {
+    let path = temp_copy(VALID_FILE);
+    let chunk_count = Png::from_file(&path).unwrap().chunks().len();
+
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, MESSAGE, "--method", "lsb"])
+        .assert()
+        .success();
+
+    let png = Png::from_file(&path).unwrap();
+    assert_eq!(png.chunks().len(), chunk_count);
+    assert!(png.get_chunk_by_type(VALID_CHUNK_TYPE).is_none());
+
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "-m", "lsb"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "secret message: '{}'",
+            MESSAGE
+        )));
+    decode_command()
+        .arg(&path)
+        .arg(VALID_CHUNK_TYPE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No chunk with type"));
+}
+
+#[test]
+fn lsb_options_must_match() {
+    let path = temp_copy(VALID_FILE);
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, MESSAGE, "-m", "lsb", "--channels", "ga"])
+        .args(["--bits", "2"])
+        .assert()
+        .success();
+
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "-m", "lsb", "--channels", "ga"])
+        .args(["--bits", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(MESSAGE));
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "-m", "lsb"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No chunk with type"));
+}
+
+#[test]
+fn lsb_capacity_exceeded() {
+    let path = temp_copy(VALID_FILE);
+    // 50x50 pixels with 3 channels and 1 bit hold 937 bytes, 12 of them chunk framing and 25
+    // message envelope
+    let message = "x".repeat(901);
+
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, &message, "-m", "lsb"])
+        .assert()
+        .failure()
+        .code(21)
+        .stderr(predicate::str::contains("938 bytes"));
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, &message[1..], "-m", "lsb"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn lsb_invalid_channels() {
+    encode_command()
+        .arg(temp_copy(VALID_FILE))
+        .args([VALID_CHUNK_TYPE, MESSAGE, "-m", "lsb", "--channels", "xyz"])
+        .assert()
+        .failure()
+        .code(2);
+}
+
+#[test]
+fn lsb_with_key() {
+    let path = temp_copy(VALID_FILE);
+    encode_command()
+        .arg(&path)
+        .args([
+            VALID_CHUNK_TYPE,
+            MESSAGE,
+            "-m",
+            "lsb",
+            "--key",
+            "passphrase",
+        ])
+        .assert()
+        .success();
+
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "-m", "lsb", "--key", "passphrase"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(MESSAGE));
+    for key in [&["--key", "other passphrase"][..], &[]] {
+        decode_command()
+            .arg(&path)
+            .args([VALID_CHUNK_TYPE, "-m", "lsb"])
+            .args(key)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("No chunk with type"));
+    }
+}
+
+#[test]
+fn encode_and_decode_matrix() {
+    let path = temp_copy(VALID_FILE);
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, MESSAGE, "-m", "matrix", "--code", "4"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^changed \d+ samples\n$").unwrap());
+
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "-m", "matrix", "--code", "4"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(MESSAGE));
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "-m", "lsb"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No chunk with type"));
+}
+
+#[test]
+fn matrix_invalid_code() {
+    encode_command()
+        .arg(temp_copy(VALID_FILE))
+        .args([VALID_CHUNK_TYPE, MESSAGE, "-m", "matrix", "--code", "0"])
+        .assert()
+        .failure()
+        .code(20);
+}
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use predicates::prelude::predicate;
+
+use pngme::{Chunk, ChunkType, ColorType, IdatOptions, Ihdr, Image, Png};
+
+use crate::common::{
+    decode_command, encode_command, gen_not_existing_file, temp_copy, MESSAGE, VALID_CHUNK_TYPE,
+    VALID_FILE,
+};
+
+mod common;
+
+/// Write indexed image with 100 distinct palette colors into temporary file
+fn indexed_png() -> PathBuf {
+    let header = Ihdr::new(20, 20, 8, ColorType::Indexed).unwrap();
+    let mut image = Image::new(header).unwrap();
+    for i in 0..image.samples.len() {
+        image.samples.set(i, (i % 100) as u16);
+    }
+    let palette = (0..100u8).flat_map(|i| [i, 2 * i, 255 - i]).collect();
+
+    let mut chunks = vec![header.to_chunk(), Chunk::new(ChunkType::PLTE, palette)];
+    chunks.extend(image.to_idat_chunks(&IdatOptions::default()).unwrap());
+    chunks.push(Chunk::new(ChunkType::IEND, vec![]));
+
+    let path = std::env::temp_dir().join(format!("{}.png", gen_not_existing_file()));
+    Png::from_chunks(chunks).write_file(&path).unwrap();
+    path
+}
+
+#[test]
+fn encode_and_decode_palette() {
+    let path = indexed_png();
+    let before = Png::from_file(&path).unwrap();
+
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, MESSAGE, "--method", "palette"])
+        .assert()
+        .success();
+
+    let after = Png::from_file(&path).unwrap();
+    assert_eq!(after.chunks().len(), before.chunks().len());
+    assert_ne!(after.palette().unwrap(), before.palette().unwrap());
+
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "-m", "palette"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "secret message: '{}'",
+            MESSAGE
+        )));
+}
+
+#[test]
+fn palette_capacity_exceeded() {
+    // 100! has 525 bits, so palette holds 65 bytes
+    encode_command()
+        .arg(indexed_png())
+        .args([VALID_CHUNK_TYPE, &"x".repeat(54), "-m", "palette"])
+        .assert()
+        .failure()
+        .code(21);
+}
+
+#[test]
+fn palette_needs_indexed_image() {
+    encode_command()
+        .arg(temp_copy(VALID_FILE))
+        .args([VALID_CHUNK_TYPE, MESSAGE, "-m", "palette"])
+        .assert()
+        .failure()
+        .code(20)
+        .stderr(predicate::str::contains("indexed"));
+}
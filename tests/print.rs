@@ -0,0 +1,71 @@
+use predicates::prelude::predicate;
+
+use crate::common::{
+    corrupted_copy, print_command, truncated_copy, INVALID_HEADER_FILE, VALID_ENCODED1, VALID_FILE,
+};
+
+mod common;
+
+#[test]
+fn print_valid() {
+    print_command()
+        .arg(VALID_FILE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Type: IHDR"))
+        .stdout(predicate::str::contains("Type: IEND"));
+}
+
+#[test]
+fn print_header_info() {
+    print_command()
+        .arg(VALID_ENCODED1)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Width: 50"))
+        .stdout(predicate::str::contains("Height: 50"))
+        .stdout(predicate::str::contains("Color type: RGB with alpha (6)"))
+        .stdout(predicate::str::contains("Interlace method: none (0)"));
+}
+
+#[test]
+fn dies_print_invalid_header() {
+    print_command()
+        .arg(INVALID_HEADER_FILE)
+        .assert()
+        .failure()
+        .code(5);
+}
+
+#[test]
+fn print_lenient_truncated() {
+    // first byte of crc of the last chunk cut off
+    let path = corrupted_copy(VALID_ENCODED1, 3);
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() - 4]).unwrap();
+
+    print_command().arg(&path).assert().failure().code(6);
+    print_command()
+        .arg(&path)
+        .arg("-l")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Type: ruSt"))
+        .stderr(predicate::str::contains("error [truncated-chunk]"));
+}
+
+#[test]
+fn print_lenient_truncated_crc() {
+    // two bytes of crc of IEND cut off
+    let path = truncated_copy(VALID_FILE, 2);
+
+    print_command()
+        .arg(&path)
+        .arg("-l")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Length: 0\n   Type: IEND\n   Data: 0 bytes",
+        ))
+        .stderr(predicate::str::contains("crc is missing"));
+}
@@ -0,0 +1,105 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use predicates::prelude::predicate;
+
+use crate::common::{
+    decode_command, encode_command, gen_not_existing_file, keygen_command, temp_copy, MESSAGE,
+    VALID_CHUNK_TYPE, VALID_FILE,
+};
+
+mod common;
+
+/// Generate key pair in temporary directory, returning path of secret key file
+fn key_pair() -> PathBuf {
+    let path = env::temp_dir().join(gen_not_existing_file());
+    keygen_command()
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("fingerprint"));
+    path
+}
+
+fn public_key(secret: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.pub", secret.display()))
+}
+
+#[test]
+fn encrypt_for_multiple_recipients() {
+    let alice = key_pair();
+    let bob = key_pair();
+    let path = temp_copy(VALID_FILE);
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, MESSAGE, "--recipient"])
+        .arg(public_key(&alice))
+        .arg("--recipient")
+        .arg(public_key(&bob))
+        .assert()
+        .success();
+
+    for identity in [&alice, &bob] {
+        decode_command()
+            .arg(&path)
+            .args([VALID_CHUNK_TYPE, "--identity"])
+            .arg(identity)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!(
+                "secret message: '{}'",
+                MESSAGE
+            )));
+    }
+    decode_command()
+        .arg(&path)
+        .arg(VALID_CHUNK_TYPE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("use --identity"));
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "--identity"])
+        .arg(key_pair())
+        .assert()
+        .failure()
+        .code(26)
+        .stderr(predicate::str::contains("not encrypted for this identity"));
+}
+
+#[test]
+fn keygen_does_not_overwrite() {
+    let path = key_pair();
+
+    keygen_command().arg(&path).assert().failure().code(3);
+}
+
+#[test]
+fn invalid_key_file() {
+    let secret = key_pair();
+    let path = temp_copy(VALID_FILE);
+
+    // secret key is not a public key
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, MESSAGE, "--recipient"])
+        .arg(&secret)
+        .assert()
+        .failure()
+        .code(25)
+        .stderr(predicate::str::contains("Invalid key"));
+}
+
+#[test]
+fn recipient_conflicts_with_passphrase() {
+    let secret = key_pair();
+
+    encode_command()
+        .arg(temp_copy(VALID_FILE))
+        .args([VALID_CHUNK_TYPE, MESSAGE, "--encrypt", "--passphrase", "pw"])
+        .arg("--recipient")
+        .arg(public_key(&secret))
+        .assert()
+        .failure()
+        .code(2);
+}
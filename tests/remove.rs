@@ -1,67 +1,139 @@
 use std::fs;
+use std::path::Path;
 
 use assert_cmd::assert::Assert;
 
+use pngme::Png;
+
 use crate::common::{
-    remove_command, DIFFERENT_MESSAGE, EMPTY_FILE, MESSAGE, VALID_CHUNK_TYPE,
-    VALID_DIFFERENT_CHUNK_TYPE, VALID_ENCODED1, VALID_ENCODED2, VALID_ENCODED2_DIFFERENT,
-    VALID_FILE, TestResult
+    remove_command, temp_copy, DIFFERENT_MESSAGE, EMPTY_FILE, MESSAGE, SECOND_MESSAGE,
+    VALID_CHUNK_TYPE, VALID_DIFFERENT_CHUNK_TYPE, VALID_ENCODED1, VALID_ENCODED2,
+    VALID_ENCODED2_DIFFERENT, VALID_FILE,
 };
-const TEMP_FILE: &str = "tests/temp.png";
+
 mod common;
-fn remove(filename: &str, chunk: &str) -> Assert {
-    remove_command().arg(filename).arg(chunk).assert()
+
+fn remove(filename: &Path, chunk: &str, flags: &[&str]) -> Assert {
+    remove_command()
+        .arg(filename)
+        .arg(chunk)
+        .args(flags)
+        .assert()
 }
 
-fn remove_existing(filename: &str, chunk_type: &str, message: &str, copy_to_temp: bool) -> TestResult {
-    if copy_to_temp { 
-        fs::copy(filename, TEMP_FILE)?;
-    }
-    remove(filename, chunk_type)
+fn remove_existing(filename: &Path, chunk_type: &str, message: &str) {
+    remove(filename, chunk_type, &[])
         .success()
         .stdout(predicates::str::contains(format!(
             "deleted chunk with message '{}'",
             message
         )));
-    Ok(())
 }
 
-fn remove_not_existing(filename: &str, chunk_type: &str) {
-    remove(filename, chunk_type)
+fn remove_not_existing(filename: &Path, chunk_type: &str) {
+    remove(filename, chunk_type, &[])
         .failure()
+        .code(10)
         .stderr(predicates::str::contains(format!(
             "No chunk with type '{}' was found",
             chunk_type
         )));
 }
 
+fn messages(filename: &Path, chunk_type: &str) -> Vec<String> {
+    Png::from_file(filename)
+        .unwrap()
+        .chunks()
+        .iter()
+        .filter(|chunk| chunk.chunk_type().to_string() == chunk_type)
+        .map(|chunk| chunk.data_as_string().unwrap())
+        .collect()
+}
+
 #[test]
-fn remove_valid() -> TestResult {
-    remove_existing(VALID_ENCODED1, VALID_CHUNK_TYPE, MESSAGE,true)
+fn remove_valid() {
+    let file = temp_copy(VALID_ENCODED1);
+    remove_existing(&file, VALID_CHUNK_TYPE, MESSAGE);
+
+    assert!(messages(&file, VALID_CHUNK_TYPE).is_empty());
+    fs::remove_file(file).unwrap();
 }
 
 #[test]
-fn remove_2_valid() -> TestResult {
-    remove_existing(VALID_ENCODED2_DIFFERENT, VALID_CHUNK_TYPE, MESSAGE,true)?;
-    remove_existing(
-        TEMP_FILE,
-        VALID_DIFFERENT_CHUNK_TYPE,
-        DIFFERENT_MESSAGE,false
-    )
+fn remove_2_valid() {
+    let file = temp_copy(VALID_ENCODED2_DIFFERENT);
+    remove_existing(&file, VALID_CHUNK_TYPE, MESSAGE);
+    remove_existing(&file, VALID_DIFFERENT_CHUNK_TYPE, DIFFERENT_MESSAGE);
+
+    assert!(messages(&file, VALID_CHUNK_TYPE).is_empty());
+    assert!(messages(&file, VALID_DIFFERENT_CHUNK_TYPE).is_empty());
+    fs::remove_file(file).unwrap();
 }
 
 #[test]
 fn dies_remove_invalid() {
-    remove_not_existing(VALID_FILE, VALID_CHUNK_TYPE);
+    remove_not_existing(Path::new(VALID_FILE), VALID_CHUNK_TYPE);
 }
 
 #[test]
-fn remove_first_from_two_valid() -> TestResult{
-    remove_existing(VALID_ENCODED2, VALID_CHUNK_TYPE, MESSAGE,true)
+fn remove_first_from_two_valid() {
+    let file = temp_copy(VALID_ENCODED2);
+    remove_existing(&file, VALID_CHUNK_TYPE, MESSAGE);
+
+    assert_eq!(messages(&file, VALID_CHUNK_TYPE), [SECOND_MESSAGE]);
+    fs::remove_file(file).unwrap();
 }
+
+#[test]
+fn remove_all_from_two_valid() {
+    let file = temp_copy(VALID_ENCODED2);
+    remove(&file, VALID_CHUNK_TYPE, &["--all"])
+        .success()
+        .stdout(predicates::str::contains(MESSAGE))
+        .stdout(predicates::str::contains(SECOND_MESSAGE));
+
+    assert!(messages(&file, VALID_CHUNK_TYPE).is_empty());
+    fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn remove_by_index() {
+    let file = temp_copy(VALID_ENCODED2);
+    remove(&file, VALID_CHUNK_TYPE, &["--index", "1"])
+        .success()
+        .stdout(predicates::str::contains(SECOND_MESSAGE));
+
+    assert_eq!(messages(&file, VALID_CHUNK_TYPE), [MESSAGE]);
+    fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn remove_by_message() {
+    let file = temp_copy(VALID_ENCODED2);
+    remove(&file, VALID_CHUNK_TYPE, &["--message", SECOND_MESSAGE]).success();
+
+    assert_eq!(messages(&file, VALID_CHUNK_TYPE), [MESSAGE]);
+    fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn remove_to_output_file() {
+    let file = temp_copy(VALID_ENCODED1);
+    let output = file.with_extension("out.png");
+    remove(&file, VALID_CHUNK_TYPE, &["-o", output.to_str().unwrap()]).success();
+
+    assert_eq!(messages(&file, VALID_CHUNK_TYPE), [MESSAGE]);
+    assert!(messages(&output, VALID_CHUNK_TYPE).is_empty());
+    fs::remove_file(file).unwrap();
+    fs::remove_file(output).unwrap();
+}
+
 #[test]
 fn dies_remove_from_empty() {
-    remove(EMPTY_FILE, VALID_CHUNK_TYPE)
+    remove(Path::new(EMPTY_FILE), VALID_CHUNK_TYPE, &[])
         .failure()
-        .stderr(predicates::str::contains("Invalid png file data"));
+        .code(4)
+        .stderr(predicates::str::contains(
+            "Valid png must have at least 8 bytes",
+        ));
 }
@@ -0,0 +1,96 @@
+use std::fs;
+
+use predicates::prelude::predicate;
+
+use crate::common::{
+    corrupted_copy, decode_command, repair_command, temp_copy, truncated_copy, SECOND_MESSAGE,
+    VALID_CHUNK_TYPE, VALID_ENCODED2, VALID_FILE,
+};
+
+mod common;
+
+#[test]
+fn repair_nothing() {
+    let path = temp_copy(VALID_FILE);
+
+    repair_command()
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nothing to repair"));
+    assert_eq!(fs::read(&path).unwrap(), fs::read(VALID_FILE).unwrap());
+}
+
+#[test]
+fn repair_crc_in_place() {
+    let path = corrupted_copy(VALID_ENCODED2, 0);
+
+    repair_command()
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("fixed [crc-mismatch]"));
+    decode_command()
+        .arg(&path)
+        .arg(VALID_CHUNK_TYPE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(SECOND_MESSAGE));
+}
+
+#[test]
+fn repair_strips_data_after_iend() {
+    let path = corrupted_copy(VALID_ENCODED2, 0);
+    let output = temp_copy(VALID_FILE);
+
+    repair_command()
+        .arg(&path)
+        .arg("--strip-after-iend")
+        .arg("-o")
+        .arg(&output)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("fixed [after-iend]"));
+    decode_command()
+        .arg(&output)
+        .arg(VALID_CHUNK_TYPE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No chunk with type"));
+}
+
+#[test]
+fn repair_keeps_critical_crc() {
+    // crc of IEND
+    let path = temp_copy(VALID_FILE);
+    let mut bytes = fs::read(&path).unwrap();
+    let len = bytes.len();
+    bytes[len - 1] ^= 0xff;
+    fs::write(&path, bytes).unwrap();
+
+    repair_command()
+        .arg(&path)
+        .args(["--crc", "ancillary"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("not fixed error [crc-mismatch]"))
+        .stdout(predicate::str::contains("Nothing to repair"));
+}
+
+#[test]
+fn repair_truncated() {
+    // inside IDAT data and inside crc of IEND
+    for cut in [4788 - 4700, 2] {
+        let path = truncated_copy(VALID_FILE, cut);
+        let output = temp_copy(VALID_FILE);
+
+        repair_command()
+            .arg(&path)
+            .arg("-o")
+            .arg(&output)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("fixed [truncated-chunk]"));
+        common::validate_command().arg(&output).assert().success();
+    }
+}
@@ -0,0 +1,174 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use pngme::{Chunk, Envelope, Png};
+use predicates::prelude::predicate;
+
+use crate::common::{
+    decode_command, encode_command, gen_not_existing_file, keygen_command, temp_copy,
+    verify_command, MESSAGE, VALID_CHUNK_TYPE, VALID_DIFFERENT_CHUNK_TYPE, VALID_FILE,
+};
+
+mod common;
+
+/// Generate signing key pair in temporary directory, returning path of secret key file and
+/// printed fingerprint
+fn signing_key() -> (PathBuf, String) {
+    let path = env::temp_dir().join(gen_not_existing_file());
+    let output = keygen_command()
+        .arg(&path)
+        .arg("--signing")
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    let fingerprint = stdout
+        .split("(fingerprint ")
+        .nth(1)
+        .and_then(|rest| rest.split(')').next())
+        .unwrap()
+        .to_string();
+    (path, fingerprint)
+}
+
+fn public_key(secret: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.pub", secret.display()))
+}
+
+fn signed_copy(key: &Path) -> PathBuf {
+    let path = temp_copy(VALID_FILE);
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, MESSAGE, "--sign"])
+        .arg(key)
+        .assert()
+        .success();
+    path
+}
+
+#[test]
+fn sign_and_verify_on_decode() {
+    let (key, _) = signing_key();
+    let path = signed_copy(&key);
+
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "--verify"])
+        .arg(public_key(&key))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("signature: valid signature by"))
+        .stdout(predicate::str::contains(format!(
+            "secret message: '{}'",
+            MESSAGE
+        )));
+    decode_command()
+        .arg(&path)
+        .arg(VALID_CHUNK_TYPE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "secret message: '{}'",
+            MESSAGE
+        )));
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "--verify"])
+        .arg(public_key(&signing_key().0))
+        .assert()
+        .failure()
+        .code(27)
+        .stdout(predicate::str::contains("signature: invalid signature"));
+}
+
+#[test]
+fn verify_unsigned_message() {
+    let path = temp_copy(VALID_FILE);
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, MESSAGE])
+        .assert()
+        .success();
+
+    decode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, "--verify"])
+        .arg(public_key(&signing_key().0))
+        .assert()
+        .failure()
+        .code(27)
+        .stdout(predicate::str::contains("signature: unsigned"));
+}
+
+#[test]
+fn verify_command_prints_signers() {
+    let (key, fingerprint) = signing_key();
+    let path = signed_copy(&key);
+    encode_command()
+        .arg(&path)
+        .args([VALID_DIFFERENT_CHUNK_TYPE, MESSAGE])
+        .assert()
+        .success();
+
+    let output = verify_command().arg(&path).assert().success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert!(
+        stdout.contains(&format!(
+            "{}: valid signature by {}",
+            VALID_CHUNK_TYPE, fingerprint
+        )),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("1 signed chunks, 0 invalid"));
+    assert!(!stdout.contains(VALID_DIFFERENT_CHUNK_TYPE));
+}
+
+#[test]
+fn verify_command_detects_tampering() {
+    let path = signed_copy(&signing_key().0);
+    // flip the last message byte, keeping envelope checksum valid
+    let mut png = Png::from_file(&path).unwrap();
+    let index = png.chunks().len() - 2;
+    let chunk = &png.chunks()[index];
+    let (envelope, payload) = Envelope::open(chunk.data()).unwrap();
+    let mut payload = payload.to_vec();
+    *payload.last_mut().unwrap() ^= 1;
+    let tampered = Chunk::enveloped(*chunk.chunk_type(), &envelope, &payload).unwrap();
+    png.replace_chunk(index, tampered).unwrap();
+    png.write_file(&path).unwrap();
+
+    verify_command()
+        .arg(&path)
+        .assert()
+        .failure()
+        .code(27)
+        .stdout(predicate::str::contains("invalid signature"));
+}
+
+#[test]
+fn sign_encrypted_message() {
+    let (key, _) = signing_key();
+    let path = temp_copy(VALID_FILE);
+    encode_command()
+        .arg(&path)
+        .args([VALID_CHUNK_TYPE, MESSAGE, "--encrypt", "--passphrase", "pw"])
+        .arg("--sign")
+        .arg(&key)
+        .assert()
+        .success();
+
+    decode_command()
+        .arg(&path)
+        .args([
+            VALID_CHUNK_TYPE,
+            "--decrypt",
+            "--passphrase",
+            "pw",
+            "--verify",
+        ])
+        .arg(public_key(&key))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("valid signature"))
+        .stdout(predicate::str::contains(MESSAGE));
+}
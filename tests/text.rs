@@ -0,0 +1,116 @@
+use predicates::prelude::predicate;
+
+use crate::common::{temp_copy, text_command, VALID_FILE};
+
+mod common;
+
+#[test]
+fn add_and_list_text() {
+    let path = temp_copy(VALID_FILE);
+
+    text_command("add")
+        .arg(&path)
+        .args(["Title", "dice"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("added tEXt 'Title': 'dice'"));
+    text_command("add")
+        .arg(&path)
+        .args(["Description", "seven dice", "--kind", "ztxt"])
+        .assert()
+        .success();
+    text_command("add")
+        .arg(&path)
+        .args([
+            "Title", "kości", "-k", "itxt", "-c", "-l", "pl", "-t", "Tytuł",
+        ])
+        .assert()
+        .success();
+
+    text_command("list")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("5: tEXt 'Title': 'dice'"))
+        .stdout(predicate::str::contains(
+            "6: zTXt 'Description': 'seven dice'",
+        ))
+        .stdout(predicate::str::contains(
+            "7: iTXt 'Title' (language 'pl', translated 'Tytuł', compressed): 'kości'",
+        ));
+}
+
+#[test]
+fn edit_and_delete_text() {
+    let path = temp_copy(VALID_FILE);
+    text_command("add")
+        .arg(&path)
+        .args(["Author", "someone"])
+        .assert()
+        .success();
+
+    text_command("edit")
+        .arg(&path)
+        .args(["Author", "someone else"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("edited tEXt 'Author'"));
+    text_command("list")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("'someone else'"));
+
+    text_command("delete")
+        .arg(&path)
+        .arg("Author")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "deleted tEXt chunk with keyword 'Author' at index 5",
+        ));
+    text_command("list")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No text chunks were found"));
+}
+
+#[test]
+fn dies_add_invalid_text() {
+    let path = temp_copy(VALID_FILE);
+
+    text_command("add")
+        .arg(&path)
+        .args(["Title", "kości"])
+        .assert()
+        .failure()
+        .code(16)
+        .stderr(predicate::str::contains("not a Latin-1 character"));
+    text_command("add")
+        .arg(&path)
+        .args(["", "empty keyword"])
+        .assert()
+        .failure()
+        .code(16);
+    text_command("add")
+        .arg(&path)
+        .args(["Title", "text", "-l", "en"])
+        .assert()
+        .failure()
+        .code(16);
+}
+
+#[test]
+fn dies_edit_missing_keyword() {
+    text_command("edit")
+        .args([VALID_FILE, "Title", "text"])
+        .assert()
+        .failure()
+        .code(17);
+    text_command("delete")
+        .args([VALID_FILE, "Title"])
+        .assert()
+        .failure()
+        .code(17);
+}
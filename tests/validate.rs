@@ -0,0 +1,58 @@
+use predicates::prelude::predicate;
+
+use crate::common::{validate_command, INVALID_HEADER_FILE, VALID_ENCODED1, VALID_FILE};
+
+mod common;
+
+#[test]
+fn validate_valid() {
+    validate_command()
+        .arg(VALID_FILE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 errors, 0 warnings"));
+}
+
+#[test]
+fn validate_valid_json() {
+    validate_command()
+        .args([VALID_FILE, "--format", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"valid\":true"))
+        .stdout(predicate::str::contains("\"issues\":[]"));
+}
+
+#[test]
+fn dies_validate_chunk_after_iend() {
+    validate_command()
+        .arg(VALID_ENCODED1)
+        .assert()
+        .failure()
+        .code(15)
+        .stdout(predicate::str::contains(
+            "error [chunk-after-iend] chunk 7 at offset 4803",
+        ));
+}
+
+#[test]
+fn dies_validate_json() {
+    validate_command()
+        .args([VALID_ENCODED1, "-f", "json"])
+        .assert()
+        .failure()
+        .code(15)
+        .stdout(predicate::str::contains("\"valid\":false"))
+        .stdout(predicate::str::contains(
+            "{\"severity\":\"error\",\"code\":\"chunk-after-iend\",\"index\":7,\"offset\":4803,",
+        ));
+}
+
+#[test]
+fn dies_validate_invalid_header() {
+    validate_command()
+        .arg(INVALID_HEADER_FILE)
+        .assert()
+        .failure()
+        .code(5);
+}